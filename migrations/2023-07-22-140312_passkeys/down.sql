@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE passkey_challenge;
+DROP TABLE passkey;
\ No newline at end of file
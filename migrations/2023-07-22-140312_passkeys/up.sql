@@ -0,0 +1,30 @@
+-- Your SQL goes here
+CREATE TABLE passkey (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    name VARCHAR(64) NOT NULL,
+    credential TEXT NOT NULL,
+    last_used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP,
+    deleted_at TIMESTAMP,
+    CONSTRAINT fk_user
+      FOREIGN KEY(user_id) 
+	  REFERENCES "user"(id)
+);
+
+CREATE TABLE passkey_challenge (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    token_hash VARCHAR(64) NOT NULL,
+    state TEXT NOT NULL,
+    valid_until TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP,
+    deleted_at TIMESTAMP,
+    CONSTRAINT fk_user
+      FOREIGN KEY(user_id) 
+	  REFERENCES "user"(id),
+    CONSTRAINT passkey_challenge_unique_token_hash
+      UNIQUE (token_hash)
+);
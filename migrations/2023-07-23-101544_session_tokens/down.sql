@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "session"
+DROP CONSTRAINT session_unique_token_hash;
+
+ALTER TABLE "session"
+    DROP COLUMN token_hash;
\ No newline at end of file
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+-- sessions keyed by serial id can not be carried over
+DELETE FROM "session";
+
+ALTER TABLE "session"
+    ADD COLUMN token_hash VARCHAR(64) NOT NULL;
+
+ALTER TABLE "session"
+ADD CONSTRAINT session_unique_token_hash
+UNIQUE (token_hash);
\ No newline at end of file
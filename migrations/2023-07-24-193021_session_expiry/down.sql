@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "session"
+    DROP COLUMN remember;
+
+ALTER TABLE "session"
+    DROP COLUMN expires_at;
\ No newline at end of file
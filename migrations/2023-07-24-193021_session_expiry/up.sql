@@ -0,0 +1,11 @@
+-- Your SQL goes here
+ALTER TABLE "session"
+    ADD COLUMN expires_at TIMESTAMP;
+
+UPDATE "session" SET expires_at = valid_until;
+
+ALTER TABLE "session"
+    ALTER COLUMN expires_at SET NOT NULL;
+
+ALTER TABLE "session"
+    ADD COLUMN remember BOOLEAN NOT NULL DEFAULT FALSE;
\ No newline at end of file
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "session"
+    DROP COLUMN last_seen_at;
+
+ALTER TABLE "session"
+    DROP COLUMN ip;
+
+ALTER TABLE "session"
+    DROP COLUMN user_agent;
\ No newline at end of file
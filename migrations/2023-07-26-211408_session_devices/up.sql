@@ -0,0 +1,14 @@
+-- Your SQL goes here
+ALTER TABLE "session"
+    ADD COLUMN user_agent VARCHAR(512);
+
+ALTER TABLE "session"
+    ADD COLUMN ip VARCHAR(64);
+
+ALTER TABLE "session"
+    ADD COLUMN last_seen_at TIMESTAMP;
+
+UPDATE "session" SET last_seen_at = COALESCE(updated_at, created_at);
+
+ALTER TABLE "session"
+    ALTER COLUMN last_seen_at SET NOT NULL;
\ No newline at end of file
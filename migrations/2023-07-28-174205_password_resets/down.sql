@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE password_reset;
+
+ALTER TABLE "user"
+    DROP COLUMN email;
\ No newline at end of file
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+ALTER TABLE "user"
+    ADD COLUMN email VARCHAR(255);
+
+CREATE TABLE password_reset (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    token_hash VARCHAR(64) NOT NULL,
+    valid_until TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP,
+    deleted_at TIMESTAMP,
+    CONSTRAINT fk_user
+      FOREIGN KEY(user_id) 
+	  REFERENCES "user"(id),
+    CONSTRAINT password_reset_unique_token_hash
+      UNIQUE (token_hash)
+);
\ No newline at end of file
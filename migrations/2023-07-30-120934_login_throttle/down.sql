@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE audit_log;
+DROP TABLE login_attempt;
\ No newline at end of file
@@ -0,0 +1,24 @@
+-- Your SQL goes here
+CREATE TABLE login_attempt (
+    id SERIAL PRIMARY KEY,
+    username VARCHAR(48) NOT NULL,
+    ip VARCHAR(64),
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP,
+    deleted_at TIMESTAMP
+);
+
+CREATE INDEX login_attempt_username_created_at ON login_attempt (username, created_at);
+CREATE INDEX login_attempt_ip_created_at ON login_attempt (ip, created_at);
+
+CREATE TABLE audit_log (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER,
+    event VARCHAR(64) NOT NULL,
+    detail TEXT NOT NULL,
+    ip VARCHAR(64),
+    created_at TIMESTAMP NOT NULL,
+    CONSTRAINT fk_user
+      FOREIGN KEY(user_id) 
+	  REFERENCES "user"(id)
+);
\ No newline at end of file
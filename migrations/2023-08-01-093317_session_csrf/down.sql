@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "session"
+    DROP COLUMN csrf_token;
\ No newline at end of file
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+ALTER TABLE "session"
+    ADD COLUMN csrf_token VARCHAR(64);
+
+UPDATE "session" SET csrf_token = md5(random()::text || id::text);
+
+ALTER TABLE "session"
+    ALTER COLUMN csrf_token SET NOT NULL;
\ No newline at end of file
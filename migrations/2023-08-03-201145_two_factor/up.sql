@@ -0,0 +1,36 @@
+-- Your SQL goes here
+ALTER TABLE "user"
+    ADD COLUMN totp_secret VARCHAR(64);
+
+ALTER TABLE "user"
+    ADD COLUMN totp_enabled_at TIMESTAMP;
+
+CREATE TABLE recovery_code (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    code_hash VARCHAR(64) NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP,
+    deleted_at TIMESTAMP,
+    CONSTRAINT fk_user
+      FOREIGN KEY(user_id) 
+	  REFERENCES "user"(id)
+);
+
+CREATE TABLE pending_login (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    token_hash VARCHAR(64) NOT NULL,
+    remember BOOLEAN NOT NULL DEFAULT FALSE,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    valid_until TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP,
+    deleted_at TIMESTAMP,
+    CONSTRAINT fk_user
+      FOREIGN KEY(user_id) 
+	  REFERENCES "user"(id),
+    CONSTRAINT pending_login_unique_token_hash
+      UNIQUE (token_hash)
+);
\ No newline at end of file
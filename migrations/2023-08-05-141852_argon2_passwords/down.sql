@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "user"
+    ALTER COLUMN password TYPE VARCHAR(64);
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- argon2id hashes spell out their parameters and run longer than bcrypt ones
+ALTER TABLE "user"
+    ALTER COLUMN password TYPE VARCHAR(255);
\ No newline at end of file
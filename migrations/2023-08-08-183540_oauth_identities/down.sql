@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE oauth_state;
+
+DROP TABLE identity;
\ No newline at end of file
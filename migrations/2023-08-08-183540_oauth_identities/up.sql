@@ -0,0 +1,29 @@
+-- Your SQL goes here
+CREATE TABLE identity (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    provider VARCHAR(48) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    email VARCHAR(255),
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP,
+    deleted_at TIMESTAMP,
+    CONSTRAINT fk_user
+      FOREIGN KEY(user_id) 
+	  REFERENCES "user"(id),
+    CONSTRAINT identity_unique_provider_subject
+      UNIQUE (provider, subject)
+);
+
+CREATE TABLE oauth_state (
+    id SERIAL PRIMARY KEY,
+    provider VARCHAR(48) NOT NULL,
+    state_hash VARCHAR(64) NOT NULL,
+    code_verifier VARCHAR(128) NOT NULL,
+    valid_until TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP,
+    deleted_at TIMESTAMP,
+    CONSTRAINT oauth_state_unique_state_hash
+      UNIQUE (state_hash)
+);
\ No newline at end of file
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE identity
+    ALTER COLUMN subject TYPE VARCHAR(255);
+
+ALTER TABLE oauth_state
+    DROP COLUMN authorization_endpoint;
+
+ALTER TABLE oauth_state
+    DROP COLUMN me;
\ No newline at end of file
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+ALTER TABLE oauth_state
+    ADD COLUMN me VARCHAR(512);
+
+ALTER TABLE oauth_state
+    ADD COLUMN authorization_endpoint VARCHAR(512);
+
+ALTER TABLE identity
+    ALTER COLUMN subject TYPE VARCHAR(512);
\ No newline at end of file
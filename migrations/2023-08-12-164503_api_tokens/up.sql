@@ -0,0 +1,17 @@
+-- Your SQL goes here
+CREATE TABLE api_token (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    name VARCHAR(64) NOT NULL,
+    token_hash VARCHAR(64) NOT NULL,
+    scope VARCHAR(16) NOT NULL,
+    last_used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP,
+    deleted_at TIMESTAMP,
+    CONSTRAINT fk_user
+      FOREIGN KEY(user_id) 
+	  REFERENCES "user"(id),
+    CONSTRAINT api_token_unique_token_hash
+      UNIQUE (token_hash)
+);
\ No newline at end of file
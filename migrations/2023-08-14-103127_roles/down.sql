@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "user"
+    DROP COLUMN suspended_reason;
+
+ALTER TABLE "user"
+    DROP COLUMN role;
\ No newline at end of file
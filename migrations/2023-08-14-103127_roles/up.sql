@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE "user"
+    ADD COLUMN role VARCHAR(16) NOT NULL DEFAULT 'user';
+
+ALTER TABLE "user"
+    ADD COLUMN suspended_reason VARCHAR(255);
\ No newline at end of file
@@ -0,0 +1,17 @@
+-- Your SQL goes here
+CREATE TABLE invite (
+    id SERIAL PRIMARY KEY,
+    issuer_user_id INTEGER NOT NULL,
+    code VARCHAR(32) NOT NULL,
+    max_uses INTEGER NOT NULL,
+    uses INTEGER NOT NULL DEFAULT 0,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP,
+    deleted_at TIMESTAMP,
+    CONSTRAINT fk_user
+      FOREIGN KEY(issuer_user_id) 
+	  REFERENCES "user"(id),
+    CONSTRAINT invite_unique_code
+      UNIQUE (code)
+);
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE page
+    DROP COLUMN visibility;
\ No newline at end of file
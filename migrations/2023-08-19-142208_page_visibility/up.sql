@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE page
+    ADD COLUMN visibility VARCHAR(16) NOT NULL DEFAULT 'public';
\ No newline at end of file
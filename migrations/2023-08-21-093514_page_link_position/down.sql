@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE page_link
+    DROP COLUMN position;
\ No newline at end of file
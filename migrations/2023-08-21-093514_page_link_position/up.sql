@@ -0,0 +1,11 @@
+-- Your SQL goes here
+ALTER TABLE page_link
+    ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+
+UPDATE page_link
+    SET position = numbered.position
+    FROM (
+        SELECT id, ROW_NUMBER() OVER (PARTITION BY page_id ORDER BY created_at, id) - 1 AS position
+        FROM page_link
+    ) AS numbered
+    WHERE page_link.id = numbered.id;
\ No newline at end of file
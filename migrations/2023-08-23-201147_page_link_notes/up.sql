@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE page_link
+    ADD COLUMN notes TEXT NOT NULL DEFAULT '';
\ No newline at end of file
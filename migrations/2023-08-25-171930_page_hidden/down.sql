@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE page
+    DROP COLUMN hidden;
\ No newline at end of file
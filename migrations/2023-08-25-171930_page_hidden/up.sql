@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE page
+    ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT false;
\ No newline at end of file
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "user"
+    DROP COLUMN max_links;
+
+ALTER TABLE "user"
+    DROP COLUMN max_pages;
+
+ALTER TABLE "user"
+    DROP COLUMN quota_plan;
\ No newline at end of file
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+ALTER TABLE "user"
+    ADD COLUMN quota_plan VARCHAR(32);
+
+ALTER TABLE "user"
+    ADD COLUMN max_pages INTEGER;
+
+ALTER TABLE "user"
+    ADD COLUMN max_links INTEGER;
\ No newline at end of file
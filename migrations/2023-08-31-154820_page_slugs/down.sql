@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE page_slug;
+
+ALTER TABLE page
+DROP CONSTRAINT page_unique_slug_user;
+
+ALTER TABLE page
+    DROP COLUMN slug;
\ No newline at end of file
@@ -0,0 +1,32 @@
+-- Your SQL goes here
+ALTER TABLE page
+    ADD COLUMN slug VARCHAR(80) NOT NULL DEFAULT '';
+
+-- names are stored escaped, so entities like &amp; are dropped along with the punctuation
+UPDATE page
+    SET slug = COALESCE(NULLIF(TRIM(BOTH '-' FROM LOWER(REGEXP_REPLACE(name, '(&[a-z]+;|[^a-zA-Z0-9])+', '-', 'g'))), ''), 'group');
+
+-- names that only differ in case or punctuation come out the same
+UPDATE page
+    SET slug = page.slug || '-' || page.id
+    FROM (
+        SELECT id, ROW_NUMBER() OVER (PARTITION BY user_id, slug, deleted_at ORDER BY id) AS n
+        FROM page
+    ) AS numbered
+    WHERE page.id = numbered.id AND numbered.n > 1;
+
+ALTER TABLE page
+ADD CONSTRAINT page_unique_slug_user
+UNIQUE NULLS NOT DISTINCT (slug, user_id, deleted_at);
+
+CREATE TABLE page_slug (
+    id SERIAL PRIMARY KEY,
+    page_id INTEGER NOT NULL,
+    slug VARCHAR(80) NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP,
+    deleted_at TIMESTAMP,
+    CONSTRAINT fk_page
+      FOREIGN KEY(page_id) 
+	  REFERENCES page(id)
+);
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE email_verification;
+
+DROP INDEX user_unique_email;
\ No newline at end of file
@@ -0,0 +1,33 @@
+-- Your SQL goes here
+-- addresses were never unique, so the oldest live account keeps a shared one
+UPDATE "user" AS duplicate
+SET email = NULL
+WHERE duplicate.deleted_at IS NULL
+    AND duplicate.email IS NOT NULL
+    AND EXISTS (
+        SELECT 1 FROM "user" AS original
+        WHERE original.deleted_at IS NULL
+            AND LOWER(original.email) = LOWER(duplicate.email)
+            AND original.id < duplicate.id
+    );
+
+CREATE UNIQUE INDEX user_unique_email
+    ON "user" (LOWER(email))
+    WHERE deleted_at IS NULL;
+
+CREATE TABLE email_verification (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    email VARCHAR(255) NOT NULL,
+    token_hash VARCHAR(64) NOT NULL,
+    valid_until TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP,
+    deleted_at TIMESTAMP,
+    CONSTRAINT fk_user
+      FOREIGN KEY(user_id) 
+	  REFERENCES "user"(id),
+    CONSTRAINT email_verification_unique_token_hash
+      UNIQUE (token_hash)
+);
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE oauth_state
+    DROP COLUMN nonce;
\ No newline at end of file
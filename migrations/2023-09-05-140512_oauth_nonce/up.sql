@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE oauth_state
+    ADD COLUMN nonce VARCHAR(64);
\ No newline at end of file
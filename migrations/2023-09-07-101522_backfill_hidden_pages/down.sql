@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+UPDATE page
+    SET hidden = false
+    FROM audit_log
+    WHERE audit_log.event = 'page_hidden'
+        AND audit_log.user_id = page.user_id
+        AND page.deleted_at IS NOT NULL
+        AND audit_log.created_at BETWEEN page.deleted_at - INTERVAL '1 second'
+            AND page.deleted_at + INTERVAL '1 second';
\ No newline at end of file
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+-- groups moderators took down before page.hidden existed only went to the trash, each
+-- of them was logged against its owner as page_hidden the moment it was deleted
+UPDATE page
+    SET hidden = true
+    FROM audit_log
+    WHERE audit_log.event = 'page_hidden'
+        AND audit_log.user_id = page.user_id
+        AND page.deleted_at IS NOT NULL
+        AND audit_log.created_at BETWEEN page.deleted_at - INTERVAL '1 second'
+            AND page.deleted_at + INTERVAL '1 second';
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE "user"
+    ADD COLUMN totp_last_step BIGINT;
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE page_link
+    ADD COLUMN hidden_reason VARCHAR(255);
\ No newline at end of file
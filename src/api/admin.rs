@@ -0,0 +1,15 @@
+macro_rules! admin {
+    () => {
+        routes::admin::console()
+            .and_then(handlers::admin::console)
+            .or(routes::admin::suspend_user().and_then(handlers::admin::console))
+            .or(routes::admin::change_role().and_then(handlers::admin::console))
+            .or(routes::admin::change_quota().and_then(handlers::admin::console))
+            .or(routes::admin::hide_page().and_then(handlers::admin::console))
+            .or(routes::admin::hide_link().and_then(handlers::admin::console))
+            .recover(handle_rejection)
+            .with(warp::trace::named("admin"))
+    };
+}
+
+pub(crate) use admin;
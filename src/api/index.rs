@@ -1,7 +1,7 @@
 macro_rules! index_api {
     () => {
         routes::index::index()
-            .and(routes::user::authenticate_cookie())
+            .and(routes::user::authenticate())
             .and_then(handlers::index::index_authenticated)
             .or(routes::index::index().and_then(handlers::index::index))
     };
@@ -1,3 +1,4 @@
+pub mod admin;
 pub mod assets;
 pub mod index;
 pub mod link;
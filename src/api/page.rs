@@ -7,18 +7,50 @@ macro_rules! page {
                     .or(routes::page::get().and_then(handlers::page::view))
                     .or(routes::page::get_unauthenticated()
                         .and_then(handlers::page::view_unauthenticated))
+                    .or(routes::page::get_links_authenticated()
+                        .and_then(handlers::page::more_links_authenticated))
+                    .or(routes::page::get_links().and_then(handlers::page::more_links))
+                    .or(routes::page::get_links_unauthenticated()
+                        .and_then(handlers::page::more_links))
                     .or(routes::page::create_link()
                         .and_then(handlers::page::view_authenticated)
                         .recover(handlers::page::handle_create_link_error))
                     .or(routes::page::create()
                         .and_then(handlers::page::view_authenticated)
                         .recover(handlers::page::handle_create_page_error))
-                    .or(routes::page::delete().and_then(handlers::user::profile))
-                    .or(routes::page::delete_link().and_then(handlers::page::view_authenticated))
+                    .or(routes::page::update()
+                        .and_then(handlers::page::view_authenticated)
+                        .recover(handlers::page::handle_update_page_error))
+                    .or(routes::page::delete().and_then(handlers::trash::page_trashed))
+                    .or(routes::page::delete_link().and_then(handlers::trash::link_trashed))
+                    .or(routes::page::reorder_links().and_then(handlers::page::view_authenticated))
+                    .or(routes::page::update_link().and_then(handlers::page::view_authenticated))
+                    .or(routes::page::bulk_links().and_then(handlers::page::links_updated_in_bulk))
+                    .or(routes::page::move_link().and_then(handlers::page::view_authenticated))
                     .recover(handle_rejection),
             )
             .with(warp::trace::named("page"))
     };
 }
 
+// pages by their owner's name and slug, renamed ones redirect to where they are now
+macro_rules! page_slug {
+    () => {
+        warp::path("u")
+            .and(
+                routes::page::get_by_slug_authenticated()
+                    .and_then(handlers::page::view_authenticated)
+                    .or(routes::page::get_by_slug().and_then(handlers::page::view))
+                    .or(routes::page::get_by_slug_unauthenticated()
+                        .and_then(handlers::page::view_unauthenticated))
+                    .or(routes::page::get_by_old_slug().and_then(handlers::page::moved))
+                    .or(routes::page::get_by_old_slug_unauthenticated()
+                        .and_then(handlers::page::moved))
+                    .recover(handle_rejection),
+            )
+            .with(warp::trace::named("page_slug"))
+    };
+}
+
 pub(crate) use page;
+pub(crate) use page_slug;
@@ -7,20 +7,102 @@ macro_rules! user {
                     .or(routes::user::login()
                         .and_then(handlers::user::profile_with_cookie)
                         .recover(handlers::user::handle_login_errors))
+                    .or(routes::user::login_verify()
+                        .and_then(handlers::user::profile_with_cookie)
+                        .recover(handlers::user::handle_two_factor_login_errors))
                     .or(routes::user::signup()
                         .and_then(handlers::user::profile_with_cookie)
                         .recover(handlers::user::handle_signup_errors))
                     .or(routes::user::logout()
                         .and_then(handlers::user::logout)
                         .recover(handlers::user::handle_logout_errors))
+                    // boxed every so often, the full chain is too deep for the compiler to follow
+                    .boxed()
                     .or(routes::user::signup_form()
                         .and(routes::user::authenticate_cookie())
                         .and_then(handlers::user::profile))
-                    .or(routes::user::signup_form().and_then(handlers::user::signup_form))
+                    .or(routes::user::signup_form_with_invite()
+                        .and_then(handlers::user::signup_form))
                     .or(routes::user::login_form()
                         .and(routes::user::authenticate_cookie())
                         .and_then(handlers::user::profile))
                     .or(routes::user::login_form().and_then(handlers::user::login_form))
+                    .or(routes::oauth::providers().and_then(handlers::oauth::providers))
+                    .or(routes::oauth::start().and_then(handlers::oauth::redirect))
+                    .or(routes::oauth::callback()
+                        .and_then(handlers::user::profile_with_cookie)
+                        .recover(handlers::oauth::handle_oauth_errors))
+                    .or(routes::indieauth::start()
+                        .and_then(handlers::indieauth::redirect)
+                        .recover(handlers::indieauth::handle_indieauth_errors))
+                    .or(routes::indieauth::callback()
+                        .and_then(handlers::user::profile_with_cookie)
+                        .recover(handlers::indieauth::handle_indieauth_errors))
+                    .boxed()
+                    .or(routes::passkey::register_start()
+                        .and_then(handlers::passkey::registration_challenge))
+                    .or(routes::passkey::register_finish().and_then(handlers::user::profile))
+                    .or(routes::passkey::login_start().and_then(handlers::passkey::login_challenge))
+                    .or(routes::passkey::login_finish()
+                        .and_then(handlers::user::profile_with_cookie))
+                    .or(routes::passkey::delete().and_then(handlers::user::profile))
+                    .or(routes::api_token::create().and_then(handlers::user::api_token_created))
+                    .or(routes::api_token::delete().and_then(handlers::user::profile))
+                    .boxed()
+                    .or(routes::invite::list().and_then(handlers::invite::list))
+                    .or(routes::invite::create()
+                        .and_then(handlers::invite::list)
+                        .recover(handlers::invite::handle_invite_errors))
+                    .or(routes::invite::delete().and_then(handlers::invite::list))
+                    .or(routes::trash::list().and_then(handlers::trash::list))
+                    .or(routes::trash::restore_page().and_then(handlers::trash::updated))
+                    .or(routes::trash::purge_page().and_then(handlers::trash::updated))
+                    .or(routes::trash::restore_link().and_then(handlers::trash::updated))
+                    .or(routes::trash::purge_link().and_then(handlers::trash::updated))
+                    .or(routes::session::list().and_then(handlers::session::list))
+                    .or(routes::session::delete().and_then(handlers::session::list))
+                    .or(routes::session::delete_all()
+                        .and_then(handlers::session::logout_everywhere))
+                    .boxed()
+                    .or(routes::user::pages().and_then(handlers::user::more_pages))
+                    .or(routes::user::page_options().and_then(handlers::user::more_page_options))
+                    .or(routes::user::settings().and_then(handlers::user::settings))
+                    .or(routes::user::change_password()
+                        .and_then(handlers::user::settings_saved)
+                        .recover(handlers::user::handle_settings_errors))
+                    .or(routes::user::change_username()
+                        .and_then(handlers::user::settings_saved)
+                        .recover(handlers::user::handle_settings_errors))
+                    .or(routes::user::change_email()
+                        .and_then(handlers::user::email_saved)
+                        .recover(handlers::user::handle_settings_errors))
+                    .or(routes::user::verify_email()
+                        .and_then(handlers::user::email_verified)
+                        .recover(handlers::user::handle_email_verification_errors))
+                    .boxed()
+                    .or(routes::user::two_factor_settings()
+                        .and_then(handlers::user::two_factor_settings))
+                    .or(routes::user::two_factor_setup().and_then(handlers::user::two_factor_setup))
+                    .or(routes::user::two_factor_enable()
+                        .and_then(handlers::user::recovery_codes)
+                        .recover(handlers::user::handle_two_factor_errors))
+                    .or(routes::user::two_factor_disable()
+                        .and_then(handlers::user::two_factor_disabled)
+                        .recover(handlers::user::handle_two_factor_errors))
+                    .or(routes::user::regenerate_recovery_codes()
+                        .and_then(handlers::user::recovery_codes)
+                        .recover(handlers::user::handle_two_factor_errors))
+                    .or(routes::user::forgot_password_form()
+                        .and_then(handlers::user::forgot_password_form))
+                    .or(routes::user::forgot_password().and_then(handlers::user::reset_link_sent))
+                    .or(routes::user::reset_password_form()
+                        .and_then(handlers::user::reset_password_form))
+                    .or(routes::user::reset_password()
+                        .and_then(handlers::user::password_reset)
+                        .recover(handlers::user::handle_reset_errors))
+                    .or(routes::user::delete_account()
+                        .and_then(handlers::user::account_deleted)
+                        .recover(handlers::user::handle_settings_errors))
                     .recover(handle_rejection),
             )
             .with(warp::trace::named("user"))
@@ -1,6 +1,10 @@
+use crate::models;
+use argon2::Params;
+use chrono::Duration;
 use dotenv::dotenv;
 use log::info;
 use std::env;
+use std::net::IpAddr;
 
 // how many requests we will allow to process at once
 // all others wait their turn
@@ -8,6 +12,133 @@ const MAX_INFLIGHT_REQUESTS: usize = 100;
 // how many connections can be open an running at one time
 // the rest wait until a permit opens up
 const MAX_CONNS: usize = 100;
+// how long a session survives without any requests
+const SESSION_IDLE_MINUTES: i64 = 60;
+// how long a session survives no matter how active it is
+const SESSION_LIFETIME_HOURS: i64 = 12;
+// how long a "remember me" session survives
+const REMEMBER_ME_DAYS: i64 = 30;
+// failed logins allowed inside the window before a lockout
+const LOGIN_MAX_ATTEMPTS: usize = 5;
+// a single address gets more room since many people may share it
+const LOGIN_MAX_ATTEMPTS_PER_IP: usize = 20;
+const LOGIN_WINDOW_MINUTES: i64 = 15;
+const LOGIN_LOCKOUT_MINUTES: i64 = 15;
+// the first retry waits this long, every one after that twice as long
+const LOGIN_BACKOFF_SECONDS: i64 = 1;
+// where the file mailer drops messages when there is no mail server
+const MAIL_FILE_PATH: &str = "mail.log";
+const SMTP_PORT: u16 = 587;
+// argon2id cost for new password hashes, the owasp minimums by default
+const ARGON2_MEMORY_KIB: u32 = 19 * 1024;
+const ARGON2_ITERATIONS: u32 = 2;
+const ARGON2_PARALLELISM: u32 = 1;
+// enough to learn who someone is and how to reach them
+const OAUTH_SCOPES: &str = "openid email profile";
+// how many invites a user can ever hand out, admins are not limited
+const INVITES_PER_USER: usize = 5;
+// how many accounts a single invite code can create
+const INVITE_MAX_USES: i32 = 1;
+const INVITE_DURATION_DAYS: i64 = 7;
+// the plan everyone is on when QUOTA_PLANS isn't set
+const QUOTA_PLAN: &str = "free";
+const QUOTA_MAX_PAGES: usize = 10;
+const QUOTA_MAX_LINKS: usize = 20;
+
+// who may create an account at /user/signup
+#[derive(Clone, Debug, PartialEq)]
+pub enum SignupMode {
+    Open,
+    InviteOnly,
+    Closed,
+}
+
+#[derive(Clone, Debug)]
+pub enum MailerConfig {
+    Smtp {
+        host: String,
+        port: u16,
+        username: String,
+        password: String,
+        from: String,
+    },
+    File {
+        path: String,
+    },
+}
+
+// an openid connect provider users can sign in with, found at /user/oauth/{slug}
+#[derive(Clone, Debug)]
+pub struct OAuthProviderConfig {
+    pub slug: String,
+    pub name: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub authorize_url: String,
+    pub token_url: String,
+    pub userinfo_url: String,
+    pub scopes: String,
+    // what the provider's id tokens name as their iss
+    pub issuer: String,
+}
+
+impl OAuthProviderConfig {
+    // every setting for a provider lives under OAUTH_{SLUG}_
+    fn from_env(slug: &str) -> Self {
+        let prefix = format!("OAUTH_{}_", slug.to_uppercase());
+        let var = |name: &str| {
+            env::var(format!("{}{}", prefix, name))
+                .unwrap_or_else(|_| panic!("{}{} must be set", prefix, name))
+        };
+
+        OAuthProviderConfig {
+            slug: slug.to_string(),
+            name: env::var(format!("{}NAME", prefix)).unwrap_or(slug.to_string()),
+            client_id: var("CLIENT_ID"),
+            client_secret: var("CLIENT_SECRET"),
+            authorize_url: var("AUTHORIZE_URL"),
+            token_url: var("TOKEN_URL"),
+            userinfo_url: var("USERINFO_URL"),
+            scopes: env::var(format!("{}SCOPES", prefix)).unwrap_or(String::from(OAUTH_SCOPES)),
+            issuer: var("ISSUER"),
+        }
+    }
+}
+
+// what a single user ends up with once their own overrides are applied
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quota {
+    pub plan: String,
+    pub max_pages: usize,
+    pub max_links: usize,
+}
+
+// how many groups, and links per group, a user on this plan can have
+#[derive(Clone, Debug)]
+pub struct QuotaPlanConfig {
+    pub name: String,
+    pub max_pages: usize,
+    pub max_links: usize,
+}
+
+impl QuotaPlanConfig {
+    // every limit for a plan lives under QUOTA_{NAME}_
+    fn from_env(name: &str) -> Self {
+        let prefix = format!("QUOTA_{}_", name.to_uppercase());
+        let var = |limit: &str, default: usize| match env::var(format!("{}{}", prefix, limit)) {
+            Ok(v) => v
+                .parse::<usize>()
+                .unwrap_or_else(|_| panic!("{}{} must be an integer", prefix, limit)),
+            Err(_) => default,
+        };
+
+        QuotaPlanConfig {
+            name: name.to_string(),
+            max_pages: var("MAX_PAGES", QUOTA_MAX_PAGES),
+            max_links: var("MAX_LINKS", QUOTA_MAX_LINKS),
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Config {
@@ -19,6 +150,26 @@ pub struct Config {
     pub tls: bool,
     pub cert_path: Option<String>,
     pub key_path: Option<String>,
+    pub webauthn_rp_id: String,
+    pub webauthn_rp_origin: String,
+    pub session_idle_minutes: i64,
+    pub session_lifetime_hours: i64,
+    pub remember_me_days: i64,
+    pub app_url: String,
+    pub mailer: MailerConfig,
+    pub login_max_attempts: usize,
+    pub login_max_attempts_per_ip: usize,
+    pub login_window_minutes: i64,
+    pub login_lockout_minutes: i64,
+    pub login_backoff_seconds: i64,
+    pub password_params: Params,
+    pub oauth_providers: Vec<OAuthProviderConfig>,
+    pub signup_mode: SignupMode,
+    pub invites_per_user: usize,
+    pub invite_max_uses: i32,
+    pub invite_duration_days: i64,
+    pub quota_plans: Vec<QuotaPlanConfig>,
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 impl Config {
@@ -59,6 +210,186 @@ impl Config {
             key_path = None;
         }
 
+        // passkeys are bound to the domain and origin the browser sees
+        let webauthn_rp_id = env::var("WEBAUTHN_RP_ID").unwrap_or(String::from("localhost"));
+        let webauthn_rp_origin = env::var("WEBAUTHN_RP_ORIGIN")
+            .unwrap_or(format!("https://{}:{}", webauthn_rp_id, app_port));
+
+        let session_idle_minutes = match env::var("SESSION_IDLE_MINUTES") {
+            Ok(m) => m
+                .parse::<i64>()
+                .expect("SESSION_IDLE_MINUTES must be an integer"),
+            Err(_) => SESSION_IDLE_MINUTES,
+        };
+
+        let session_lifetime_hours = match env::var("SESSION_LIFETIME_HOURS") {
+            Ok(h) => h
+                .parse::<i64>()
+                .expect("SESSION_LIFETIME_HOURS must be an integer"),
+            Err(_) => SESSION_LIFETIME_HOURS,
+        };
+
+        let remember_me_days = match env::var("REMEMBER_ME_DAYS") {
+            Ok(d) => d
+                .parse::<i64>()
+                .expect("REMEMBER_ME_DAYS must be an integer"),
+            Err(_) => REMEMBER_ME_DAYS,
+        };
+
+        let login_max_attempts = match env::var("LOGIN_MAX_ATTEMPTS") {
+            Ok(a) => a
+                .parse::<usize>()
+                .expect("LOGIN_MAX_ATTEMPTS must be an integer"),
+            Err(_) => LOGIN_MAX_ATTEMPTS,
+        };
+
+        let login_max_attempts_per_ip = match env::var("LOGIN_MAX_ATTEMPTS_PER_IP") {
+            Ok(a) => a
+                .parse::<usize>()
+                .expect("LOGIN_MAX_ATTEMPTS_PER_IP must be an integer"),
+            Err(_) => LOGIN_MAX_ATTEMPTS_PER_IP,
+        };
+
+        let login_window_minutes = match env::var("LOGIN_WINDOW_MINUTES") {
+            Ok(m) => m
+                .parse::<i64>()
+                .expect("LOGIN_WINDOW_MINUTES must be an integer"),
+            Err(_) => LOGIN_WINDOW_MINUTES,
+        };
+
+        let login_lockout_minutes = match env::var("LOGIN_LOCKOUT_MINUTES") {
+            Ok(m) => m
+                .parse::<i64>()
+                .expect("LOGIN_LOCKOUT_MINUTES must be an integer"),
+            Err(_) => LOGIN_LOCKOUT_MINUTES,
+        };
+
+        let login_backoff_seconds = match env::var("LOGIN_BACKOFF_SECONDS") {
+            Ok(s) => s
+                .parse::<i64>()
+                .expect("LOGIN_BACKOFF_SECONDS must be an integer"),
+            Err(_) => LOGIN_BACKOFF_SECONDS,
+        };
+
+        let argon2_memory_kib = match env::var("ARGON2_MEMORY_KIB") {
+            Ok(m) => m
+                .parse::<u32>()
+                .expect("ARGON2_MEMORY_KIB must be an integer"),
+            Err(_) => ARGON2_MEMORY_KIB,
+        };
+
+        let argon2_iterations = match env::var("ARGON2_ITERATIONS") {
+            Ok(i) => i
+                .parse::<u32>()
+                .expect("ARGON2_ITERATIONS must be an integer"),
+            Err(_) => ARGON2_ITERATIONS,
+        };
+
+        let argon2_parallelism = match env::var("ARGON2_PARALLELISM") {
+            Ok(p) => p
+                .parse::<u32>()
+                .expect("ARGON2_PARALLELISM must be an integer"),
+            Err(_) => ARGON2_PARALLELISM,
+        };
+
+        // fail at startup rather than on the first signup
+        let password_params = Params::new(
+            argon2_memory_kib,
+            argon2_iterations,
+            argon2_parallelism,
+            None,
+        )
+        .expect("ARGON2_* settings are out of range");
+
+        let oauth_providers = match env::var("OAUTH_PROVIDERS") {
+            Ok(providers) => providers
+                .split(',')
+                .map(|slug| slug.trim().to_lowercase())
+                .filter(|slug| !slug.is_empty())
+                .map(|slug| OAuthProviderConfig::from_env(&slug))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+
+        let signup_mode = match env::var("SIGNUP_MODE").as_deref() {
+            Ok("open") | Err(_) => SignupMode::Open,
+            Ok("invite") => SignupMode::InviteOnly,
+            Ok("closed") => SignupMode::Closed,
+            Ok(other) => panic!("SIGNUP_MODE must be open, invite or closed, not {}", other),
+        };
+
+        let invites_per_user = match env::var("INVITES_PER_USER") {
+            Ok(i) => i
+                .parse::<usize>()
+                .expect("INVITES_PER_USER must be an integer"),
+            Err(_) => INVITES_PER_USER,
+        };
+
+        let invite_max_uses = match env::var("INVITE_MAX_USES") {
+            Ok(u) => u
+                .parse::<i32>()
+                .expect("INVITE_MAX_USES must be an integer"),
+            Err(_) => INVITE_MAX_USES,
+        };
+
+        let invite_duration_days = match env::var("INVITE_DURATION_DAYS") {
+            Ok(d) => d
+                .parse::<i64>()
+                .expect("INVITE_DURATION_DAYS must be an integer"),
+            Err(_) => INVITE_DURATION_DAYS,
+        };
+
+        // the first plan is the one new users start on
+        let quota_plans = match env::var("QUOTA_PLANS") {
+            Ok(plans) => plans
+                .split(',')
+                .map(|name| name.trim().to_lowercase())
+                .filter(|name| !name.is_empty())
+                .map(|name| QuotaPlanConfig::from_env(&name))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        let quota_plans = if quota_plans.is_empty() {
+            vec![QuotaPlanConfig::from_env(QUOTA_PLAN)]
+        } else {
+            quota_plans
+        };
+
+        // only these peers may tell us who the client is through x-forwarded-for
+        let trusted_proxies = match env::var("TRUSTED_PROXIES") {
+            Ok(proxies) => proxies
+                .split(',')
+                .map(str::trim)
+                .filter(|proxy| !proxy.is_empty())
+                .map(|proxy| {
+                    proxy
+                        .parse::<IpAddr>()
+                        .expect("TRUSTED_PROXIES must be a list of IP addresses")
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+
+        // links we send out need to know where we live
+        let app_url = env::var("APP_URL").unwrap_or(format!("https://localhost:{}", app_port));
+
+        let mailer = match env::var("MAILER").as_deref() {
+            Ok("smtp") => MailerConfig::Smtp {
+                host: env::var("SMTP_HOST").expect("SMTP_HOST must be set"),
+                port: match env::var("SMTP_PORT") {
+                    Ok(p) => p.parse::<u16>().expect("SMTP_PORT must be a port number"),
+                    Err(_) => SMTP_PORT,
+                },
+                username: env::var("SMTP_USERNAME").expect("SMTP_USERNAME must be set"),
+                password: env::var("SMTP_PASSWORD").expect("SMTP_PASSWORD must be set"),
+                from: env::var("MAIL_FROM").expect("MAIL_FROM must be set"),
+            },
+            Ok("file") | Err(_) => MailerConfig::File {
+                path: env::var("MAIL_FILE_PATH").unwrap_or(String::from(MAIL_FILE_PATH)),
+            },
+            Ok(other) => panic!("MAILER must be smtp or file, not {}", other),
+        };
+
         Config {
             app_addr,
             max_conn,
@@ -68,6 +399,64 @@ impl Config {
             tls,
             cert_path,
             key_path,
+            webauthn_rp_id,
+            webauthn_rp_origin,
+            session_idle_minutes,
+            session_lifetime_hours,
+            remember_me_days,
+            app_url,
+            mailer,
+            login_max_attempts,
+            login_max_attempts_per_ip,
+            login_window_minutes,
+            login_lockout_minutes,
+            login_backoff_seconds,
+            password_params,
+            oauth_providers,
+            signup_mode,
+            invites_per_user,
+            invite_max_uses,
+            invite_duration_days,
+            quota_plans,
+            trusted_proxies,
+        }
+    }
+
+    // (idle timeout, absolute lifetime) for a new or refreshed session
+    pub fn session_durations(&self, remember: bool) -> (Duration, Duration) {
+        if remember {
+            let lifetime = Duration::days(self.remember_me_days);
+            (lifetime, lifetime)
+        } else {
+            (
+                Duration::minutes(self.session_idle_minutes),
+                Duration::hours(self.session_lifetime_hours),
+            )
+        }
+    }
+
+    pub fn oauth_provider(&self, slug: &str) -> Option<&OAuthProviderConfig> {
+        self.oauth_providers
+            .iter()
+            .find(|provider| provider.slug == slug)
+    }
+
+    pub fn quota_plan(&self, name: &str) -> Option<&QuotaPlanConfig> {
+        self.quota_plans.iter().find(|plan| plan.name == name)
+    }
+
+    // a plan that was dropped from the config falls back to the default
+    pub fn quota_for(&self, user: &models::user::User) -> Quota {
+        let plan = user
+            .quota_plan
+            .as_deref()
+            .and_then(|name| self.quota_plan(name))
+            .unwrap_or(&self.quota_plans[0]);
+
+        Quota {
+            plan: plan.name.clone(),
+            max_pages: user.max_pages.map_or(plan.max_pages, |max| max as usize),
+            max_links: user.max_links.map_or(plan.max_links, |max| max as usize),
         }
     }
 }
@@ -80,3 +469,50 @@ pub fn db_test_url() -> String {
     dotenv().ok();
     env::var("DATABASE_URL_TEST").expect("DATABASE_URL must be set")
 }
+
+#[cfg(test)]
+pub fn test_config() -> Config {
+    Config {
+        app_addr: String::from("127.0.0.1:3000"),
+        max_conn: MAX_CONNS,
+        max_reqs: MAX_INFLIGHT_REQUESTS,
+        is_mocking: true,
+        db_path: String::new(),
+        tls: false,
+        cert_path: None,
+        key_path: None,
+        webauthn_rp_id: String::from("localhost"),
+        webauthn_rp_origin: String::from("https://localhost:3000"),
+        session_idle_minutes: SESSION_IDLE_MINUTES,
+        session_lifetime_hours: SESSION_LIFETIME_HOURS,
+        remember_me_days: REMEMBER_ME_DAYS,
+        app_url: String::from("https://localhost:3000"),
+        mailer: MailerConfig::File {
+            path: String::from(MAIL_FILE_PATH),
+        },
+        login_max_attempts: LOGIN_MAX_ATTEMPTS,
+        login_max_attempts_per_ip: LOGIN_MAX_ATTEMPTS_PER_IP,
+        login_window_minutes: LOGIN_WINDOW_MINUTES,
+        login_lockout_minutes: LOGIN_LOCKOUT_MINUTES,
+        login_backoff_seconds: LOGIN_BACKOFF_SECONDS,
+        password_params: Params::default(),
+        oauth_providers: Vec::new(),
+        signup_mode: SignupMode::Open,
+        invites_per_user: INVITES_PER_USER,
+        invite_max_uses: INVITE_MAX_USES,
+        invite_duration_days: INVITE_DURATION_DAYS,
+        quota_plans: vec![
+            QuotaPlanConfig {
+                name: String::from(QUOTA_PLAN),
+                max_pages: QUOTA_MAX_PAGES,
+                max_links: QUOTA_MAX_LINKS,
+            },
+            QuotaPlanConfig {
+                name: String::from("pro"),
+                max_pages: 100,
+                max_links: 200,
+            },
+        ],
+        trusted_proxies: Vec::new(),
+    }
+}
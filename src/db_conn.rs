@@ -24,5 +24,57 @@ impl DbConn {
 
 pub fn establish_test_connection() -> PgConnection {
     let database_url = crate::config::db_test_url();
-    PgConnection::establish(&database_url).expect(&format!("Error connecting to {}", database_url))
+    PgConnection::establish(&database_url)
+        .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
+}
+
+#[cfg(test)]
+const MIGRATIONS: diesel_migrations::EmbeddedMigrations = diesel_migrations::embed_migrations!();
+
+// tests that need rows run against DATABASE_URL_TEST inside a transaction that is
+// rolled back, and are skipped when no test database is configured
+#[cfg(test)]
+pub fn test_connection() -> Option<PgConnection> {
+    use diesel_migrations::MigrationHarness;
+    use std::sync::Once;
+
+    static MIGRATE: Once = Once::new();
+
+    dotenv::dotenv().ok();
+    if std::env::var("DATABASE_URL_TEST").is_err() {
+        eprintln!("DATABASE_URL_TEST is not set, skipping");
+        return None;
+    }
+    let mut conn = establish_test_connection();
+    MIGRATE.call_once(|| {
+        conn.run_pending_migrations(MIGRATIONS)
+            .expect("Error migrating the test database");
+    });
+    Some(conn)
+}
+
+// handlers take their connection from the context's pool, so route tests get a pool of
+// one connection that sits in a test transaction and is rolled back when it is dropped
+#[cfg(test)]
+pub fn test_pool() -> Option<DbConn> {
+    test_connection()?;
+    let manager = ConnectionManager::<PgConnection>::new(crate::config::db_test_url());
+    let pool = Pool::builder()
+        .max_size(1)
+        .connection_customizer(Box::new(TestTransaction))
+        .build(manager)
+        .unwrap();
+    Some(DbConn { pool })
+}
+
+#[cfg(test)]
+#[derive(Debug)]
+struct TestTransaction;
+
+#[cfg(test)]
+impl diesel::r2d2::CustomizeConnection<PgConnection, diesel::r2d2::Error> for TestTransaction {
+    fn on_acquire(&self, conn: &mut PgConnection) -> Result<(), diesel::r2d2::Error> {
+        conn.begin_test_transaction()
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
 }
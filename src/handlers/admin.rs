@@ -0,0 +1,77 @@
+use crate::{models, server::Context, views};
+use diesel::PgConnection;
+
+// the console shows the newest of each, older ones are a database query away
+const LIST_LIMIT: i64 = 100;
+
+pub async fn console(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+
+    let (counts, users, pages, links) = read_console(&mut conn).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject::not_found()
+    })?;
+    let users = users
+        .into_iter()
+        .map(|(user, page_count)| {
+            let quota = context.config.quota_for(&user);
+            (user, page_count, quota)
+        })
+        .collect();
+    let html = views::admin::console(
+        &expanded_user,
+        counts,
+        users,
+        pages,
+        links,
+        &context.config.quota_plans,
+    );
+
+    Ok(warp::reply::html(html))
+}
+
+#[allow(clippy::type_complexity)]
+fn read_console(
+    conn: &mut PgConnection,
+) -> Result<
+    (
+        views::admin::Counts,
+        Vec<(models::user::User, usize)>,
+        Vec<(models::page::Page, String, usize)>,
+        Vec<(
+            models::page_link::PageLink,
+            models::link::Link,
+            models::page::Page,
+        )>,
+    ),
+    diesel::result::Error,
+> {
+    let counts = views::admin::Counts {
+        users: models::user::get_count_of_users(conn)?,
+        pages: models::page::get_count_of_pages(conn)?,
+        links: models::link::get_count_of_links(conn)?,
+    };
+
+    let users = models::user::read_recent(conn, LIST_LIMIT)?
+        .into_iter()
+        .map(|user| {
+            let page_count = models::page::get_count_of_pages_per_user(conn, user.id)?;
+            Ok((user, page_count))
+        })
+        .collect::<Result<Vec<_>, diesel::result::Error>>()?;
+
+    let pages = models::page::read_recent_with_username(conn, LIST_LIMIT)?
+        .into_iter()
+        .map(|(page, username)| {
+            let link_count = models::page_link::get_count_of_links_per_page(conn, page.id)?;
+            Ok((page, username, link_count))
+        })
+        .collect::<Result<Vec<_>, diesel::result::Error>>()?;
+
+    let links = models::page_link::read_recent_with_page(conn, LIST_LIMIT)?;
+
+    Ok((counts, users, pages, links))
+}
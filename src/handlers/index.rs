@@ -11,6 +11,7 @@ pub async fn index_authenticated(
 ) -> Result<impl warp::Reply, Infallible> {
     Ok(warp::reply::html(views::body::index_authenticated(
         &expanded_user.user,
+        &expanded_user.session,
         "",
     )))
 }
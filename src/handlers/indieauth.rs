@@ -0,0 +1,48 @@
+use crate::{
+    error_reply, models::oauth_state::STATE_DURATION_MINUTES, views, MalformedForm, NotAuthorized,
+    NotFound, SignupClosed,
+};
+use hyper::StatusCode;
+use std::convert::Infallible;
+use warp::{reject, Rejection, Reply};
+
+pub async fn redirect(url: String, state: String) -> Result<impl warp::Reply, Infallible> {
+    Ok(warp::reply::with_header(
+        warp::reply::with_header(
+            warp::reply::with_status(warp::reply(), StatusCode::SEE_OTHER),
+            "Location",
+            url,
+        ),
+        "Set-Cookie",
+        state_cookie(&state),
+    ))
+}
+
+// lax, since their authorization endpoint sends the browser back with a cross site redirect
+fn state_cookie(state: &str) -> String {
+    format!(
+        "indieauth_state={}; Path=/user/indieauth; HttpOnly; Secure; SameSite=Lax; Max-Age={}",
+        state,
+        STATE_DURATION_MINUTES * 60
+    )
+}
+
+pub async fn handle_indieauth_errors(err: Rejection) -> Result<impl Reply, Rejection> {
+    if let Some(MalformedForm(message)) = err.find::<MalformedForm>() {
+        let html = views::user::login_form(&format!("Error: {}", message));
+        error_reply(StatusCode::BAD_REQUEST, html)
+    } else if err.find::<NotFound>().is_some() || err.find::<reject::MissingCookie>().is_some() {
+        let html = views::user::login_form("Error: That sign in has expired, please try again");
+        error_reply(StatusCode::NOT_FOUND, html)
+    } else if let Some(SignupClosed) = err.find::<SignupClosed>() {
+        let html = views::user::login_form(
+            "Error: New accounts can only be made from the signup form on this instance",
+        );
+        error_reply(StatusCode::FORBIDDEN, html)
+    } else if let Some(NotAuthorized) = err.find::<NotAuthorized>() {
+        let html = views::user::login_form("Error: We could not sign you in with that website");
+        error_reply(StatusCode::UNAUTHORIZED, html)
+    } else {
+        Err(err)
+    }
+}
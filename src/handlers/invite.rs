@@ -0,0 +1,68 @@
+use crate::{
+    config::SignupMode, error_reply, models, server::Context, views, ResourceError,
+    ResourceErrorData,
+};
+use hyper::StatusCode;
+use warp::{Rejection, Reply};
+
+// loaded into the profile by htmx, empty unless signups need an invite
+pub async fn list(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::html(render_invites(
+        &context,
+        &expanded_user,
+        "",
+    )?))
+}
+
+fn render_invites(
+    context: &Context,
+    expanded_user: &models::user::ExpandedUser,
+    message: &str,
+) -> Result<String, warp::Rejection> {
+    if context.config.signup_mode != SignupMode::InviteOnly {
+        return Ok(String::new());
+    }
+    let mut conn = context.db_conn.get_conn();
+
+    let invites =
+        models::invite::read_by_issuer(&mut conn, expanded_user.user.id).map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject::not_found()
+        })?;
+    let issued = models::invite::get_count_of_invites_per_issuer(&mut conn, expanded_user.user.id)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject::not_found()
+        })?;
+
+    let remaining = if expanded_user.user.has_role(models::user::ROLE_ADMIN) {
+        None
+    } else {
+        Some(context.config.invites_per_user.saturating_sub(issued))
+    };
+    Ok(views::user::invites(invites, remaining, message))
+}
+
+pub async fn handle_invite_errors(err: Rejection) -> Result<impl Reply, Rejection> {
+    if let Some(ResourceError::TooMany(
+        ResourceErrorData {
+            context: Some(context),
+            expanded_user: Some(expanded_user),
+            ..
+        },
+        limit,
+    )) = err.find::<ResourceError>()
+    {
+        let html = render_invites(
+            context,
+            expanded_user,
+            &format!("Error: You have handed out all {} of your invites", limit),
+        )?;
+        error_reply(StatusCode::CONFLICT, html)
+    } else {
+        Err(err)
+    }
+}
@@ -3,17 +3,25 @@ use std::convert::Infallible;
 
 pub async fn link_pages(
     _context: Context,
-    user: models::user::User,
+    expanded_user: models::user::ExpandedUser,
     link: models::link::Link,
-    pages: Vec<models::page::Page>,
+    pages: Vec<(models::page::Page, String)>,
+    cursor: Option<i32>,
     my_pages: Vec<models::page::Page>,
+    my_cursor: Option<i32>,
 ) -> Result<impl warp::Reply, Infallible> {
-    let pages_html = pages_to_list(pages);
+    let pages_html = pages_to_list(pages) + &load_more_pages(&link, cursor);
     let form_html = pages_to_options(my_pages);
-    let add_to_my_pages_form = views::link_page::add_to_my_page(&link, form_html);
+    let add_to_my_pages_form =
+        views::link_page::add_to_my_page(&link, form_html, my_cursor, &expanded_user.session);
 
-    let link_page_html =
-        views::link_page::link_page(&link, &user, &pages_html, &add_to_my_pages_form);
+    let link_page_html = views::link_page::link_page(
+        &link,
+        &expanded_user.user,
+        &expanded_user.session,
+        &pages_html,
+        &add_to_my_pages_form,
+    );
 
     Ok(warp::reply::html(link_page_html))
 }
@@ -21,20 +29,40 @@ pub async fn link_pages(
 pub async fn link_pages_unauthenticated(
     _context: Context,
     link: models::link::Link,
-    pages: Vec<models::page::Page>,
+    pages: Vec<(models::page::Page, String)>,
+    cursor: Option<i32>,
 ) -> Result<impl warp::Reply, Infallible> {
-    let pages_html = pages_to_list(pages);
+    let pages_html = pages_to_list(pages) + &load_more_pages(&link, cursor);
 
     let link_page_html = views::link_page::link_page_unauthenticated(&link, &pages_html);
 
     Ok(warp::reply::html(link_page_html))
 }
 
-fn pages_to_list(pages: Vec<models::page::Page>) -> String {
+pub async fn more_pages(
+    _context: Context,
+    link: models::link::Link,
+    pages: Vec<(models::page::Page, String)>,
+    cursor: Option<i32>,
+) -> Result<impl warp::Reply, Infallible> {
+    let pages_html = pages
+        .iter()
+        .map(|(page, username)| views::page::list_item(page, username))
+        .collect::<String>()
+        + &load_more_pages(&link, cursor);
+
+    Ok(warp::reply::html(pages_html))
+}
+
+fn load_more_pages(link: &models::link::Link, cursor: Option<i32>) -> String {
+    views::page::load_more_pages(&format!("/link/{}/pages", link.id), cursor)
+}
+
+fn pages_to_list(pages: Vec<(models::page::Page, String)>) -> String {
     if pages.len() != 0 {
         pages
             .iter()
-            .map(|page| views::page::list_item(page))
+            .map(|(page, username)| views::page::list_item(page, username))
             .collect::<String>()
     } else {
         String::from("<h3>This link has not been saved under any pages, yet!</h3>")
@@ -45,7 +73,7 @@ fn pages_to_options(pages: Vec<models::page::Page>) -> String {
     if pages.len() != 0 {
         pages
             .iter()
-            .map(|page| views::page::option_item(page))
+            .map(views::page::option_item)
             .collect::<String>()
     } else {
         String::from("<h3>You need to make a page first!</h3>")
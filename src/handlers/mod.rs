@@ -1,4 +1,11 @@
+pub mod admin;
 pub mod index;
+pub mod indieauth;
+pub mod invite;
 pub mod link;
+pub mod oauth;
 pub mod page;
+pub mod passkey;
+pub mod session;
+pub mod trash;
 pub mod user;
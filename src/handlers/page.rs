@@ -1,5 +1,6 @@
 use crate::{
     error_reply, handlers, models, server::Context, views, ResourceError, ResourceErrorData,
+    PAGE_SIZE,
 };
 use hyper::StatusCode;
 
@@ -8,9 +9,9 @@ pub async fn view(
     expanded_user: models::user::ExpandedUser,
     expanded_page: models::page::ExpandedPage,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let links = get_links(context, &expanded_page)?;
+    let (links, cursor) = get_first_links(context, &expanded_page)?;
 
-    let page_html = views::page::view(expanded_user, expanded_page, links, "");
+    let page_html = views::page::view(expanded_user, expanded_page, links, cursor, "");
 
     Ok(warp::reply::html(page_html))
 }
@@ -20,9 +21,40 @@ pub async fn view_authenticated(
     expanded_user: models::user::ExpandedUser,
     expanded_page: models::page::ExpandedPage,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let links = get_links(context, &expanded_page)?;
+    let (links, cursor) = get_first_links(context.clone(), &expanded_page)?;
+    let pages = handlers::user::get_pages(context, &expanded_user)?;
+
+    let page_html = views::page::view_authenticated(
+        expanded_user,
+        expanded_page,
+        links,
+        cursor,
+        pages,
+        "",
+        "",
+    );
 
-    let page_html = views::page::view_authenticated(expanded_user, expanded_page, links, "");
+    Ok(warp::reply::html(page_html))
+}
+
+pub async fn links_updated_in_bulk(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    expanded_page: models::page::ExpandedPage,
+    results: Vec<models::page_link::BulkLinkResult>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let (links, cursor) = get_first_links(context.clone(), &expanded_page)?;
+    let pages = handlers::user::get_pages(context, &expanded_user)?;
+
+    let page_html = views::page::view_authenticated(
+        expanded_user,
+        expanded_page,
+        links,
+        cursor,
+        pages,
+        "",
+        &views::page::bulk_results(&results),
+    );
 
     Ok(warp::reply::html(page_html))
 }
@@ -31,20 +63,60 @@ pub async fn view_unauthenticated(
     context: Context,
     expanded_page: models::page::ExpandedPage,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let links = get_links(context, &expanded_page)?;
+    let (links, cursor) = get_first_links(context, &expanded_page)?;
 
-    let page_html = views::page::view_unauthenticated(expanded_page, links, "");
+    let page_html = views::page::view_unauthenticated(expanded_page, links, cursor, "");
 
     Ok(warp::reply::html(page_html))
 }
 
+pub async fn more_links(
+    _context: Context,
+    expanded_page: models::page::ExpandedPage,
+    links: Vec<(models::link::Link, models::page_link::PageLink)>,
+    cursor: Option<models::page_link::LinkCursor>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::html(views::link::more_links(
+        links,
+        &expanded_page,
+        cursor,
+    )))
+}
+
+pub async fn more_links_authenticated(
+    _context: Context,
+    expanded_user: models::user::ExpandedUser,
+    expanded_page: models::page::ExpandedPage,
+    links: Vec<(models::link::Link, models::page_link::PageLink)>,
+    cursor: Option<models::page_link::LinkCursor>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::html(views::link::more_links_authenticated(
+        links,
+        &expanded_page,
+        &expanded_user.session,
+        cursor,
+    )))
+}
+
+// permanent, the old slug only ever points at where the page went
+pub async fn moved(url: String) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::with_header(
+        warp::reply::with_status(warp::reply(), StatusCode::MOVED_PERMANENTLY),
+        "Location",
+        url,
+    ))
+}
+
 pub async fn handle_create_link_error(
     err: warp::Rejection,
 ) -> Result<impl warp::Reply, warp::Rejection> {
     if let Some(ResourceError::Duplicate(resource)) = err.find::<ResourceError>() {
         process_page_error(resource, "Error: Link already exists in this group")
-    } else if let Some(ResourceError::TooMany(resource)) = err.find::<ResourceError>() {
-        process_page_error(resource, "Error: You cannot add any more links")
+    } else if let Some(ResourceError::TooMany(resource, limit)) = err.find::<ResourceError>() {
+        process_page_error(
+            resource,
+            &format!("Error: A group can hold at most {} links", limit),
+        )
     } else {
         Err(err)
     }
@@ -55,8 +127,22 @@ pub async fn handle_create_page_error(
 ) -> Result<impl warp::Reply, warp::Rejection> {
     if let Some(ResourceError::Duplicate(resource)) = err.find::<ResourceError>() {
         process_profile_error(resource, "Error: Group with this name already exists")
-    } else if let Some(ResourceError::TooMany(resource)) = err.find::<ResourceError>() {
-        process_profile_error(resource, "Error: You cannot add any more groups")
+    } else if let Some(ResourceError::TooMany(resource, limit)) = err.find::<ResourceError>() {
+        process_profile_error(
+            resource,
+            &format!("Error: You cannot have more than {} groups", limit),
+        )
+    } else {
+        Err(err)
+    }
+}
+
+// an edit is sent back to the page it came from rather than the profile
+pub async fn handle_update_page_error(
+    err: warp::Rejection,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Some(ResourceError::Duplicate(resource)) = err.find::<ResourceError>() {
+        process_page_error(resource, "Error: Group with this name already exists")
     } else {
         Err(err)
     }
@@ -65,16 +151,20 @@ pub async fn handle_create_page_error(
 pub fn process_page_error(
     resource: &ResourceErrorData,
     message: &str,
-) -> Result<impl warp::Reply, warp::Rejection> {
+) -> Result<impl warp::Reply + use<>, warp::Rejection> {
     if let Some(expanded_user) = resource.expanded_user.clone() && let Some(expanded_page) = resource.expanded_page.clone() && let Some(context) = resource.context.clone() {
 
-        let links = get_links(context, &expanded_page)?;
+        let (links, cursor) = get_first_links(context.clone(), &expanded_page)?;
+        let pages = handlers::user::get_pages(context, &expanded_user)?;
 
         let html = views::page::view_authenticated(
             expanded_user,
             expanded_page,
             links,
-            message
+            cursor,
+            pages,
+            message,
+            ""
         );
 
         error_reply(StatusCode::CONFLICT, html)
@@ -86,13 +176,21 @@ pub fn process_page_error(
 pub fn process_profile_error(
     resource: &ResourceErrorData,
     message: &str,
-) -> Result<impl warp::Reply, warp::Rejection> {
+) -> Result<impl warp::Reply + use<>, warp::Rejection> {
     if let Some(expanded_user) = resource.expanded_user.clone() && let Some(context) = resource.context.clone() {
-        let pages = handlers::user::get_pages(context, &expanded_user)?;
+        let quota = context.config.quota_for(&expanded_user.user);
+        let pages = handlers::user::get_profile_pages(context.clone(), &expanded_user)?;
+        let passkeys = handlers::user::get_passkeys(context.clone(), &expanded_user)?;
+        let api_tokens = handlers::user::get_api_tokens(context, &expanded_user)?;
         let html = views::user::profile(
             expanded_user.user,
             expanded_user.background,
+            &expanded_user.session,
             pages,
+            &quota,
+            passkeys,
+            api_tokens,
+            None,
             message,
         );
         error_reply(StatusCode::CONFLICT, html)
@@ -104,14 +202,22 @@ pub fn process_profile_error(
     }
 }
 
-fn get_links(
+#[allow(clippy::type_complexity)]
+fn get_first_links(
     context: Context,
     expanded_page: &models::page::ExpandedPage,
-) -> Result<Vec<(models::link::Link, models::page_link::PageLink)>, warp::Rejection> {
+) -> Result<
+    (
+        Vec<(models::link::Link, models::page_link::PageLink)>,
+        Option<models::page_link::LinkCursor>,
+    ),
+    warp::Rejection,
+> {
     let mut conn = context.db_conn.get_conn();
 
-    models::link::read_links_by_page(&mut conn, &expanded_page.page).map_err(|e| {
-        log::error!("{:?}", e);
-        warp::reject::not_found()
-    })
+    models::link::read_links_by_page_after(&mut conn, &expanded_page.page, None, PAGE_SIZE)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject::not_found()
+        })
 }
@@ -0,0 +1,33 @@
+use crate::models::passkey::CHALLENGE_DURATION_MINUTES;
+use std::convert::Infallible;
+use webauthn_rs::prelude as webauthn;
+
+pub async fn registration_challenge(
+    creation: webauthn::CreationChallengeResponse,
+    token: String,
+) -> Result<impl warp::Reply, Infallible> {
+    Ok(warp::reply::with_header(
+        warp::reply::json(&creation),
+        "Set-Cookie",
+        challenge_cookie(&token),
+    ))
+}
+
+pub async fn login_challenge(
+    request: webauthn::RequestChallengeResponse,
+    token: String,
+) -> Result<impl warp::Reply, Infallible> {
+    Ok(warp::reply::with_header(
+        warp::reply::json(&request),
+        "Set-Cookie",
+        challenge_cookie(&token),
+    ))
+}
+
+fn challenge_cookie(token: &str) -> String {
+    format!(
+        "passkey_challenge={}; Path=/user/passkey; HttpOnly; Secure; SameSite=Strict; Max-Age={}",
+        token,
+        CHALLENGE_DURATION_MINUTES * 60
+    )
+}
@@ -0,0 +1,32 @@
+use crate::{handlers, models, server::Context, views};
+use std::convert::Infallible;
+
+pub async fn list(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+
+    let sessions = models::session::read_active_by_user_id(&mut conn, expanded_user.user.id)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject::not_found()
+        })?;
+
+    let sessions_html = views::session::list(
+        &expanded_user.user,
+        &expanded_user.background,
+        &expanded_user.session,
+        sessions,
+    );
+
+    Ok(warp::reply::html(sessions_html))
+}
+
+pub async fn logout_everywhere() -> Result<impl warp::Reply, Infallible> {
+    Ok(warp::reply::with_header(
+        warp::reply::html(views::body::index("You have logged out everywhere")),
+        "Set-Cookie",
+        handlers::user::clear_session_cookie(),
+    ))
+}
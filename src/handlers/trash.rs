@@ -0,0 +1,83 @@
+use crate::{handlers, models, server::Context, views};
+
+pub async fn list(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    render_trash(context, expanded_user, "")
+}
+
+pub async fn updated(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    message: String,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    render_trash(context, expanded_user, &message)
+}
+
+// the page re-renders as usual, the header tells the toast where undo goes
+pub async fn page_trashed(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    undo: String,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let reply = handlers::user::profile(context, expanded_user).await?;
+    Ok(warp::reply::with_header(
+        reply,
+        "HX-Trigger",
+        trashed_event(&undo),
+    ))
+}
+
+pub async fn link_trashed(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    expanded_page: models::page::ExpandedPage,
+    undo: String,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let reply = handlers::page::view_authenticated(context, expanded_user, expanded_page).await?;
+    Ok(warp::reply::with_header(
+        reply,
+        "HX-Trigger",
+        trashed_event(&undo),
+    ))
+}
+
+fn trashed_event(undo: &str) -> String {
+    serde_json::json!({ "trashed": undo }).to_string()
+}
+
+fn render_trash(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    message: &str,
+) -> Result<impl warp::Reply + use<>, warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+
+    let pages =
+        models::page::read_trash_by_user_id(&mut conn, expanded_user.user.id).map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject::not_found()
+        })?;
+    let page_links = models::page_link::read_trash_by_user_id(&mut conn, expanded_user.user.id)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject::not_found()
+        })?;
+
+    let hidden_links = models::page_link::read_hidden_by_user_id(&mut conn, expanded_user.user.id)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject::not_found()
+        })?;
+
+    Ok(warp::reply::html(views::trash::list(
+        &expanded_user.user,
+        &expanded_user.background,
+        &expanded_user.session,
+        pages,
+        page_links,
+        hidden_links,
+        message,
+    )))
+}
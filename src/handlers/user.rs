@@ -1,4 +1,8 @@
-use crate::{error_reply, models, server::Context, views, NotFound, ResourceError};
+use crate::{
+    config::SignupMode, error_reply, models, server::Context, utils::now, validation, views,
+    CredentialError, InvalidPasswordReset, InvalidTwoFactorCode, LoginThrottled, NotFound,
+    ResourceError, ResourceErrorData, SignupClosed, TwoFactorRequired, ValidationError, PAGE_SIZE,
+};
 use hyper::StatusCode;
 use std::convert::Infallible;
 use warp::{reject, Rejection, Reply};
@@ -7,61 +11,440 @@ pub async fn profile(
     context: Context,
     expanded_user: models::user::ExpandedUser,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let pages = get_pages(context, &expanded_user)?;
+    let quota = context.config.quota_for(&expanded_user.user);
+    let pages = get_profile_pages(context.clone(), &expanded_user)?;
+    let passkeys = get_passkeys(context.clone(), &expanded_user)?;
+    let api_tokens = get_api_tokens(context, &expanded_user)?;
 
-    let profile_html =
-        views::user::profile(expanded_user.user, expanded_user.background, pages, "");
+    let profile_html = views::user::profile(
+        expanded_user.user,
+        expanded_user.background,
+        &expanded_user.session,
+        pages,
+        &quota,
+        passkeys,
+        api_tokens,
+        None,
+        "",
+    );
 
     Ok(warp::reply::html(profile_html))
 }
 
+// the only time the token itself is ever shown
+pub async fn api_token_created(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    token: String,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let quota = context.config.quota_for(&expanded_user.user);
+    let pages = get_profile_pages(context.clone(), &expanded_user)?;
+    let passkeys = get_passkeys(context.clone(), &expanded_user)?;
+    let api_tokens = get_api_tokens(context, &expanded_user)?;
+
+    let profile_html = views::user::profile(
+        expanded_user.user,
+        expanded_user.background,
+        &expanded_user.session,
+        pages,
+        &quota,
+        passkeys,
+        api_tokens,
+        Some(&token),
+        "",
+    );
+
+    Ok(warp::reply::with_header(
+        warp::reply::html(profile_html),
+        "Cache-Control",
+        "no-store",
+    ))
+}
+
 pub async fn profile_with_cookie(
     context: Context,
     expanded_user: models::user::ExpandedUser,
+    token: String,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let pages = get_pages(context, &expanded_user)?;
-    let profile_html =
-        views::user::profile(expanded_user.user, expanded_user.background, pages, "");
+    let cookie = session_cookie(&token, &expanded_user.session);
+    let quota = context.config.quota_for(&expanded_user.user);
+    let pages = get_profile_pages(context.clone(), &expanded_user)?;
+    let passkeys = get_passkeys(context.clone(), &expanded_user)?;
+    let api_tokens = get_api_tokens(context, &expanded_user)?;
+    let profile_html = views::user::profile(
+        expanded_user.user,
+        expanded_user.background,
+        &expanded_user.session,
+        pages,
+        &quota,
+        passkeys,
+        api_tokens,
+        None,
+        "",
+    );
 
     Ok(warp::reply::with_header(
         warp::reply::html(profile_html),
         "Set-Cookie",
-        format!("session={}; path=/", expanded_user.session.id),
+        cookie,
     ))
 }
 
+// the idle timeout slides on the server, so the browser keeps the cookie
+// for as long as the session could possibly live
+pub fn session_cookie(token: &str, session: &models::session::Session) -> String {
+    let max_age = (session.expires_at - now()).num_seconds().max(0);
+    format!(
+        "session={}; Path=/; HttpOnly; Secure; SameSite=Lax; Max-Age={}",
+        token, max_age
+    )
+}
+
+pub fn clear_session_cookie() -> String {
+    String::from("session=; Path=/; HttpOnly; Secure; SameSite=Lax; Max-Age=0")
+}
+
+// only the verify step needs it, and only for as long as a pending login lives
+pub fn pending_login_cookie(token: &str) -> String {
+    format!(
+        "pending_login={}; Path=/user/login; HttpOnly; Secure; SameSite=Strict; Max-Age={}",
+        token,
+        models::pending_login::PENDING_LOGIN_DURATION_MINUTES * 60
+    )
+}
+
+pub fn get_profile_pages(
+    context: Context,
+    expanded_user: &models::user::ExpandedUser,
+) -> Result<views::user::ProfilePages, warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+
+    let (pages, cursor) = models::page::read_pages_by_user_id_after(
+        &mut conn,
+        expanded_user.user.id,
+        None,
+        PAGE_SIZE,
+    )
+    .map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject::not_found()
+    })?;
+    let count = models::page::get_count_of_pages_per_user(&mut conn, expanded_user.user.id)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject::not_found()
+        })?;
+
+    Ok(views::user::ProfilePages {
+        pages,
+        cursor,
+        count,
+    })
+}
+
+pub async fn more_pages(
+    _context: Context,
+    expanded_user: models::user::ExpandedUser,
+    pages: Vec<models::page::Page>,
+    cursor: Option<i32>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::html(views::user::more_pages(
+        pages,
+        &expanded_user.user.username,
+        cursor,
+    )))
+}
+
 pub fn get_pages(
     context: Context,
     expanded_user: &models::user::ExpandedUser,
-) -> Result<Vec<models::page::Page>, warp::Rejection> {
+) -> Result<views::page::PageOptions, warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+
+    let (pages, cursor) = models::page::read_pages_by_user_id_after(
+        &mut conn,
+        expanded_user.user.id,
+        None,
+        PAGE_SIZE,
+    )
+    .map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject::not_found()
+    })?;
+
+    Ok(views::page::PageOptions { pages, cursor })
+}
+
+pub async fn more_page_options(
+    _context: Context,
+    _expanded_user: models::user::ExpandedUser,
+    pages: Vec<models::page::Page>,
+    cursor: Option<i32>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::html(views::page::more_page_options(
+        pages, cursor,
+    )))
+}
+
+pub fn get_passkeys(
+    context: Context,
+    expanded_user: &models::user::ExpandedUser,
+) -> Result<Vec<models::passkey::Passkey>, warp::Rejection> {
     let mut conn = context.db_conn.get_conn();
 
-    models::page::read_pages_by_user_id(&mut conn, expanded_user.user.id).map_err(|e| {
+    models::passkey::read_by_user_id(&mut conn, expanded_user.user.id).map_err(|e| {
         log::error!("{:?}", e);
         warp::reject::not_found()
     })
 }
 
+pub fn get_api_tokens(
+    context: Context,
+    expanded_user: &models::user::ExpandedUser,
+) -> Result<Vec<models::api_token::ApiToken>, warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+
+    models::api_token::read_by_user_id(&mut conn, expanded_user.user.id).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject::not_found()
+    })
+}
+
+pub async fn settings(
+    _context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::html(views::user::settings(
+        &expanded_user.user,
+        &expanded_user.background,
+        &expanded_user.session,
+        "",
+    )))
+}
+
+pub async fn settings_saved(
+    _context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::html(views::user::settings(
+        &expanded_user.user,
+        &expanded_user.background,
+        &expanded_user.session,
+        "Your settings have been saved",
+    )))
+}
+
+pub async fn email_saved(
+    _context: Context,
+    expanded_user: models::user::ExpandedUser,
+    pending: Option<String>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let message = match pending {
+        Some(email) => format!("Check {} for a link to confirm your new email", email),
+        None => String::from("Your settings have been saved"),
+    };
+    Ok(warp::reply::html(views::user::settings(
+        &expanded_user.user,
+        &expanded_user.background,
+        &expanded_user.session,
+        &message,
+    )))
+}
+
+pub async fn two_factor_settings(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::html(render_two_factor_settings(
+        &context,
+        &expanded_user,
+        "",
+    )?))
+}
+
+pub async fn two_factor_disabled(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::html(render_two_factor_settings(
+        &context,
+        &expanded_user,
+        "Two-factor login has been turned off",
+    )?))
+}
+
+fn render_two_factor_settings(
+    context: &Context,
+    expanded_user: &models::user::ExpandedUser,
+    message: &str,
+) -> Result<String, warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+    let recovery_codes_left =
+        models::recovery_code::count_unused_by_user_id(&mut conn, expanded_user.user.id).map_err(
+            |e| {
+                log::error!("{:?}", e);
+                warp::reject()
+            },
+        )?;
+
+    Ok(views::user::two_factor_settings(
+        &expanded_user.user,
+        &expanded_user.background,
+        &expanded_user.session,
+        recovery_codes_left,
+        message,
+    ))
+}
+
+pub async fn two_factor_setup(
+    _context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::html(render_two_factor_setup(
+        &expanded_user,
+        "",
+    )?))
+}
+
+fn render_two_factor_setup(
+    expanded_user: &models::user::ExpandedUser,
+    message: &str,
+) -> Result<String, warp::Rejection> {
+    let totp = expanded_user.user.totp().ok_or_else(warp::reject)?;
+
+    Ok(views::user::two_factor_setup(
+        &expanded_user.user,
+        &expanded_user.background,
+        &expanded_user.session,
+        &totp,
+        message,
+    ))
+}
+
+pub async fn recovery_codes(
+    _context: Context,
+    expanded_user: models::user::ExpandedUser,
+    codes: Vec<String>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::html(views::user::recovery_codes(
+        &expanded_user.user,
+        &expanded_user.background,
+        &expanded_user.session,
+        &codes,
+        "Two-factor login is on",
+    )))
+}
+
+pub async fn account_deleted() -> Result<impl warp::Reply, Infallible> {
+    Ok(warp::reply::with_header(
+        warp::reply::html(views::body::index("Your account has been deleted")),
+        "Set-Cookie",
+        clear_session_cookie(),
+    ))
+}
+
 pub async fn logout() -> Result<impl warp::Reply, Infallible> {
     Ok(warp::reply::with_header(
         warp::reply::html(views::body::index("You have logged out")),
         "Set-Cookie",
-        format!("session=; Path=/"),
+        clear_session_cookie(),
     ))
 }
 
-pub async fn signup_form() -> Result<impl warp::Reply, Infallible> {
-    Ok(warp::reply::html(views::user::signup_form("")))
+pub async fn signup_form(
+    context: Context,
+    query: models::invite::InviteQuery,
+) -> Result<impl warp::Reply, Infallible> {
+    let message = match context.config.signup_mode {
+        SignupMode::Open => "",
+        SignupMode::InviteOnly => "You need an invite code from an existing member to sign up",
+        SignupMode::Closed => "This instance is not accepting new accounts",
+    };
+
+    Ok(warp::reply::html(views::user::signup_form(
+        message,
+        &validation::FieldErrors::default(),
+        query.invite.as_deref().unwrap_or(""),
+    )))
 }
 
 pub async fn login_form() -> Result<impl warp::Reply, Infallible> {
     Ok(warp::reply::html(views::user::login_form("")))
 }
 
+pub async fn forgot_password_form() -> Result<impl warp::Reply, Infallible> {
+    Ok(warp::reply::html(views::user::forgot_password_form("")))
+}
+
+pub async fn reset_link_sent() -> Result<impl warp::Reply, Infallible> {
+    Ok(warp::reply::html(views::user::forgot_password_form(
+        "If that account has an email on file, a reset link is on its way",
+    )))
+}
+
+pub async fn reset_password_form(
+    query: models::user::ResetTokenQuery,
+) -> Result<impl warp::Reply, Infallible> {
+    Ok(warp::reply::html(views::user::reset_password_form(
+        &query.token,
+        "",
+    )))
+}
+
+pub async fn password_reset() -> Result<impl warp::Reply, Infallible> {
+    Ok(warp::reply::html(views::user::login_form(
+        "Your password has been reset, log in with your new password",
+    )))
+}
+
+pub async fn email_verified() -> Result<impl warp::Reply, Infallible> {
+    Ok(warp::reply::html(views::user::login_form(
+        "Your email has been confirmed, password reset links will go there from now on",
+    )))
+}
+
+pub fn throttled_message(seconds: i64) -> String {
+    if seconds >= 60 {
+        format!(
+            "Error: Too many failed logins, try again in {} minutes",
+            (seconds + 59) / 60
+        )
+    } else {
+        format!(
+            "Error: Too many failed logins, try again in {} seconds",
+            seconds
+        )
+    }
+}
+
 pub async fn handle_login_errors(err: Rejection) -> Result<impl Reply, Rejection> {
-    if let Some(NotFound) = err.find::<NotFound>() {
+    if let Some(TwoFactorRequired(token)) = err.find::<TwoFactorRequired>() {
+        Ok(Box::new(warp::reply::with_header(
+            warp::reply::html(views::user::two_factor_form("")),
+            "Set-Cookie",
+            pending_login_cookie(token),
+        )) as Box<dyn Reply>)
+    } else if let Some(NotFound) = err.find::<NotFound>() {
         let html = views::user::login_form("Error: Invalid login credentials");
         error_reply(StatusCode::NOT_FOUND, html)
+    } else if let Some(LoginThrottled(seconds)) = err.find::<LoginThrottled>() {
+        let html = views::user::login_form(&throttled_message(*seconds));
+        error_reply(StatusCode::TOO_MANY_REQUESTS, html)
+    } else {
+        Err(err)
+    }
+}
+
+pub async fn handle_two_factor_login_errors(err: Rejection) -> Result<impl Reply, Rejection> {
+    if let Some(InvalidTwoFactorCode(_)) = err.find::<InvalidTwoFactorCode>() {
+        let html = views::user::two_factor_form("Error: That code is not valid");
+        error_reply(StatusCode::UNAUTHORIZED, html)
+    } else if let Some(LoginThrottled(seconds)) = err.find::<LoginThrottled>() {
+        let html = views::user::two_factor_form(&throttled_message(*seconds));
+        error_reply(StatusCode::TOO_MANY_REQUESTS, html)
+    } else if err.find::<NotFound>().is_some() || err.find::<reject::MissingCookie>().is_some() {
+        let html = views::user::login_form("Error: Your login has expired, please log in again");
+        error_reply(StatusCode::UNAUTHORIZED, html)
     } else {
         Err(err)
     }
@@ -78,9 +461,113 @@ pub async fn handle_logout_errors(err: Rejection) -> Result<impl Reply, Rejectio
 
 pub async fn handle_signup_errors(err: Rejection) -> Result<impl Reply, Rejection> {
     if let Some(ResourceError::Duplicate(_)) = err.find::<ResourceError>() {
-        let html = views::user::signup_form("Error: Username already in use");
+        let html = views::user::signup_form(
+            "Error: Username already in use",
+            &validation::FieldErrors::default(),
+            "",
+        );
         error_reply(StatusCode::BAD_REQUEST, html)
+    } else if let Some(ValidationError { errors, .. }) = err.find::<ValidationError>() {
+        let html = views::user::signup_form("", errors, "");
+        error_reply(StatusCode::BAD_REQUEST, html)
+    } else if let Some(SignupClosed) = err.find::<SignupClosed>() {
+        let html = views::user::signup_form(
+            "Error: This instance is not accepting new accounts",
+            &validation::FieldErrors::default(),
+            "",
+        );
+        error_reply(StatusCode::FORBIDDEN, html)
     } else {
         Err(err)
     }
 }
+
+pub async fn handle_reset_errors(err: Rejection) -> Result<impl Reply, Rejection> {
+    if let Some(InvalidPasswordReset(token, errors)) = err.find::<InvalidPasswordReset>() {
+        let html = views::user::reset_password_form(token, &errors.message());
+        error_reply(StatusCode::BAD_REQUEST, html)
+    } else if let Some(NotFound) = err.find::<NotFound>() {
+        let html = views::user::forgot_password_form(
+            "Error: This reset link is invalid or has expired, request a new one",
+        );
+        error_reply(StatusCode::NOT_FOUND, html)
+    } else {
+        Err(err)
+    }
+}
+
+pub async fn handle_email_verification_errors(err: Rejection) -> Result<impl Reply, Rejection> {
+    if let Some(NotFound) = err.find::<NotFound>() {
+        let html = views::error::error(
+            StatusCode::NOT_FOUND,
+            "Error: This link is invalid or has expired, change your email again for a new one",
+        );
+        error_reply(StatusCode::NOT_FOUND, html)
+    } else if let Some(ResourceError::Duplicate(_)) = err.find::<ResourceError>() {
+        let html = views::error::error(
+            StatusCode::CONFLICT,
+            "Error: That email is already used by another account",
+        );
+        error_reply(StatusCode::CONFLICT, html)
+    } else {
+        Err(err)
+    }
+}
+
+pub async fn handle_settings_errors(err: Rejection) -> Result<impl Reply, Rejection> {
+    if let Some(CredentialError::WrongPassword(resource)) = err.find::<CredentialError>() {
+        process_settings_error(resource, StatusCode::FORBIDDEN, "Error: Incorrect password")
+    } else if let Some(ValidationError { data, errors }) = err.find::<ValidationError>() {
+        process_settings_error(data, StatusCode::BAD_REQUEST, &errors.message())
+    } else if let Some(ResourceError::Duplicate(resource)) = err.find::<ResourceError>() {
+        process_settings_error(
+            resource,
+            StatusCode::CONFLICT,
+            "Error: Username already in use",
+        )
+    } else {
+        Err(err)
+    }
+}
+
+pub async fn handle_two_factor_errors(err: Rejection) -> Result<impl Reply, Rejection> {
+    if let Some(CredentialError::WrongPassword(resource)) = err.find::<CredentialError>()
+        && let ResourceErrorData {
+            context: Some(context),
+            expanded_user: Some(expanded_user),
+            ..
+        } = resource
+    {
+        let html = render_two_factor_settings(context, expanded_user, "Error: Incorrect password")?;
+        error_reply(StatusCode::FORBIDDEN, html)
+    } else if let Some(InvalidTwoFactorCode(resource)) = err.find::<InvalidTwoFactorCode>()
+        && let Some(expanded_user) = &resource.expanded_user
+    {
+        let html = render_two_factor_setup(
+            expanded_user,
+            "Error: That code is not valid, check your app's clock and try again",
+        )?;
+        error_reply(StatusCode::BAD_REQUEST, html)
+    } else {
+        Err(err)
+    }
+}
+
+fn process_settings_error(
+    resource: &ResourceErrorData,
+    code: StatusCode,
+    message: &str,
+) -> Result<Box<dyn Reply>, Rejection> {
+    match &resource.expanded_user {
+        Some(expanded_user) => error_reply(
+            code,
+            views::user::settings(
+                &expanded_user.user,
+                &expanded_user.background,
+                &expanded_user.session,
+                message,
+            ),
+        ),
+        None => error_reply(code, views::error::error(code, message)),
+    }
+}
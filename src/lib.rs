@@ -1,14 +1,25 @@
-#![feature(let_chains)]
+// the codebase spells out struct fields, clones its timestamps and checks lengths by hand,
+// so clippy's shorthand suggestions for those are left off
+#![allow(
+    clippy::clone_on_copy,
+    clippy::len_zero,
+    clippy::redundant_field_names,
+    clippy::redundant_pattern_matching
+)]
 
 pub mod api;
 pub mod config;
 pub mod db_conn;
 pub mod handlers;
+pub mod indieauth;
+pub mod mailer;
 pub mod models;
+pub mod oauth;
 pub mod routes;
 pub mod schema;
 pub mod server;
 pub mod utils;
+pub mod validation;
 pub mod views;
 
 #[macro_use]
@@ -20,12 +31,13 @@ use std::convert::Infallible;
 use std::error::Error;
 use warp::{http::StatusCode, reject, Rejection, Reply};
 
-pub const MAX_PAGE_COUNT: usize = 10;
-pub const MAX_LINK_COUNT: usize = 20;
+// how many rows a list shows before it offers to load more
+pub const PAGE_SIZE: i64 = 50;
 
+// TooMany carries the limit that was hit
 #[derive(Debug)]
 pub enum ResourceError {
-    TooMany(ResourceErrorData),
+    TooMany(ResourceErrorData, usize),
     Duplicate(ResourceErrorData),
 }
 
@@ -38,6 +50,13 @@ pub struct ResourceErrorData {
 
 impl reject::Reject for ResourceError {}
 
+#[derive(Debug)]
+pub enum CredentialError {
+    WrongPassword(ResourceErrorData),
+}
+
+impl reject::Reject for CredentialError {}
+
 #[derive(Debug)]
 struct NotFound;
 impl reject::Reject for NotFound {}
@@ -50,6 +69,46 @@ impl reject::Reject for NotAuthorized {}
 struct OldCookie;
 impl reject::Reject for OldCookie {}
 
+#[derive(Debug)]
+pub struct ValidationError {
+    data: ResourceErrorData,
+    errors: validation::FieldErrors,
+}
+
+impl reject::Reject for ValidationError {}
+
+#[derive(Debug)]
+struct CsrfMismatch;
+impl reject::Reject for CsrfMismatch {}
+
+#[derive(Debug)]
+struct MalformedForm(String);
+impl reject::Reject for MalformedForm {}
+
+// how many seconds until the next login attempt is allowed
+#[derive(Debug)]
+struct LoginThrottled(i64);
+impl reject::Reject for LoginThrottled {}
+
+// carries the reset token so the form can be shown again
+#[derive(Debug)]
+struct InvalidPasswordReset(String, validation::FieldErrors);
+impl reject::Reject for InvalidPasswordReset {}
+
+// the password checked out, carries the token for the pending login cookie
+#[derive(Debug)]
+struct TwoFactorRequired(String);
+impl reject::Reject for TwoFactorRequired {}
+
+#[derive(Debug)]
+struct InvalidTwoFactorCode(ResourceErrorData);
+impl reject::Reject for InvalidTwoFactorCode {}
+
+// the signup mode does not allow this account to be created
+#[derive(Debug)]
+struct SignupClosed;
+impl reject::Reject for SignupClosed {}
+
 pub async fn handle_final_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
     let code;
     let message;
@@ -74,9 +133,57 @@ pub async fn handle_rejection(err: Rejection) -> Result<Box<dyn warp::Reply>, Re
     if let Some(ResourceError::Duplicate(_)) = err.find::<ResourceError>() {
         let code = StatusCode::BAD_REQUEST;
         error_reply(code, views::error::error(code, "Duplicate resource"))
-    } else if let Some(ResourceError::TooMany(_)) = err.find::<ResourceError>() {
+    } else if let Some(ResourceError::TooMany(_, limit)) = err.find::<ResourceError>() {
+        let code = StatusCode::BAD_REQUEST;
+        error_reply(
+            code,
+            views::error::error(code, &format!("Too many resources, the limit is {}", limit)),
+        )
+    } else if let Some(CredentialError::WrongPassword(_)) = err.find::<CredentialError>() {
+        let code = StatusCode::FORBIDDEN;
+        error_reply(code, views::error::error(code, "Incorrect password"))
+    } else if let Some(ValidationError { errors, .. }) = err.find::<ValidationError>() {
+        let code = StatusCode::BAD_REQUEST;
+        error_reply(code, views::error::error(code, &errors.message()))
+    } else if let Some(InvalidPasswordReset(_, errors)) = err.find::<InvalidPasswordReset>() {
+        let code = StatusCode::BAD_REQUEST;
+        error_reply(code, views::error::error(code, &errors.message()))
+    } else if let Some(LoginThrottled(seconds)) = err.find::<LoginThrottled>() {
+        let code = StatusCode::TOO_MANY_REQUESTS;
+        error_reply(
+            code,
+            views::error::error(code, &handlers::user::throttled_message(*seconds)),
+        )
+    } else if let Some(_) = err.find::<TwoFactorRequired>() {
+        let code = StatusCode::UNAUTHORIZED;
+        error_reply(
+            code,
+            views::error::error(code, "A second factor is required to log in"),
+        )
+    } else if let Some(_) = err.find::<InvalidTwoFactorCode>() {
+        let code = StatusCode::UNAUTHORIZED;
+        error_reply(
+            code,
+            views::error::error(code, "That authentication code is not valid"),
+        )
+    } else if let Some(_) = err.find::<SignupClosed>() {
+        let code = StatusCode::FORBIDDEN;
+        error_reply(
+            code,
+            views::error::error(code, "This instance is not accepting new accounts"),
+        )
+    } else if let Some(_) = err.find::<CsrfMismatch>() {
+        let code = StatusCode::FORBIDDEN;
+        error_reply(
+            code,
+            views::error::error(
+                code,
+                "We could not verify this request, please reload the page and try again",
+            ),
+        )
+    } else if let Some(MalformedForm(message)) = err.find::<MalformedForm>() {
         let code = StatusCode::BAD_REQUEST;
-        error_reply(code, views::error::error(code, "Too many resources"))
+        error_reply(code, views::error::error(code, message))
     } else if let Some(_) = err.find::<NotAuthorized>() {
         let code = StatusCode::FORBIDDEN;
         error_reply(
@@ -87,7 +194,7 @@ pub async fn handle_rejection(err: Rejection) -> Result<Box<dyn warp::Reply>, Re
         Ok(Box::new(warp::reply::with_header(
             warp::reply::html(views::body::index("Your session has expired")),
             "Set-Cookie",
-            format!("session=; Path=/"),
+            handlers::user::clear_session_cookie(),
         )))
     } else if let Some(_) = err.find::<reject::MissingCookie>() {
         let code = StatusCode::FORBIDDEN;
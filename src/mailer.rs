@@ -0,0 +1,146 @@
+use crate::config::MailerConfig;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, Message, SmtpTransport,
+    Transport,
+};
+use std::fmt::Debug;
+use std::fs::OpenOptions;
+use std::io::prelude::*;
+use std::sync::Arc;
+
+pub type MailError = Box<dyn std::error::Error + Send + Sync>;
+
+// anything that can get a message into someone's inbox
+pub trait Mailer: Debug + Send + Sync {
+    fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), MailError>;
+}
+
+// smtp waits on the server, so handlers hand every send to a blocking thread
+pub async fn send(
+    mailer: Arc<dyn Mailer>,
+    to: &str,
+    subject: &str,
+    body: &str,
+) -> Result<(), MailError> {
+    let (to, subject, body) = (to.to_string(), subject.to_string(), body.to_string());
+    tokio::task::spawn_blocking(move || mailer.send(&to, &subject, &body)).await?
+}
+
+pub fn from_config(config: &MailerConfig) -> Arc<dyn Mailer> {
+    match config {
+        MailerConfig::Smtp {
+            host,
+            port,
+            username,
+            password,
+            from,
+        } => Arc::new(
+            SmtpMailer::new(host, *port, username, password, from)
+                .expect("SMTP settings must be valid"),
+        ),
+        MailerConfig::File { path } => Arc::new(FileMailer::new(path)),
+    }
+}
+
+pub struct SmtpMailer {
+    transport: SmtpTransport,
+    from: Mailbox,
+}
+
+// the transport holds the smtp credentials, so only the sender is shown
+impl Debug for SmtpMailer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SmtpMailer")
+            .field("from", &self.from)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SmtpMailer {
+    pub fn new(
+        host: &str,
+        port: u16,
+        username: &str,
+        password: &str,
+        from: &str,
+    ) -> Result<Self, MailError> {
+        let transport = SmtpTransport::relay(host)?
+            .port(port)
+            .credentials(Credentials::new(username.to_string(), password.to_string()))
+            .build();
+
+        Ok(SmtpMailer {
+            transport,
+            from: from.parse()?,
+        })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), MailError> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse()?)
+            .subject(subject)
+            .body(body.to_string())?;
+
+        self.transport.send(&message)?;
+        Ok(())
+    }
+}
+
+// for dev and tests, every message is logged and appended to a file instead
+#[derive(Debug)]
+pub struct FileMailer {
+    path: String,
+}
+
+impl FileMailer {
+    pub fn new(path: &str) -> Self {
+        FileMailer {
+            path: path.to_string(),
+        }
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), MailError> {
+        log::info!("Mailing {} about {}", to, subject);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "To: {}\nSubject: {}\n\n{}\n", to, subject, body)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_file_mailer() {
+    let path = std::env::temp_dir().join(format!("digitheque-mail-{}.log", std::process::id()));
+    let path = path.to_str().unwrap();
+    let _ = std::fs::remove_file(path);
+
+    let mailer = FileMailer::new(path);
+    mailer
+        .send(
+            "hg@example.com",
+            "Reset your password",
+            "https://localhost/reset",
+        )
+        .unwrap();
+    mailer
+        .send(
+            "hg@example.com",
+            "Reset your password",
+            "https://localhost/again",
+        )
+        .unwrap();
+
+    let contents = std::fs::read_to_string(path).unwrap();
+    assert!(contents.contains("To: hg@example.com"));
+    assert!(contents.contains("https://localhost/reset"));
+    assert!(contents.contains("https://localhost/again"));
+
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,284 @@
+use crate::{
+    models,
+    schema::api_token,
+    utils::{generate_token, now, sanitize_html},
+};
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Deserialize;
+
+pub const READ: &str = "read";
+pub const WRITE: &str = "write";
+
+// makes our tokens easy to spot in a leaked config or a secret scanner
+pub const TOKEN_PREFIX: &str = "dgt_";
+
+#[derive(Clone, Debug, Identifiable, Associations, Selectable, Queryable)]
+#[diesel(belongs_to(models::user::User))]
+#[diesel(table_name = api_token)]
+pub struct ApiToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub token_hash: String,
+    pub scope: String,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl ApiToken {
+    // write implies read
+    pub fn allows(&self, scope: &str) -> bool {
+        self.scope == WRITE || self.scope == scope
+    }
+
+    // handlers render for a session, so a token request gets a stand-in
+    // that lives only as long as the request and is never saved
+    pub fn session(&self) -> models::session::Session {
+        models::session::Session {
+            id: 0,
+            user_id: self.user_id,
+            valid_until: now(),
+            created_at: self.created_at,
+            updated_at: None,
+            deleted_at: None,
+            token_hash: self.token_hash.clone(),
+            expires_at: now(),
+            remember: false,
+            user_agent: Some(format!("API token {}", self.name)),
+            ip: None,
+            last_seen_at: now(),
+            csrf_token: generate_token(),
+        }
+    }
+
+    pub fn inject_values(&self, string: &str) -> String {
+        string
+            .replace("{api_token.id}", &self.id.to_string())
+            .replace("{api_token.name}", &self.name)
+            .replace("{api_token.scope}", &self.scope)
+            .replace(
+                "{api_token.created_at}",
+                &self.created_at.format("%Y-%m-%d").to_string(),
+            )
+            .replace(
+                "{api_token.last_used_at}",
+                &match self.last_used_at {
+                    Some(last_used_at) => last_used_at.format("%Y-%m-%d").to_string(),
+                    None => String::from("never"),
+                },
+            )
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NewApiTokenApi {
+    pub name: String,
+    pub scope: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = api_token)]
+pub struct NewApiToken {
+    pub user_id: i32,
+    pub name: String,
+    pub token_hash: String,
+    pub scope: String,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl NewApiToken {
+    pub fn new(user_id: i32, name: String, token_hash: String, scope: &str) -> Self {
+        NewApiToken {
+            user_id,
+            name: sanitize_html(&name),
+            token_hash,
+            scope: scope.to_string(),
+            last_used_at: None,
+            created_at: now(),
+            updated_at: None,
+            deleted_at: None,
+        }
+    }
+
+    pub fn insert(&self, conn: &mut PgConnection) -> Result<ApiToken, diesel::result::Error> {
+        create(conn, self)
+    }
+}
+
+pub fn generate() -> String {
+    format!("{}{}", TOKEN_PREFIX, generate_token())
+}
+
+// anything that isn't one of ours is left for the other filters
+pub fn bearer_token(header: &str) -> Option<&str> {
+    let (scheme, token) = header.trim().split_once(' ')?;
+    let token = token.trim();
+    if scheme.eq_ignore_ascii_case("bearer") && token.starts_with(TOKEN_PREFIX) {
+        Some(token)
+    } else {
+        None
+    }
+}
+
+pub fn parse_scope(scope: &str) -> Option<&'static str> {
+    match scope {
+        READ => Some(READ),
+        WRITE => Some(WRITE),
+        _ => None,
+    }
+}
+
+pub fn create(
+    conn: &mut PgConnection,
+    new_api_token: &NewApiToken,
+) -> Result<ApiToken, diesel::result::Error> {
+    diesel::insert_into(api_token::table)
+        .values(new_api_token)
+        .get_result(conn)
+}
+
+pub fn read_by_token_hash(
+    conn: &mut PgConnection,
+    token_hash: &str,
+) -> Result<ApiToken, diesel::result::Error> {
+    api_token::table
+        .filter(api_token::token_hash.eq(token_hash))
+        .filter(api_token::deleted_at.is_null())
+        .first::<ApiToken>(conn)
+}
+
+pub fn read_by_user_id(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<Vec<ApiToken>, diesel::result::Error> {
+    api_token::table
+        .filter(api_token::user_id.eq(user_id))
+        .filter(api_token::deleted_at.is_null())
+        .order(api_token::created_at.desc())
+        .load::<ApiToken>(conn)
+}
+
+pub fn read_by_id_and_user_id(
+    conn: &mut PgConnection,
+    id: i32,
+    user_id: i32,
+) -> Result<ApiToken, diesel::result::Error> {
+    api_token::table
+        .filter(api_token::id.eq(id))
+        .filter(api_token::user_id.eq(user_id))
+        .filter(api_token::deleted_at.is_null())
+        .first::<ApiToken>(conn)
+}
+
+pub fn touch(conn: &mut PgConnection, api_token: &ApiToken) -> QueryResult<ApiToken> {
+    diesel::update(api_token)
+        .set((api_token::last_used_at.eq(Some(now())),))
+        .get_result(conn)
+}
+
+pub fn delete(conn: &mut PgConnection, api_token: &ApiToken) -> QueryResult<usize> {
+    diesel::update(api_token)
+        .set((api_token::deleted_at.eq(Some(now())),))
+        .execute(conn)
+}
+
+pub fn delete_by_user_id(conn: &mut PgConnection, user_id: i32) -> QueryResult<usize> {
+    diesel::update(api_token::table)
+        .filter(api_token::user_id.eq(user_id))
+        .filter(api_token::deleted_at.is_null())
+        .set((api_token::deleted_at.eq(Some(now())),))
+        .execute(conn)
+}
+
+#[test]
+fn test_bearer_token() {
+    let token = generate();
+    assert!(token.starts_with(TOKEN_PREFIX));
+
+    assert_eq!(bearer_token(&format!("Bearer {}", token)), Some(&token[..]));
+    assert_eq!(
+        bearer_token(&format!("bearer  {} ", token)),
+        Some(&token[..])
+    );
+    assert_eq!(bearer_token("Bearer someone-elses-token"), None);
+    assert_eq!(bearer_token(&format!("Basic {}", token)), None);
+    assert_eq!(bearer_token("Bearer"), None);
+}
+
+#[test]
+fn test_token_scope() {
+    let mut api_token = ApiToken {
+        id: 1,
+        user_id: 1,
+        name: String::from("bookmarklet"),
+        token_hash: String::new(),
+        scope: String::from(READ),
+        last_used_at: None,
+        created_at: now(),
+        updated_at: None,
+        deleted_at: None,
+    };
+    assert!(api_token.allows(READ));
+    assert!(!api_token.allows(WRITE));
+
+    api_token.scope = String::from(WRITE);
+    assert!(api_token.allows(READ));
+    assert!(api_token.allows(WRITE));
+
+    assert_eq!(parse_scope("write"), Some(WRITE));
+    assert_eq!(parse_scope("admin"), None);
+}
+
+#[test]
+fn test_tokens_are_stored_and_found_by_hash() {
+    let Some(mut conn) = crate::db_conn::test_connection() else {
+        return;
+    };
+    conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+        let user = models::user::insert_test_user(conn, "tokens", "password");
+        let token = generate();
+        let token_hash = crate::utils::hash_token(&token);
+        let api_token = NewApiToken::new(user.id, String::from("script"), token_hash.clone(), READ)
+            .insert(conn)?;
+
+        assert_eq!(api_token.token_hash, token_hash);
+        assert!(!api_token.token_hash.contains(&token[TOKEN_PREFIX.len()..]));
+
+        assert_eq!(read_by_token_hash(conn, &token_hash)?.id, api_token.id);
+        assert!(read_by_token_hash(conn, &token).is_err());
+        assert!(read_by_token_hash(conn, &crate::utils::hash_token(&generate())).is_err());
+        Ok(())
+    });
+}
+
+#[test]
+fn test_revoked_tokens_are_refused() {
+    let Some(mut conn) = crate::db_conn::test_connection() else {
+        return;
+    };
+    conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+        let user = models::user::insert_test_user(conn, "revoked", "password");
+        let token_hash = crate::utils::hash_token(&generate());
+        let api_token = NewApiToken::new(user.id, String::from("old"), token_hash.clone(), WRITE)
+            .insert(conn)?;
+        let kept_hash = crate::utils::hash_token(&generate());
+        let kept = NewApiToken::new(user.id, String::from("kept"), kept_hash.clone(), READ)
+            .insert(conn)?;
+
+        assert_eq!(delete(conn, &api_token)?, 1);
+        assert!(read_by_token_hash(conn, &token_hash).is_err());
+        assert!(read_by_id_and_user_id(conn, api_token.id, user.id).is_err());
+        assert_eq!(read_by_token_hash(conn, &kept_hash)?.id, kept.id);
+
+        assert_eq!(delete_by_user_id(conn, user.id)?, 1);
+        assert!(read_by_token_hash(conn, &kept_hash).is_err());
+        assert!(read_by_user_id(conn, user.id)?.is_empty());
+        Ok(())
+    });
+}
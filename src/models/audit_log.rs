@@ -0,0 +1,73 @@
+use crate::{models, schema::audit_log, utils::now};
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+
+pub const LINK_HIDDEN: &str = "link_hidden";
+pub const LOGIN_LOCKOUT: &str = "login_lockout";
+pub const PAGE_HIDDEN: &str = "page_hidden";
+pub const QUOTA_CHANGED: &str = "quota_changed";
+pub const RECOVERY_CODE_USED: &str = "recovery_code_used";
+pub const ROLE_CHANGED: &str = "role_changed";
+pub const TWO_FACTOR_DISABLED: &str = "two_factor_disabled";
+pub const USER_SUSPENDED: &str = "user_suspended";
+
+#[derive(Clone, Debug, Identifiable, Associations, Selectable, Queryable)]
+#[diesel(belongs_to(models::user::User))]
+#[diesel(table_name = audit_log)]
+pub struct AuditLog {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub event: String,
+    pub detail: String,
+    pub ip: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = audit_log)]
+pub struct NewAuditLog {
+    pub user_id: Option<i32>,
+    pub event: String,
+    pub detail: String,
+    pub ip: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl NewAuditLog {
+    pub fn new(user_id: Option<i32>, event: &str, detail: String, ip: Option<String>) -> Self {
+        NewAuditLog {
+            user_id,
+            event: event.to_string(),
+            detail,
+            ip,
+            created_at: now(),
+        }
+    }
+
+    pub fn insert(&self, conn: &mut PgConnection) -> Result<AuditLog, diesel::result::Error> {
+        create(conn, self)
+    }
+}
+
+pub fn create(
+    conn: &mut PgConnection,
+    new_audit_log: &NewAuditLog,
+) -> Result<AuditLog, diesel::result::Error> {
+    diesel::insert_into(audit_log::table)
+        .values(new_audit_log)
+        .get_result(conn)
+}
+
+// the log is append only, a failure to write is logged but never fails the request
+pub fn record(
+    conn: &mut PgConnection,
+    user_id: Option<i32>,
+    event: &str,
+    detail: String,
+    ip: Option<String>,
+) {
+    log::warn!("Audit {}: {}", event, detail);
+    if let Err(e) = NewAuditLog::new(user_id, event, detail, ip).insert(conn) {
+        log::error!("{:?}", e);
+    }
+}
@@ -7,7 +7,6 @@ use diesel::prelude::*;
 
 #[derive(Clone, Debug, Identifiable, Selectable, Queryable)]
 #[diesel(table_name = background)]
-
 pub struct Background {
     pub id: i32,
     pub count: i32,
@@ -24,6 +23,7 @@ pub struct Background {
 }
 
 impl Background {
+    #[allow(clippy::too_many_arguments)]
     pub fn from_params(
         count: i32,
         frequency: i32,
@@ -91,6 +91,7 @@ pub struct NewBackground {
 }
 
 impl NewBackground {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         count: i32,
         frequency: i32,
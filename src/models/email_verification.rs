@@ -0,0 +1,91 @@
+use crate::{models, schema::email_verification, utils::now};
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+
+pub const VERIFICATION_DURATION_HOURS: i64 = 24;
+
+#[derive(Clone, Debug, Identifiable, Associations, Selectable, Queryable)]
+#[diesel(belongs_to(models::user::User))]
+#[diesel(table_name = email_verification)]
+pub struct EmailVerification {
+    pub id: i32,
+    pub user_id: i32,
+    pub email: String,
+    pub token_hash: String,
+    pub valid_until: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = email_verification)]
+pub struct NewEmailVerification {
+    pub user_id: i32,
+    pub email: String,
+    pub token_hash: String,
+    pub valid_until: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl NewEmailVerification {
+    pub fn new(user_id: i32, email: String, token_hash: String) -> Self {
+        NewEmailVerification {
+            user_id,
+            email,
+            token_hash,
+            valid_until: now() + chrono::Duration::hours(VERIFICATION_DURATION_HOURS),
+            used_at: None,
+            created_at: now(),
+            updated_at: None,
+            deleted_at: None,
+        }
+    }
+
+    pub fn insert(
+        &self,
+        conn: &mut PgConnection,
+    ) -> Result<EmailVerification, diesel::result::Error> {
+        create(conn, self)
+    }
+}
+
+pub fn create(
+    conn: &mut PgConnection,
+    new_verification: &NewEmailVerification,
+) -> Result<EmailVerification, diesel::result::Error> {
+    diesel::insert_into(email_verification::table)
+        .values(new_verification)
+        .get_result(conn)
+}
+
+// checking and using up the token is one statement, so a link only ever works once
+pub fn redeem(
+    conn: &mut PgConnection,
+    token_hash: &str,
+) -> Result<EmailVerification, diesel::result::Error> {
+    diesel::update(email_verification::table)
+        .filter(email_verification::token_hash.eq(token_hash))
+        .filter(email_verification::valid_until.gt(now()))
+        .filter(email_verification::used_at.is_null())
+        .filter(email_verification::deleted_at.is_null())
+        .set((
+            email_verification::used_at.eq(Some(now())),
+            email_verification::updated_at.eq(Some(now())),
+        ))
+        .get_result(conn)
+}
+
+// asking to verify another address retires the links sent for earlier ones
+pub fn delete_by_user_id(conn: &mut PgConnection, user_id: i32) -> QueryResult<usize> {
+    diesel::update(email_verification::table)
+        .filter(email_verification::user_id.eq(user_id))
+        .filter(email_verification::used_at.is_null())
+        .filter(email_verification::deleted_at.is_null())
+        .set((email_verification::deleted_at.eq(Some(now())),))
+        .execute(conn)
+}
@@ -0,0 +1,90 @@
+use crate::{models, schema::identity, utils::now};
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Deserialize;
+
+// an account at an outside provider that can log in as one of our users
+#[derive(Clone, Debug, Identifiable, Associations, Selectable, Queryable)]
+#[diesel(belongs_to(models::user::User))]
+#[diesel(table_name = identity)]
+pub struct Identity {
+    pub id: i32,
+    pub user_id: i32,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+// providers send either a code and our state back, or an error
+#[derive(Deserialize)]
+pub struct OAuthCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct IndieAuthLoginApi {
+    pub url: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = identity)]
+pub struct NewIdentity {
+    pub user_id: i32,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl NewIdentity {
+    pub fn new(user_id: i32, provider: &str, subject: &str, email: Option<String>) -> Self {
+        NewIdentity {
+            user_id,
+            provider: provider.to_string(),
+            subject: subject.to_string(),
+            email,
+            created_at: now(),
+            updated_at: None,
+            deleted_at: None,
+        }
+    }
+
+    pub fn insert(&self, conn: &mut PgConnection) -> Result<Identity, diesel::result::Error> {
+        create(conn, self)
+    }
+}
+
+pub fn create(
+    conn: &mut PgConnection,
+    new_identity: &NewIdentity,
+) -> Result<Identity, diesel::result::Error> {
+    diesel::insert_into(identity::table)
+        .values(new_identity)
+        .get_result(conn)
+}
+
+pub fn read_by_provider_and_subject(
+    conn: &mut PgConnection,
+    provider: &str,
+    subject: &str,
+) -> Result<Identity, diesel::result::Error> {
+    identity::table
+        .filter(identity::provider.eq(provider))
+        .filter(identity::subject.eq(subject))
+        .filter(identity::deleted_at.is_null())
+        .first::<Identity>(conn)
+}
+
+// hard delete, the provider and subject pair has to be free to link again
+pub fn delete_by_user_id(conn: &mut PgConnection, user_id: i32) -> QueryResult<usize> {
+    diesel::delete(identity::table)
+        .filter(identity::user_id.eq(user_id))
+        .execute(conn)
+}
@@ -0,0 +1,203 @@
+use crate::{
+    models,
+    schema::invite,
+    utils::{generate_token, now},
+};
+use chrono::{naive::NaiveDateTime, Duration};
+use diesel::prelude::*;
+use serde::Deserialize;
+
+#[derive(Clone, Debug, Identifiable, Associations, Selectable, Queryable)]
+#[diesel(belongs_to(models::user::User, foreign_key = issuer_user_id))]
+#[diesel(table_name = invite)]
+pub struct Invite {
+    pub id: i32,
+    pub issuer_user_id: i32,
+    pub code: String,
+    pub max_uses: i32,
+    pub uses: i32,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl Invite {
+    pub fn is_usable(&self) -> bool {
+        self.deleted_at.is_none() && self.uses < self.max_uses && self.expires_at > now()
+    }
+
+    pub fn inject_values(&self, string: &str) -> String {
+        string
+            .replace("{invite.id}", &self.id.to_string())
+            .replace("{invite.code}", &self.code)
+            .replace("{invite.uses}", &self.uses.to_string())
+            .replace("{invite.max_uses}", &self.max_uses.to_string())
+            .replace(
+                "{invite.expires_at}",
+                &self.expires_at.format("%Y-%m-%d").to_string(),
+            )
+    }
+}
+
+// an invite link lands on the signup form with the code filled in
+#[derive(Deserialize)]
+pub struct InviteQuery {
+    pub invite: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = invite)]
+pub struct NewInvite {
+    pub issuer_user_id: i32,
+    pub code: String,
+    pub max_uses: i32,
+    pub uses: i32,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl NewInvite {
+    pub fn new(issuer_user_id: i32, max_uses: i32, duration_days: i64) -> Self {
+        NewInvite {
+            issuer_user_id,
+            code: generate_code(),
+            max_uses,
+            uses: 0,
+            expires_at: now() + Duration::days(duration_days),
+            created_at: now(),
+            updated_at: None,
+            deleted_at: None,
+        }
+    }
+
+    pub fn insert(&self, conn: &mut PgConnection) -> Result<Invite, diesel::result::Error> {
+        create(conn, self)
+    }
+}
+
+// short enough to read out loud, long enough not to be guessed
+pub fn generate_code() -> String {
+    generate_token()[..16].to_string()
+}
+
+pub fn create(
+    conn: &mut PgConnection,
+    new_invite: &NewInvite,
+) -> Result<Invite, diesel::result::Error> {
+    diesel::insert_into(invite::table)
+        .values(new_invite)
+        .get_result(conn)
+}
+
+pub fn read_by_issuer(
+    conn: &mut PgConnection,
+    issuer_user_id: i32,
+) -> Result<Vec<Invite>, diesel::result::Error> {
+    invite::table
+        .filter(invite::issuer_user_id.eq(issuer_user_id))
+        .filter(invite::deleted_at.is_null())
+        .order(invite::created_at.desc())
+        .load::<Invite>(conn)
+}
+
+pub fn read_by_id_and_issuer(
+    conn: &mut PgConnection,
+    id: i32,
+    issuer_user_id: i32,
+) -> Result<Invite, diesel::result::Error> {
+    invite::table
+        .filter(invite::id.eq(id))
+        .filter(invite::issuer_user_id.eq(issuer_user_id))
+        .filter(invite::deleted_at.is_null())
+        .first::<Invite>(conn)
+}
+
+// revoked invites still count, otherwise the limit could be dodged
+pub fn get_count_of_invites_per_issuer(
+    conn: &mut PgConnection,
+    issuer_user_id: i32,
+) -> Result<usize, diesel::result::Error> {
+    invite::table
+        .filter(invite::issuer_user_id.eq(issuer_user_id))
+        .count()
+        .get_result(conn)
+        .map(|v: i64| v as usize)
+}
+
+// claims a use in a single statement so two signups can't share the last one
+pub fn redeem(conn: &mut PgConnection, code: &str) -> Result<Invite, diesel::result::Error> {
+    diesel::update(invite::table)
+        .filter(invite::code.eq(code.trim()))
+        .filter(invite::deleted_at.is_null())
+        .filter(invite::expires_at.gt(now()))
+        .filter(invite::uses.lt(invite::max_uses))
+        .set((
+            invite::uses.eq(invite::uses + 1),
+            invite::updated_at.eq(Some(now())),
+        ))
+        .get_result(conn)
+}
+
+// hands the use back when the signup it was claimed for falls through
+pub fn release(conn: &mut PgConnection, invite: &Invite) -> QueryResult<usize> {
+    diesel::update(invite)
+        .set((
+            invite::uses.eq(invite::uses - 1),
+            invite::updated_at.eq(Some(now())),
+        ))
+        .execute(conn)
+}
+
+pub fn delete(conn: &mut PgConnection, invite: &Invite) -> QueryResult<usize> {
+    diesel::update(invite)
+        .set((invite::deleted_at.eq(Some(now())),))
+        .execute(conn)
+}
+
+pub fn delete_by_user_id(conn: &mut PgConnection, issuer_user_id: i32) -> QueryResult<usize> {
+    diesel::update(invite::table)
+        .filter(invite::issuer_user_id.eq(issuer_user_id))
+        .filter(invite::deleted_at.is_null())
+        .set((invite::deleted_at.eq(Some(now())),))
+        .execute(conn)
+}
+
+#[test]
+fn test_invite_usable() {
+    let mut invite = NewInvite::new(1, 2, 7);
+    assert_eq!(invite.code.len(), 16);
+    assert_ne!(invite.code, generate_code());
+
+    invite.uses = 1;
+    let invite = Invite {
+        id: 1,
+        issuer_user_id: invite.issuer_user_id,
+        code: invite.code,
+        max_uses: invite.max_uses,
+        uses: invite.uses,
+        expires_at: invite.expires_at,
+        created_at: invite.created_at,
+        updated_at: None,
+        deleted_at: None,
+    };
+    assert!(invite.is_usable());
+
+    assert!(!Invite {
+        uses: 2,
+        ..invite.clone()
+    }
+    .is_usable());
+    assert!(!Invite {
+        expires_at: now() - Duration::minutes(1),
+        ..invite.clone()
+    }
+    .is_usable());
+    assert!(!Invite {
+        deleted_at: Some(now()),
+        ..invite
+    }
+    .is_usable());
+}
@@ -1,13 +1,13 @@
 use crate::{
     models,
     schema::{link, page_link},
-    utils::{get_metadata_from_url, now, sanitize_html},
+    utils::{get_metadata_from_url, now, sanitize_html, split_page},
 };
 use chrono::naive::NaiveDateTime;
 use diesel::prelude::*;
 use serde::Deserialize;
 
-use super::page_link::PageLink;
+use super::page_link::{LinkCursor, PageLink};
 
 #[derive(Debug, Identifiable, Selectable, Queryable, AsChangeset)]
 #[diesel(table_name = link)]
@@ -45,7 +45,7 @@ impl Link {
             .replace(
                 "{link.img_url}",
                 match &self.img_url {
-                    Some(url) => &url,
+                    Some(url) => url,
                     None => "/missing.png",
                 },
             )
@@ -53,7 +53,7 @@ impl Link {
                 "{link.title}",
                 match &self.title {
                     Some(title) => {
-                        if "" == title {
+                        if title.is_empty() {
                             &self.url
                         } else {
                             title
@@ -65,7 +65,7 @@ impl Link {
             .replace(
                 "{link.description}",
                 match &self.description {
-                    Some(description) => &description,
+                    Some(description) => description,
                     None => "",
                 },
             )
@@ -97,11 +97,11 @@ pub struct AddLinkToPageApi {
     pub url: String,
 }
 
-impl Into<NewLinkApi> for AddLinkToPageApi {
-    fn into(self) -> NewLinkApi {
+impl From<AddLinkToPageApi> for NewLinkApi {
+    fn from(link: AddLinkToPageApi) -> Self {
         NewLinkApi {
-            name: self.name,
-            url: self.url,
+            name: link.name,
+            url: link.url,
         }
     }
 }
@@ -149,12 +149,20 @@ pub fn read(conn: &mut PgConnection) -> Result<Vec<Link>, diesel::result::Error>
     link::table.load::<Link>(conn)
 }
 
+// hidden links are gone as far as everyone but the database is concerned
 pub fn read_by_id(conn: &mut PgConnection, id: i32) -> Result<Link, diesel::result::Error> {
-    link::table.filter(link::id.eq(id)).first::<Link>(conn)
+    link::table
+        .filter(link::id.eq(id))
+        .filter(link::deleted_at.is_null())
+        .first::<Link>(conn)
 }
 
+// a hidden link keeps its url, adding it again gets a fresh row instead of the hidden one
 pub fn read_by_url(conn: &mut PgConnection, url: String) -> Result<Link, diesel::result::Error> {
-    link::table.filter(link::url.eq(url)).first::<Link>(conn)
+    link::table
+        .filter(link::url.eq(url))
+        .filter(link::deleted_at.is_null())
+        .first::<Link>(conn)
 }
 
 pub fn delete(conn: &mut PgConnection, link: &Link) -> QueryResult<usize> {
@@ -169,15 +177,40 @@ pub fn update(conn: &mut PgConnection, link: &mut Link) -> QueryResult<usize> {
         .execute(conn)
 }
 
-pub fn read_links_by_page(
+// one screen of a page's links at a time, in the order the owner arranged them
+#[allow(clippy::type_complexity)]
+pub fn read_links_by_page_after(
     conn: &mut PgConnection,
     page: &models::page::Page,
-) -> Result<Vec<(Link, PageLink)>, diesel::result::Error> {
-    models::page_link::PageLink::belonging_to(page)
+    after: Option<LinkCursor>,
+    limit: i64,
+) -> Result<(Vec<(Link, PageLink)>, Option<LinkCursor>), diesel::result::Error> {
+    let after = after.unwrap_or(LinkCursor {
+        position: i32::MIN,
+        id: 0,
+    });
+    let links = models::page_link::PageLink::belonging_to(page)
         .inner_join(link::table)
         .select((Link::as_select(), PageLink::as_select()))
         .filter(page_link::deleted_at.is_null())
-        .load::<(Link, PageLink)>(conn)
+        .filter(link::deleted_at.is_null())
+        .filter(
+            page_link::position
+                .gt(after.position)
+                .or(page_link::position
+                    .eq(after.position)
+                    .and(page_link::id.gt(after.id))),
+        )
+        .order((page_link::position.asc(), page_link::id.asc()))
+        .limit(limit + 1)
+        .load::<(Link, PageLink)>(conn)?;
+
+    let (links, has_more) = split_page(links, limit);
+    let cursor = match links.last() {
+        Some((_, page_link)) if has_more => Some(LinkCursor::of(page_link)),
+        _ => None,
+    };
+    Ok((links, cursor))
 }
 
 pub fn get_count_of_links(conn: &mut PgConnection) -> Result<usize, diesel::result::Error> {
@@ -187,3 +220,46 @@ pub fn get_count_of_links(conn: &mut PgConnection) -> Result<usize, diesel::resu
         .get_result(conn)
         .map(|v: i64| v as usize)
 }
+
+#[cfg(test)]
+pub fn insert_test_link(
+    conn: &mut PgConnection,
+    url: &str,
+    creator_user_id: i32,
+) -> QueryResult<Link> {
+    NewLink {
+        url: String::from(url),
+        creator_user_id,
+        created_at: now(),
+        updated_at: None,
+        deleted_at: None,
+        img_url: None,
+        title: None,
+        description: None,
+    }
+    .insert(conn)
+}
+
+#[test]
+fn test_read_by_url_skips_hidden_links() {
+    let Some(mut conn) = crate::db_conn::test_connection() else {
+        return;
+    };
+    conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+        let user = models::user::insert_test_user(conn, "linkowner", "password");
+        let url = "https://example.com/hidden";
+        let hidden = insert_test_link(conn, url, user.id)?;
+        delete(conn, &hidden)?;
+
+        assert!(matches!(
+            read_by_url(conn, String::from(url)),
+            Err(diesel::NotFound)
+        ));
+
+        // the hidden row holds on to the url, a fresh row can still take it
+        let fresh = insert_test_link(conn, url, user.id)?;
+        assert_ne!(fresh.id, hidden.id);
+        assert_eq!(read_by_url(conn, String::from(url))?.id, fresh.id);
+        Ok(())
+    });
+}
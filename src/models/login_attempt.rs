@@ -0,0 +1,145 @@
+use crate::{schema::login_attempt, utils::now};
+use chrono::{naive::NaiveDateTime, Duration};
+use diesel::prelude::*;
+
+#[derive(Clone, Debug, Identifiable, Selectable, Queryable)]
+#[diesel(table_name = login_attempt)]
+pub struct LoginAttempt {
+    pub id: i32,
+    pub username: String,
+    pub ip: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = login_attempt)]
+pub struct NewLoginAttempt {
+    pub username: String,
+    pub ip: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl NewLoginAttempt {
+    pub fn new(username: &str, ip: Option<String>) -> Self {
+        NewLoginAttempt {
+            username: username.chars().take(48).collect(),
+            ip,
+            created_at: now(),
+            updated_at: None,
+            deleted_at: None,
+        }
+    }
+
+    pub fn insert(&self, conn: &mut PgConnection) -> Result<LoginAttempt, diesel::result::Error> {
+        create(conn, self)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ThrottleRules {
+    pub max_attempts: usize,
+    pub backoff: Duration,
+    pub lockout: Duration,
+}
+
+// each failure doubles the wait before the next try, until
+// max_attempts is hit and the lockout kicks in instead
+pub fn retry_after(
+    failures: &[LoginAttempt],
+    rules: ThrottleRules,
+    now: NaiveDateTime,
+) -> Option<Duration> {
+    let last_failure = failures.iter().map(|attempt| attempt.created_at).max()?;
+
+    let wait = if failures.len() >= rules.max_attempts {
+        rules.lockout
+    } else {
+        rules.backoff * 2_i32.pow((failures.len() - 1).min(16) as u32)
+    };
+
+    let allowed_at = last_failure + wait;
+    if allowed_at > now {
+        Some(allowed_at - now)
+    } else {
+        None
+    }
+}
+
+pub fn create(
+    conn: &mut PgConnection,
+    new_attempt: &NewLoginAttempt,
+) -> Result<LoginAttempt, diesel::result::Error> {
+    diesel::insert_into(login_attempt::table)
+        .values(new_attempt)
+        .get_result(conn)
+}
+
+pub fn read_recent_by_username(
+    conn: &mut PgConnection,
+    username: &str,
+    since: NaiveDateTime,
+) -> Result<Vec<LoginAttempt>, diesel::result::Error> {
+    login_attempt::table
+        .filter(login_attempt::username.eq(username))
+        .filter(login_attempt::created_at.gt(since))
+        .filter(login_attempt::deleted_at.is_null())
+        .load::<LoginAttempt>(conn)
+}
+
+pub fn read_recent_by_ip(
+    conn: &mut PgConnection,
+    ip: &str,
+    since: NaiveDateTime,
+) -> Result<Vec<LoginAttempt>, diesel::result::Error> {
+    login_attempt::table
+        .filter(login_attempt::ip.eq(ip))
+        .filter(login_attempt::created_at.gt(since))
+        .filter(login_attempt::deleted_at.is_null())
+        .load::<LoginAttempt>(conn)
+}
+
+// a successful login forgives the failures against that username
+pub fn delete_by_username(conn: &mut PgConnection, username: &str) -> QueryResult<usize> {
+    diesel::update(login_attempt::table)
+        .filter(login_attempt::username.eq(username))
+        .filter(login_attempt::deleted_at.is_null())
+        .set((login_attempt::deleted_at.eq(Some(now())),))
+        .execute(conn)
+}
+
+#[test]
+fn test_retry_after() {
+    let rules = ThrottleRules {
+        max_attempts: 3,
+        backoff: Duration::seconds(2),
+        lockout: Duration::minutes(15),
+    };
+    let start = now();
+    let failure = |seconds_ago: i64| LoginAttempt {
+        id: 1,
+        username: String::from("hg"),
+        ip: None,
+        created_at: start - Duration::seconds(seconds_ago),
+        updated_at: None,
+        deleted_at: None,
+    };
+
+    assert!(retry_after(&[], rules, start).is_none());
+    assert_eq!(
+        retry_after(&[failure(0)], rules, start),
+        Some(Duration::seconds(2))
+    );
+    assert!(retry_after(&[failure(3)], rules, start).is_none());
+    assert_eq!(
+        retry_after(&[failure(10), failure(1)], rules, start),
+        Some(Duration::seconds(3))
+    );
+    assert_eq!(
+        retry_after(&[failure(30), failure(20), failure(0)], rules, start),
+        Some(Duration::minutes(15))
+    );
+}
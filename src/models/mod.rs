@@ -1,6 +1,18 @@
+pub mod api_token;
+pub mod audit_log;
 pub mod background;
+pub mod email_verification;
+pub mod identity;
+pub mod invite;
 pub mod link;
+pub mod login_attempt;
+pub mod oauth_state;
 pub mod page;
 pub mod page_link;
+pub mod page_slug;
+pub mod passkey;
+pub mod password_reset;
+pub mod pending_login;
+pub mod recovery_code;
 pub mod session;
 pub mod user;
@@ -0,0 +1,104 @@
+use crate::{schema::oauth_state, utils::now};
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+
+pub const STATE_DURATION_MINUTES: i64 = 10;
+
+// remembers an authorization request between the redirect out and the callback
+#[derive(Clone, Debug, Identifiable, Selectable, Queryable)]
+#[diesel(table_name = oauth_state)]
+pub struct OAuthState {
+    pub id: i32,
+    pub provider: String,
+    pub state_hash: String,
+    pub code_verifier: String,
+    pub valid_until: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+    pub me: Option<String>,
+    pub authorization_endpoint: Option<String>,
+    pub nonce: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = oauth_state)]
+pub struct NewOAuthState {
+    pub provider: String,
+    pub state_hash: String,
+    pub code_verifier: String,
+    pub valid_until: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+    pub me: Option<String>,
+    pub authorization_endpoint: Option<String>,
+    pub nonce: Option<String>,
+}
+
+impl NewOAuthState {
+    pub fn new(provider: &str, state_hash: String, code_verifier: String) -> Self {
+        NewOAuthState {
+            provider: provider.to_string(),
+            state_hash,
+            code_verifier,
+            valid_until: now() + chrono::Duration::minutes(STATE_DURATION_MINUTES),
+            created_at: now(),
+            updated_at: None,
+            deleted_at: None,
+            me: None,
+            authorization_endpoint: None,
+            nonce: None,
+        }
+    }
+
+    // indieauth has no configured provider, so we carry who they said they were
+    // and where we sent them
+    pub fn with_profile(self, me: String, authorization_endpoint: String) -> Self {
+        NewOAuthState {
+            me: Some(me),
+            authorization_endpoint: Some(authorization_endpoint),
+            ..self
+        }
+    }
+
+    // openid providers echo this back inside the id token, tying it to this request
+    pub fn with_nonce(self, nonce: String) -> Self {
+        NewOAuthState {
+            nonce: Some(nonce),
+            ..self
+        }
+    }
+
+    pub fn insert(&self, conn: &mut PgConnection) -> Result<OAuthState, diesel::result::Error> {
+        create(conn, self)
+    }
+}
+
+pub fn create(
+    conn: &mut PgConnection,
+    new_state: &NewOAuthState,
+) -> Result<OAuthState, diesel::result::Error> {
+    diesel::insert_into(oauth_state::table)
+        .values(new_state)
+        .get_result(conn)
+}
+
+pub fn read_valid_by_state_hash(
+    conn: &mut PgConnection,
+    provider: &str,
+    state_hash: &str,
+) -> Result<OAuthState, diesel::result::Error> {
+    oauth_state::table
+        .filter(oauth_state::provider.eq(provider))
+        .filter(oauth_state::state_hash.eq(state_hash))
+        .filter(oauth_state::valid_until.gt(now()))
+        .filter(oauth_state::deleted_at.is_null())
+        .first::<OAuthState>(conn)
+}
+
+pub fn delete(conn: &mut PgConnection, state: &OAuthState) -> QueryResult<usize> {
+    diesel::update(state)
+        .set((oauth_state::deleted_at.eq(Some(now())),))
+        .execute(conn)
+}
@@ -1,12 +1,23 @@
 use crate::{
     models,
-    schema::{background, page, page_link},
-    utils::{now, sanitize_html},
+    schema::{background, page, page_link, page_slug, user},
+    utils::{now, sanitize_html, split_page},
 };
 use chrono::naive::NaiveDateTime;
 use diesel::prelude::*;
 use serde::Deserialize;
 
+pub const PUBLIC: &str = "public";
+pub const UNLISTED: &str = "unlisted";
+pub const PRIVATE: &str = "private";
+
+// what a name with nothing usable in it becomes
+const DEFAULT_SLUG: &str = "group";
+// names are capped at 64 characters, which leaves room for a -n on the end
+const MAX_SLUG_LENGTH: usize = 64;
+// the name column is a VARCHAR(64), counted in characters
+const MAX_NAME_LENGTH: usize = 64;
+
 #[derive(Clone, Debug, Identifiable, Selectable, Queryable, AsChangeset)]
 #[diesel(belongs_to(models::background::Background))]
 #[diesel(table_name = page)]
@@ -19,6 +30,9 @@ pub struct Page {
     pub updated_at: Option<NaiveDateTime>,
     pub deleted_at: Option<NaiveDateTime>,
     pub background_id: i32,
+    pub visibility: String,
+    pub hidden: bool,
+    pub slug: String,
 }
 
 #[derive(Clone, Debug)]
@@ -38,14 +52,53 @@ impl Page {
             updated_at: Some(now()),
             deleted_at: self.deleted_at.clone(),
             background_id: self.background_id.clone(),
+            visibility: self.visibility.clone(),
+            hidden: self.hidden,
+            slug: self.slug.clone(),
+        }
+    }
+
+    // goes through NewPage so edits are cleaned the same way as new pages,
+    // an edit that leaves out the visibility keeps the current one and the
+    // slug only follows the name when the name changes
+    pub fn with_details(&self, details: NewPageApi) -> Self {
+        let keep_visibility = details.visibility.is_none();
+        let details = NewPage::new(details, self.user_id, self.background_id);
+        let slug = if details.name == self.name {
+            self.slug.clone()
+        } else {
+            details.slug
+        };
+        Self {
+            name: details.name,
+            slug,
+            description: details.description,
+            visibility: if keep_visibility {
+                self.visibility.clone()
+            } else {
+                details.visibility
+            },
+            ..self.clone()
         }
     }
 
+    // unlisted pages are only hidden from listings, anyone with the url can read them
+    pub fn is_visible_to(&self, user_id: Option<i32>) -> bool {
+        self.visibility != PRIVATE || user_id == Some(self.user_id)
+    }
+
+    // pages are read by people who never see the id, only the owner's name
+    pub fn url(&self, username: &str) -> String {
+        format!("/u/{}/{}", username, self.slug)
+    }
+
     pub fn inject_values(&self, string: &str) -> String {
         string
             .replace("{page.id}", &self.id.to_string())
+            .replace("{page.slug}", &self.slug)
             .replace("{page.name}", &self.name)
             .replace("{page.description}", &self.description)
+            .replace("{page.visibility}", &self.visibility)
     }
 }
 
@@ -53,6 +106,13 @@ impl Page {
 pub struct NewPageApi {
     pub name: String,
     pub description: String,
+    pub visibility: Option<String>,
+}
+
+// ?cursor= on a list, the last row the previous screen showed
+#[derive(Deserialize)]
+pub struct CursorQuery {
+    pub cursor: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -65,11 +125,16 @@ pub struct NewPage {
     pub updated_at: Option<NaiveDateTime>,
     pub deleted_at: Option<NaiveDateTime>,
     pub background_id: i32,
+    pub visibility: String,
+    pub hidden: bool,
+    pub slug: String,
 }
 
 impl NewPage {
     pub fn new(new_page: NewPageApi, user_id: i32, background_id: i32) -> Self {
         NewPage {
+            // from the name as typed, the slug has no room for escaped html
+            slug: slugify(&new_page.name),
             name: sanitize_html(&new_page.name),
             description: sanitize_html(&new_page.description),
             user_id: user_id,
@@ -77,6 +142,20 @@ impl NewPage {
             updated_at: None,
             deleted_at: None,
             background_id: background_id,
+            visibility: new_page
+                .visibility
+                .as_deref()
+                .and_then(parse_visibility)
+                .unwrap_or(PUBLIC)
+                .to_string(),
+            hidden: false,
+        }
+    }
+
+    pub fn with_slug(self, taken: &[String]) -> Self {
+        Self {
+            slug: unique_slug(&self.slug, taken),
+            ..self
         }
     }
 
@@ -85,6 +164,42 @@ impl NewPage {
     }
 }
 
+pub fn parse_visibility(visibility: &str) -> Option<&'static str> {
+    match visibility {
+        PUBLIC => Some(PUBLIC),
+        UNLISTED => Some(UNLISTED),
+        PRIVATE => Some(PRIVATE),
+        _ => None,
+    }
+}
+
+// lowercase ascii letters and numbers, anything else in between becomes a dash
+pub fn slugify(name: &str) -> String {
+    let slug = name
+        .to_ascii_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let slug = slug[..slug.len().min(MAX_SLUG_LENGTH)].trim_end_matches('-');
+    if slug.is_empty() {
+        String::from(DEFAULT_SLUG)
+    } else {
+        slug.to_string()
+    }
+}
+
+// names only have to differ, so two of them can still come out as the same slug
+pub fn unique_slug(slug: &str, taken: &[String]) -> String {
+    let mut unique = slug.to_string();
+    let mut n = 2;
+    while taken.contains(&unique) {
+        unique = format!("{}-{}", slug, n);
+        n += 1;
+    }
+    unique
+}
+
 pub fn create(conn: &mut PgConnection, new_page: &NewPage) -> Result<Page, diesel::result::Error> {
     diesel::insert_into(page::table)
         .values(new_page)
@@ -115,6 +230,26 @@ pub fn read_by_id(conn: &mut PgConnection, id: i32) -> Result<ExpandedPage, dies
         .map(|(page, background)| expand(page, background))
 }
 
+pub fn read_by_username_and_slug(
+    conn: &mut PgConnection,
+    username: &str,
+    slug: &str,
+) -> Result<ExpandedPage, diesel::result::Error> {
+    page::table
+        .inner_join(background::table.on(page::background_id.eq(background::id)))
+        .inner_join(user::table.on(page::user_id.eq(user::id)))
+        .filter(user::username.eq(username))
+        .filter(user::deleted_at.is_null())
+        .filter(page::slug.eq(slug))
+        .filter(page::deleted_at.is_null())
+        .select((
+            Page::as_select(),
+            models::background::Background::as_select(),
+        ))
+        .first(conn)
+        .map(|(page, background)| expand(page, background))
+}
+
 pub fn read_by_id_and_user_id(
     conn: &mut PgConnection,
     id: i32,
@@ -139,31 +274,214 @@ pub fn delete(conn: &mut PgConnection, page: &Page) -> QueryResult<usize> {
         .execute(conn)
 }
 
-pub fn update(conn: &mut PgConnection, page: &mut Page) -> QueryResult<usize> {
+// a moderator's delete, kept out of the owner's trash so it can't be undone
+pub fn hide(conn: &mut PgConnection, page: &Page) -> QueryResult<usize> {
+    diesel::update(page)
+        .set((page::deleted_at.eq(Some(now())), page::hidden.eq(true)))
+        .execute(conn)
+}
+
+pub fn read_trash_by_user_id(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<Vec<Page>, diesel::result::Error> {
+    page::table
+        .filter(page::user_id.eq(user_id))
+        .filter(page::deleted_at.is_not_null())
+        .filter(page::hidden.eq(false))
+        .order(page::deleted_at.desc())
+        .load::<Page>(conn)
+}
+
+pub fn read_trash_by_id_and_user_id(
+    conn: &mut PgConnection,
+    id: i32,
+    user_id: i32,
+) -> Result<Page, diesel::result::Error> {
+    page::table
+        .filter(page::id.eq(id))
+        .filter(page::user_id.eq(user_id))
+        .filter(page::deleted_at.is_not_null())
+        .filter(page::hidden.eq(false))
+        .first::<Page>(conn)
+}
+
+pub fn restore(conn: &mut PgConnection, page: &Page, name: &str, slug: &str) -> QueryResult<Page> {
+    diesel::update(page)
+        .set((
+            page::name.eq(name),
+            page::slug.eq(slug),
+            page::deleted_at.eq(None::<NaiveDateTime>),
+            page::updated_at.eq(Some(now())),
+        ))
+        .get_result(conn)
+}
+
+// its links and background go with it, nothing else points at them
+pub fn purge(conn: &mut PgConnection, page: &Page) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        diesel::delete(page_link::table.filter(page_link::page_id.eq(page.id))).execute(conn)?;
+        diesel::delete(page_slug::table.filter(page_slug::page_id.eq(page.id))).execute(conn)?;
+        let purged = diesel::delete(page).execute(conn)?;
+        diesel::delete(background::table.find(page.background_id)).execute(conn)?;
+        Ok(purged)
+    })
+}
+
+// the unique constraint treats null deleted_at values as equal, so a page
+// can't come back under a name that was taken while it was in the trash
+pub fn restored_name(name: &str, taken: &[String]) -> String {
+    let mut restored = name.to_string();
+    let mut n = 1;
+    while taken.contains(&restored) {
+        let suffix = match n {
+            1 => String::from(" (restored)"),
+            _ => format!(" (restored {})", n),
+        };
+        // a long name gives up its end so the suffix still fits the column
+        let base = name
+            .chars()
+            .take(MAX_NAME_LENGTH - suffix.chars().count())
+            .collect::<String>();
+        restored = format!("{}{}", base.trim_end(), suffix);
+        n += 1;
+    }
+    restored
+}
+
+// takes the links in each page down with it
+pub fn delete_by_user_id(conn: &mut PgConnection, user_id: i32) -> QueryResult<usize> {
+    let deleted_at = Some(now());
+
+    diesel::update(page_link::table)
+        .filter(
+            page_link::page_id.eq_any(
+                page::table
+                    .select(page::id)
+                    .filter(page::user_id.eq(user_id)),
+            ),
+        )
+        .filter(page_link::deleted_at.is_null())
+        .set((page_link::deleted_at.eq(deleted_at),))
+        .execute(conn)?;
+
     diesel::update(page::table)
+        .filter(page::user_id.eq(user_id))
+        .filter(page::deleted_at.is_null())
+        .set((page::deleted_at.eq(deleted_at),))
+        .execute(conn)
+}
+
+pub fn update(conn: &mut PgConnection, page: &mut Page) -> QueryResult<usize> {
+    diesel::update(page::table.find(page.id))
         .set(&page.for_update())
         .execute(conn)
 }
 
-pub fn read_pages_by_user_id(
+// a rename moves the page to a new slug, the old one is kept so links to it still work
+pub fn update_with_slug_history(
+    conn: &mut PgConnection,
+    page: &mut Page,
+    old_slug: &str,
+) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        let updated = update(conn, page)?;
+        if page.slug != old_slug {
+            models::page_slug::NewPageSlug::new(page.id, old_slug).insert(conn)?;
+        }
+        Ok(updated)
+    })
+}
+
+pub fn read_slugs_by_user_id(
     conn: &mut PgConnection,
     user_id: i32,
-) -> Result<Vec<Page>, diesel::result::Error> {
+) -> Result<Vec<String>, diesel::result::Error> {
     page::table
         .filter(page::user_id.eq(user_id))
         .filter(page::deleted_at.is_null())
-        .load::<Page>(conn)
+        .select(page::slug)
+        .load(conn)
+}
+
+// names are checked against every page the user has, without loading the pages themselves
+pub fn read_names_by_user_id(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<Vec<String>, diesel::result::Error> {
+    page::table
+        .filter(page::user_id.eq(user_id))
+        .filter(page::deleted_at.is_null())
+        .select(page::name)
+        .load(conn)
 }
 
+pub fn read_pages_by_user_id_after(
+    conn: &mut PgConnection,
+    user_id: i32,
+    after: Option<i32>,
+    limit: i64,
+) -> Result<(Vec<Page>, Option<i32>), diesel::result::Error> {
+    let pages = page::table
+        .filter(page::user_id.eq(user_id))
+        .filter(page::deleted_at.is_null())
+        .filter(page::id.gt(after.unwrap_or(0)))
+        .order(page::id.asc())
+        .limit(limit + 1)
+        .load::<Page>(conn)?;
+
+    Ok(next_cursor(pages, limit, |page| page.id))
+}
+
+// only public pages are listed, plus the viewer's own, oldest first after the cursor,
+// with the owner's name to link to them by
+#[allow(clippy::type_complexity)]
 pub fn read_pages_by_link(
     conn: &mut PgConnection,
     link: &models::link::Link,
-) -> Result<Vec<Page>, diesel::result::Error> {
-    models::page_link::PageLink::belonging_to(link)
+    user_id: Option<i32>,
+    after: Option<i32>,
+    limit: i64,
+) -> Result<(Vec<(Page, String)>, Option<i32>), diesel::result::Error> {
+    let pages = models::page_link::PageLink::belonging_to(link)
         .inner_join(page::table)
-        .select(Page::as_select())
+        .inner_join(user::table.on(page::user_id.eq(user::id)))
+        .select((Page::as_select(), user::username))
         .filter(page_link::deleted_at.is_null())
         .filter(page::deleted_at.is_null())
+        .filter(
+            page::visibility
+                .eq(PUBLIC)
+                .or(page::user_id.nullable().eq(user_id)),
+        )
+        .filter(page::id.gt(after.unwrap_or(0)))
+        .order(page::id.asc())
+        .limit(limit + 1)
+        .load::<(Page, String)>(conn)?;
+
+    Ok(next_cursor(pages, limit, |(page, _)| page.id))
+}
+
+fn next_cursor<T>(rows: Vec<T>, limit: i64, id: impl Fn(&T) -> i32) -> (Vec<T>, Option<i32>) {
+    let (rows, has_more) = split_page(rows, limit);
+    let cursor = match rows.last() {
+        Some(row) if has_more => Some(id(row)),
+        _ => None,
+    };
+    (rows, cursor)
+}
+
+// newest first, with the owner's name for the moderation console
+pub fn read_recent_with_username(
+    conn: &mut PgConnection,
+    limit: i64,
+) -> Result<Vec<(Page, String)>, diesel::result::Error> {
+    page::table
+        .inner_join(user::table.on(page::user_id.eq(user::id)))
+        .filter(page::deleted_at.is_null())
+        .order(page::created_at.desc())
+        .limit(limit)
+        .select((Page::as_select(), user::username))
         .load(conn)
 }
 
@@ -186,3 +504,109 @@ pub fn get_count_of_pages(conn: &mut PgConnection) -> Result<usize, diesel::resu
         .get_result(conn)
         .map(|v: i64| v as usize)
 }
+
+#[test]
+fn test_restored_name() {
+    let taken = vec![
+        String::from("Reading list"),
+        String::from("Reading list (restored)"),
+    ];
+    assert_eq!(restored_name("Recipes", &taken), "Recipes");
+    assert_eq!(
+        restored_name("Reading list", &taken),
+        "Reading list (restored 2)"
+    );
+    assert_eq!(
+        restored_name("Reading list", &taken[..1]),
+        "Reading list (restored)"
+    );
+
+    let long = "é".repeat(MAX_NAME_LENGTH);
+    let restored = restored_name(&long, std::slice::from_ref(&long));
+    assert_eq!(restored.chars().count(), MAX_NAME_LENGTH);
+    assert!(restored.ends_with(" (restored)"));
+}
+
+#[test]
+fn test_slugify() {
+    assert_eq!(slugify("Reading list"), "reading-list");
+    assert_eq!(
+        slugify("  Tom & Jerry's -- best bits! "),
+        "tom-jerry-s-best-bits"
+    );
+    assert_eq!(slugify("Café"), "caf");
+    assert_eq!(slugify("!!!"), DEFAULT_SLUG);
+    assert_eq!(slugify(&"a-".repeat(40)).len(), MAX_SLUG_LENGTH - 1);
+
+    let taken = vec![String::from("recipes"), String::from("recipes-2")];
+    assert_eq!(unique_slug("reading-list", &taken), "reading-list");
+    assert_eq!(unique_slug("recipes", &taken), "recipes-3");
+    assert_eq!(unique_slug("recipes", &taken[..1]), "recipes-2");
+}
+
+// a page the way the create route makes one, for tests that need a page row
+#[cfg(test)]
+pub fn insert_test_page(conn: &mut PgConnection, user: &models::user::User, name: &str) -> Page {
+    NewPage::new(
+        NewPageApi {
+            name: name.to_string(),
+            description: String::new(),
+            visibility: None,
+        },
+        user.id,
+        user.background_id,
+    )
+    .insert(conn)
+    .unwrap()
+}
+
+#[test]
+fn test_page_visibility() {
+    let page = Page {
+        id: 1,
+        name: String::from("Reading list"),
+        description: String::new(),
+        user_id: 1,
+        created_at: now(),
+        updated_at: None,
+        deleted_at: None,
+        background_id: 1,
+        visibility: String::from(PRIVATE),
+        hidden: false,
+        slug: String::from("reading-list"),
+    };
+    assert!(page.is_visible_to(Some(1)));
+    assert!(!page.is_visible_to(Some(2)));
+    assert!(!page.is_visible_to(None));
+
+    let page = Page {
+        visibility: String::from(UNLISTED),
+        ..page
+    };
+    assert!(page.is_visible_to(None));
+
+    assert_eq!(parse_visibility("private"), Some(PRIVATE));
+    assert_eq!(parse_visibility("secret"), None);
+}
+
+#[test]
+fn test_hidden_pages_stay_out_of_the_trash() {
+    let Some(mut conn) = crate::db_conn::test_connection() else {
+        return;
+    };
+    conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+        let user = models::user::insert_test_user(conn, "hidden", "password");
+        let deleted = insert_test_page(conn, &user, "Deleted");
+        let hidden = insert_test_page(conn, &user, "Hidden");
+        delete(conn, &deleted)?;
+        hide(conn, &hidden)?;
+
+        let trash = read_trash_by_user_id(conn, user.id)?;
+        assert_eq!(
+            trash.iter().map(|page| page.id).collect::<Vec<_>>(),
+            vec![deleted.id]
+        );
+        assert!(read_trash_by_id_and_user_id(conn, hidden.id, user.id).is_err());
+        Ok(())
+    });
+}
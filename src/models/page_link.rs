@@ -1,10 +1,25 @@
 use crate::{
     models,
-    schema::page_link,
-    utils::{now, sanitize_html},
+    schema::{link, page, page_link},
+    utils::{escape_html, now, render_markdown, sanitize_html},
 };
 use chrono::naive::NaiveDateTime;
 use diesel::prelude::*;
+use serde::Deserialize;
+
+pub const UP: &str = "up";
+pub const DOWN: &str = "down";
+
+pub const MOVE: &str = "move";
+pub const COPY: &str = "copy";
+pub const REMOVE: &str = "remove";
+
+pub const MOVED: &str = "moved";
+pub const COPIED: &str = "copied";
+pub const REMOVED: &str = "removed";
+pub const ALREADY_ON_PAGE: &str = "already on that page";
+pub const PAGE_FULL: &str = "that page is full";
+pub const NOT_ON_PAGE: &str = "not on this page";
 
 #[derive(Identifiable, Selectable, Queryable, Associations, Debug)]
 #[diesel(belongs_to(models::page::Page))]
@@ -19,11 +34,113 @@ pub struct PageLink {
     pub created_at: NaiveDateTime,
     pub updated_at: Option<NaiveDateTime>,
     pub deleted_at: Option<NaiveDateTime>,
+    pub position: i32,
+    pub notes: String,
+    pub hidden_reason: Option<String>,
 }
 
 impl PageLink {
     pub fn inject_values(&self, string: &str) -> String {
-        string.replace("{page_link.name}", &self.name.to_string())
+        string
+            .replace("{page_link.id}", &self.id.to_string())
+            .replace("{page_link.name}", &self.name.to_string())
+            .replace("{page_link.notes}", &escape_html(&self.notes))
+            .replace("{page_link.notes_html}", &render_markdown(&self.notes))
+            .replace(
+                "{page_link.hidden_reason}",
+                self.hidden_reason.as_deref().unwrap_or(""),
+            )
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EditPageLinkApi {
+    pub name: String,
+    pub notes: String,
+}
+
+// the page's link ids, comma separated, in the order they should be shown
+#[derive(Deserialize)]
+pub struct LinkOrderApi {
+    pub links: String,
+}
+
+#[derive(Deserialize)]
+pub struct MoveLinkApi {
+    pub direction: String,
+}
+
+#[derive(Deserialize)]
+pub struct HideLinkApi {
+    pub reason: String,
+}
+
+// checkboxes repeat the links field, which a plain struct can't take
+pub struct BulkLinksApi {
+    pub action: &'static str,
+    pub link_ids: Vec<i32>,
+    pub target_page_id: Option<i32>,
+}
+
+impl BulkLinksApi {
+    pub fn from_form(form: Vec<(String, String)>) -> Option<Self> {
+        let mut action = None;
+        let mut link_ids = vec![];
+        let mut target_page_id = None;
+        for (key, value) in form {
+            match key.as_str() {
+                "action" => action = parse_bulk_action(&value),
+                "links" => link_ids.push(value.parse::<i32>().ok()?),
+                "target_page_id" if !value.is_empty() => {
+                    target_page_id = Some(value.parse::<i32>().ok()?)
+                }
+                _ => (),
+            }
+        }
+
+        let action = action?;
+        if link_ids.is_empty() || (action != REMOVE && target_page_id.is_none()) {
+            return None;
+        }
+        Some(Self {
+            action,
+            link_ids,
+            target_page_id,
+        })
+    }
+}
+
+pub struct BulkLinkResult {
+    pub link_id: i32,
+    pub name: String,
+    pub outcome: &'static str,
+}
+
+// links are ordered by position, the id settles links that share one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinkCursor {
+    pub position: i32,
+    pub id: i32,
+}
+
+impl LinkCursor {
+    pub fn of(page_link: &PageLink) -> Self {
+        Self {
+            position: page_link.position,
+            id: page_link.id,
+        }
+    }
+
+    pub fn parse(cursor: &str) -> Option<Self> {
+        let (position, id) = cursor.trim().split_once('.')?;
+        Some(Self {
+            position: position.parse::<i32>().ok()?,
+            id: id.parse::<i32>().ok()?,
+        })
+    }
+
+    pub fn to_param(&self) -> String {
+        format!("{}.{}", self.position, self.id)
     }
 }
 
@@ -36,10 +153,12 @@ pub struct NewPageLink {
     pub created_at: NaiveDateTime,
     pub updated_at: Option<NaiveDateTime>,
     pub deleted_at: Option<NaiveDateTime>,
+    pub position: i32,
+    pub notes: String,
 }
 
 impl NewPageLink {
-    pub fn new(page_id: i32, link_id: i32, name: String) -> Self {
+    pub fn new(page_id: i32, link_id: i32, name: String, position: i32) -> Self {
         Self {
             page_id: page_id,
             link_id: link_id,
@@ -47,6 +166,23 @@ impl NewPageLink {
             created_at: now(),
             updated_at: None,
             deleted_at: None,
+            position: position,
+            notes: String::new(),
+        }
+    }
+
+    // the name was sanitized when first saved and the notes are rendered on display,
+    // so both are taken as they are
+    pub fn copy_of(page_link: &PageLink, page_id: i32, position: i32) -> Self {
+        Self {
+            page_id: page_id,
+            link_id: page_link.link_id,
+            name: page_link.name.clone(),
+            created_at: now(),
+            updated_at: None,
+            deleted_at: None,
+            position: position,
+            notes: page_link.notes.clone(),
         }
     }
 
@@ -55,6 +191,15 @@ impl NewPageLink {
     }
 }
 
+pub fn parse_bulk_action(action: &str) -> Option<&'static str> {
+    match action {
+        MOVE => Some(MOVE),
+        COPY => Some(COPY),
+        REMOVE => Some(REMOVE),
+        _ => None,
+    }
+}
+
 pub fn create(
     conn: &mut PgConnection,
     new_page_link: &NewPageLink,
@@ -64,18 +209,233 @@ pub fn create(
         .get_result(conn)
 }
 
+// hands back the ids of the removed rows so they can be restored from the trash
 pub fn remove_link_by_page_id_and_link_id(
     conn: &mut PgConnection,
     page_id: i32,
     link_id: i32,
-) -> Result<usize, diesel::result::Error> {
+) -> Result<Vec<i32>, diesel::result::Error> {
     diesel::update(page_link::table)
         .set((page_link::deleted_at.eq(Some(now())),))
         .filter(page_link::page_id.eq(page_id))
         .filter(page_link::link_id.eq(link_id))
+        .filter(page_link::deleted_at.is_null())
+        .returning(page_link::id)
+        .get_results(conn)
+}
+
+// a moderator's hide only takes the link off the one page, and says why to its owner
+pub fn hide(
+    conn: &mut PgConnection,
+    page_id: i32,
+    link_id: i32,
+    reason: &str,
+) -> Result<Vec<PageLink>, diesel::result::Error> {
+    diesel::update(page_link::table)
+        .set((
+            page_link::deleted_at.eq(Some(now())),
+            page_link::hidden_reason.eq(Some(sanitize_html(reason))),
+        ))
+        .filter(page_link::page_id.eq(page_id))
+        .filter(page_link::link_id.eq(link_id))
+        .filter(page_link::deleted_at.is_null())
+        .get_results(conn)
+}
+
+// removed links from pages that are still around, a moderator's hidden links stay out
+pub fn read_trash_by_user_id(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<Vec<(PageLink, String)>, diesel::result::Error> {
+    page_link::table
+        .inner_join(page::table)
+        .inner_join(link::table)
+        .filter(page::user_id.eq(user_id))
+        .filter(page::deleted_at.is_null())
+        .filter(page_link::deleted_at.is_not_null())
+        .filter(page_link::hidden_reason.is_null())
+        .filter(link::deleted_at.is_null())
+        .order(page_link::deleted_at.desc())
+        .select((PageLink::as_select(), page::name))
+        .load(conn)
+}
+
+pub fn read_trash_by_id_and_user_id(
+    conn: &mut PgConnection,
+    id: i32,
+    user_id: i32,
+) -> Result<PageLink, diesel::result::Error> {
+    page_link::table
+        .inner_join(page::table)
+        .inner_join(link::table)
+        .filter(page_link::id.eq(id))
+        .filter(page::user_id.eq(user_id))
+        .filter(page::deleted_at.is_null())
+        .filter(page_link::deleted_at.is_not_null())
+        .filter(page_link::hidden_reason.is_null())
+        .filter(link::deleted_at.is_null())
+        .select(PageLink::as_select())
+        .first(conn)
+}
+
+// what moderators took off the user's pages, listed with the reason but never restorable
+pub fn read_hidden_by_user_id(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<Vec<(PageLink, String)>, diesel::result::Error> {
+    page_link::table
+        .inner_join(page::table)
+        .filter(page::user_id.eq(user_id))
+        .filter(page::deleted_at.is_null())
+        .filter(page_link::hidden_reason.is_not_null())
+        .order(page_link::deleted_at.desc())
+        .select((PageLink::as_select(), page::name))
+        .load(conn)
+}
+
+// the newest links as they were saved to pages, for moderators to pick from
+#[allow(clippy::type_complexity)]
+pub fn read_recent_with_page(
+    conn: &mut PgConnection,
+    limit: i64,
+) -> Result<Vec<(PageLink, models::link::Link, models::page::Page)>, diesel::result::Error> {
+    page_link::table
+        .inner_join(link::table)
+        .inner_join(page::table)
+        .filter(page_link::deleted_at.is_null())
+        .filter(link::deleted_at.is_null())
+        .filter(page::deleted_at.is_null())
+        .order(page_link::created_at.desc())
+        .limit(limit)
+        .select((
+            PageLink::as_select(),
+            models::link::Link::as_select(),
+            models::page::Page::as_select(),
+        ))
+        .load(conn)
+}
+
+// a unique violation here means the link was added to the page again since
+pub fn restore(
+    conn: &mut PgConnection,
+    page_link: &PageLink,
+    position: i32,
+) -> Result<usize, diesel::result::Error> {
+    diesel::update(page_link::table.find(page_link.id))
+        .set((
+            page_link::deleted_at.eq(None::<NaiveDateTime>),
+            page_link::position.eq(position),
+            page_link::updated_at.eq(Some(now())),
+        ))
+        .execute(conn)
+}
+
+pub fn purge(
+    conn: &mut PgConnection,
+    page_link: &PageLink,
+) -> Result<usize, diesel::result::Error> {
+    diesel::delete(page_link::table.find(page_link.id)).execute(conn)
+}
+
+// notes are markdown and kept as typed, render_markdown makes them safe to display
+pub fn update_by_page_id_and_link_id(
+    conn: &mut PgConnection,
+    page_id: i32,
+    link_id: i32,
+    details: EditPageLinkApi,
+) -> Result<usize, diesel::result::Error> {
+    diesel::update(page_link::table)
+        .filter(page_link::page_id.eq(page_id))
+        .filter(page_link::link_id.eq(link_id))
+        .filter(page_link::deleted_at.is_null())
+        .set((
+            page_link::name.eq(sanitize_html(&details.name)),
+            page_link::notes.eq(details.notes.trim()),
+            page_link::updated_at.eq(Some(now())),
+        ))
         .execute(conn)
 }
 
+// all or nothing, a link that can't go where it was asked to is reported and
+// left where it was, the owner's row is locked so the target counts hold until it's done
+pub fn bulk_update(
+    conn: &mut PgConnection,
+    user_id: i32,
+    page_id: i32,
+    bulk: &BulkLinksApi,
+    max_links: usize,
+) -> Result<Vec<BulkLinkResult>, diesel::result::Error> {
+    conn.transaction(|conn| {
+        models::user::lock_by_id(conn, user_id)?;
+        let page_links = page_link::table
+            .filter(page_link::page_id.eq(page_id))
+            .filter(page_link::link_id.eq_any(&bulk.link_ids))
+            .filter(page_link::deleted_at.is_null())
+            .load::<PageLink>(conn)?;
+
+        let mut results = vec![];
+        for link_id in &bulk.link_ids {
+            let Some(page_link) = page_links.iter().find(|pl| pl.link_id == *link_id) else {
+                results.push(BulkLinkResult {
+                    link_id: *link_id,
+                    name: link_id.to_string(),
+                    outcome: NOT_ON_PAGE,
+                });
+                continue;
+            };
+
+            let refused = match bulk.target_page_id {
+                Some(target_page_id) if bulk.action != REMOVE => {
+                    copy_to_page(conn, page_link, target_page_id, max_links)?
+                }
+                _ => None,
+            };
+            let outcome = match (refused, bulk.action) {
+                (Some(reason), _) => reason,
+                (None, COPY) => COPIED,
+                (None, action) => {
+                    remove_link_by_page_id_and_link_id(conn, page_id, page_link.link_id)?;
+                    if action == MOVE {
+                        MOVED
+                    } else {
+                        REMOVED
+                    }
+                }
+            };
+            results.push(BulkLinkResult {
+                link_id: page_link.link_id,
+                name: page_link.name.clone(),
+                outcome,
+            });
+        }
+        Ok(results)
+    })
+}
+
+// returns why the link couldn't be copied, the savepoint keeps a unique
+// violation from aborting the whole batch
+fn copy_to_page(
+    conn: &mut PgConnection,
+    page_link: &PageLink,
+    target_page_id: i32,
+    max_links: usize,
+) -> Result<Option<&'static str>, diesel::result::Error> {
+    if get_count_of_links_per_page(conn, target_page_id)? >= max_links {
+        return Ok(Some(PAGE_FULL));
+    }
+    let position = next_position(conn, target_page_id)?;
+    let inserted = conn
+        .transaction(|conn| NewPageLink::copy_of(page_link, target_page_id, position).insert(conn));
+    match inserted {
+        Ok(_) => Ok(None),
+        Err(diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::UniqueViolation,
+            _,
+        )) => Ok(Some(ALREADY_ON_PAGE)),
+        Err(e) => Err(e),
+    }
+}
+
 pub fn get_count_of_links_per_page(
     conn: &mut PgConnection,
     page_id: i32,
@@ -87,3 +447,236 @@ pub fn get_count_of_links_per_page(
         .get_result(conn)
         .map(|v: i64| v as usize)
 }
+
+// new links go to the bottom of the page
+pub fn next_position(conn: &mut PgConnection, page_id: i32) -> Result<i32, diesel::result::Error> {
+    page_link::table
+        .filter(page_link::page_id.eq(page_id))
+        .filter(page_link::deleted_at.is_null())
+        .select(diesel::dsl::max(page_link::position))
+        .first::<Option<i32>>(conn)
+        .map(|position| position.map_or(0, |position| position + 1))
+}
+
+pub fn read_link_ids_by_page_id(
+    conn: &mut PgConnection,
+    page_id: i32,
+) -> Result<Vec<i32>, diesel::result::Error> {
+    page_link::table
+        .filter(page_link::page_id.eq(page_id))
+        .filter(page_link::deleted_at.is_null())
+        .order((page_link::position.asc(), page_link::id.asc()))
+        .select(page_link::link_id)
+        .load(conn)
+}
+
+// the whole page is renumbered, so a short or repeated list can't leave two links sharing a position
+pub fn reorder(
+    conn: &mut PgConnection,
+    page_id: i32,
+    link_ids: &[i32],
+) -> Result<(), diesel::result::Error> {
+    conn.transaction(|conn| {
+        let current = page_link::table
+            .filter(page_link::page_id.eq(page_id))
+            .filter(page_link::deleted_at.is_null())
+            .order((page_link::position.asc(), page_link::id.asc()))
+            .select(page_link::link_id)
+            .for_update()
+            .load::<i32>(conn)?;
+
+        for (position, link_id) in arrange(&current, link_ids).iter().enumerate() {
+            diesel::update(page_link::table)
+                .filter(page_link::page_id.eq(page_id))
+                .filter(page_link::link_id.eq(link_id))
+                .filter(page_link::deleted_at.is_null())
+                .set((
+                    page_link::position.eq(position as i32),
+                    page_link::updated_at.eq(Some(now())),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+}
+
+// the owner only has the screens they've loaded, so the links they sent go first in
+// that order and the rest follow as they were, ids that aren't on the page are dropped
+pub fn arrange(current: &[i32], link_ids: &[i32]) -> Vec<i32> {
+    let mut arranged: Vec<i32> = Vec::with_capacity(current.len());
+    for link_id in link_ids.iter().chain(current) {
+        if current.contains(link_id) && !arranged.contains(link_id) {
+            arranged.push(*link_id);
+        }
+    }
+    arranged
+}
+
+pub fn parse_order(links: &str) -> Option<Vec<i32>> {
+    links
+        .split(',')
+        .filter(|id| !id.trim().is_empty())
+        .map(|id| id.trim().parse::<i32>().ok())
+        .collect()
+}
+
+// swaps the link with its neighbour, None when it's already at that end
+pub fn shift(link_ids: &[i32], link_id: i32, direction: &str) -> Option<Vec<i32>> {
+    let from = link_ids.iter().position(|id| *id == link_id)?;
+    let to = match direction {
+        UP => from.checked_sub(1)?,
+        DOWN => from + 1,
+        _ => return None,
+    };
+    if to >= link_ids.len() {
+        return None;
+    }
+
+    let mut link_ids = link_ids.to_vec();
+    link_ids.swap(from, to);
+    Some(link_ids)
+}
+
+#[test]
+fn test_bulk_links_form() {
+    let form = |pairs: &[(&str, &str)]| {
+        BulkLinksApi::from_form(
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        )
+    };
+
+    let bulk = form(&[
+        ("csrf_token", "abc"),
+        ("links", "3"),
+        ("links", "7"),
+        ("action", "move"),
+        ("target_page_id", "2"),
+    ])
+    .unwrap();
+    assert_eq!(bulk.action, MOVE);
+    assert_eq!(bulk.link_ids, vec![3, 7]);
+    assert_eq!(bulk.target_page_id, Some(2));
+
+    assert!(form(&[("links", "3"), ("action", "remove"), ("target_page_id", "")]).is_some());
+    assert!(form(&[("links", "3"), ("action", "copy")]).is_none());
+    assert!(form(&[("action", "remove")]).is_none());
+    assert!(form(&[("links", "three"), ("action", "remove")]).is_none());
+    assert!(form(&[("links", "3"), ("action", "archive")]).is_none());
+}
+
+#[test]
+fn test_link_order() {
+    assert_eq!(parse_order("3, 1,2"), Some(vec![3, 1, 2]));
+    assert_eq!(parse_order(""), Some(vec![]));
+    assert_eq!(parse_order("3,one"), None);
+
+    let link_ids = vec![3, 1, 2];
+    assert_eq!(shift(&link_ids, 1, UP), Some(vec![1, 3, 2]));
+    assert_eq!(shift(&link_ids, 1, DOWN), Some(vec![3, 2, 1]));
+    assert_eq!(shift(&link_ids, 3, UP), None);
+    assert_eq!(shift(&link_ids, 2, DOWN), None);
+    assert_eq!(shift(&link_ids, 4, UP), None);
+    assert_eq!(shift(&link_ids, 1, "sideways"), None);
+
+    assert_eq!(arrange(&link_ids, &[2, 3, 1]), vec![2, 3, 1]);
+    assert_eq!(arrange(&link_ids, &[2]), vec![2, 3, 1]);
+    assert_eq!(arrange(&link_ids, &[1, 1, 9]), vec![1, 3, 2]);
+    assert_eq!(arrange(&link_ids, &[]), vec![3, 1, 2]);
+}
+
+#[test]
+fn test_reorder_renumbers_the_whole_page() {
+    let Some(mut conn) = crate::db_conn::test_connection() else {
+        return;
+    };
+    conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+        let user = models::user::insert_test_user(conn, "reorder", "password");
+        let page = models::page::insert_test_page(conn, &user, "Reading list");
+        let mut link_ids = vec![];
+        for (position, url) in [
+            "https://a.example",
+            "https://b.example",
+            "https://c.example",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let link = models::link::insert_test_link(conn, url, user.id)?;
+            NewPageLink::new(page.id, link.id, url.to_string(), position as i32).insert(conn)?;
+            link_ids.push(link.id);
+        }
+
+        // a repeated, partial list with a stranger in it
+        reorder(conn, page.id, &[link_ids[2], link_ids[2], -1])?;
+
+        let positions = page_link::table
+            .filter(page_link::page_id.eq(page.id))
+            .order(page_link::position.asc())
+            .select((page_link::link_id, page_link::position))
+            .load::<(i32, i32)>(conn)?;
+        assert_eq!(
+            positions,
+            vec![(link_ids[2], 0), (link_ids[0], 1), (link_ids[1], 2)]
+        );
+        Ok(())
+    });
+}
+
+#[test]
+fn test_link_cursor() {
+    let cursor = LinkCursor {
+        position: 3,
+        id: 17,
+    };
+    assert_eq!(cursor.to_param(), "3.17");
+    assert_eq!(LinkCursor::parse("3.17"), Some(cursor));
+    assert_eq!(
+        LinkCursor::parse(" 0.4 "),
+        Some(LinkCursor { position: 0, id: 4 })
+    );
+
+    assert!(LinkCursor::parse("").is_none());
+    assert!(LinkCursor::parse("17").is_none());
+    assert!(LinkCursor::parse("a.17").is_none());
+}
+
+#[test]
+fn test_hide_is_scoped_to_one_page() {
+    let Some(mut conn) = crate::db_conn::test_connection() else {
+        return;
+    };
+    conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+        let owner = models::user::insert_test_user(conn, "hideowner", "password");
+        let other = models::user::insert_test_user(conn, "hideother", "password");
+        let page = models::page::insert_test_page(conn, &owner, "Reading list");
+        let other_page = models::page::insert_test_page(conn, &other, "Also reading");
+        let link = models::link::insert_test_link(conn, "https://shared.example", owner.id)?;
+        NewPageLink::new(page.id, link.id, String::from("Shared"), 0).insert(conn)?;
+        NewPageLink::new(other_page.id, link.id, String::from("Shared"), 0).insert(conn)?;
+
+        let hidden = hide(conn, page.id, link.id, "Spam <b>link</b>")?;
+        assert_eq!(hidden.len(), 1);
+
+        let (links, _) = models::link::read_links_by_page_after(conn, &page, None, 10)?;
+        assert!(links.is_empty());
+        let (links, _) = models::link::read_links_by_page_after(conn, &other_page, None, 10)?;
+        assert_eq!(links.len(), 1);
+
+        // the owner can read why, but it's not theirs to restore
+        assert!(read_trash_by_user_id(conn, owner.id)?.is_empty());
+        assert!(read_trash_by_id_and_user_id(conn, hidden[0].id, owner.id).is_err());
+        let listed = read_hidden_by_user_id(conn, owner.id)?;
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].1, "Reading list");
+        assert!(!listed[0]
+            .0
+            .hidden_reason
+            .as_deref()
+            .unwrap()
+            .contains("<b>"));
+        Ok(())
+    });
+}
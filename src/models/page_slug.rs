@@ -0,0 +1,74 @@
+use crate::{
+    models,
+    schema::{page, page_slug, user},
+    utils::now,
+};
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+
+// a slug a page went by before it was renamed
+#[derive(Clone, Debug, Identifiable, Associations, Selectable, Queryable)]
+#[diesel(belongs_to(models::page::Page))]
+#[diesel(table_name = page_slug)]
+pub struct PageSlug {
+    pub id: i32,
+    pub page_id: i32,
+    pub slug: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = page_slug)]
+pub struct NewPageSlug {
+    pub page_id: i32,
+    pub slug: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl NewPageSlug {
+    pub fn new(page_id: i32, slug: &str) -> Self {
+        NewPageSlug {
+            page_id,
+            slug: slug.to_string(),
+            created_at: now(),
+            updated_at: None,
+            deleted_at: None,
+        }
+    }
+
+    pub fn insert(&self, conn: &mut PgConnection) -> Result<PageSlug, diesel::result::Error> {
+        create(conn, self)
+    }
+}
+
+pub fn create(
+    conn: &mut PgConnection,
+    new_page_slug: &NewPageSlug,
+) -> Result<PageSlug, diesel::result::Error> {
+    diesel::insert_into(page_slug::table)
+        .values(new_page_slug)
+        .get_result(conn)
+}
+
+// the page that most recently gave the slug up, a page using it now takes precedence
+pub fn read_page_by_username_and_slug(
+    conn: &mut PgConnection,
+    username: &str,
+    slug: &str,
+) -> Result<models::page::Page, diesel::result::Error> {
+    page_slug::table
+        .inner_join(page::table)
+        .inner_join(user::table.on(page::user_id.eq(user::id)))
+        .filter(user::username.eq(username))
+        .filter(user::deleted_at.is_null())
+        .filter(page_slug::slug.eq(slug))
+        .filter(page_slug::deleted_at.is_null())
+        .filter(page::deleted_at.is_null())
+        .order(page_slug::created_at.desc())
+        .select(models::page::Page::as_select())
+        .first(conn)
+}
@@ -0,0 +1,338 @@
+use crate::{
+    config::Config,
+    models,
+    schema::{passkey, passkey_challenge},
+    utils::{now, sanitize_html},
+};
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use webauthn_rs::prelude as webauthn;
+
+pub const CHALLENGE_DURATION_MINUTES: i64 = 5;
+
+#[derive(Clone, Debug, Identifiable, Associations, Selectable, Queryable)]
+#[diesel(belongs_to(models::user::User))]
+#[diesel(table_name = passkey)]
+pub struct Passkey {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub credential: String,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl Passkey {
+    pub fn credential(&self) -> Result<webauthn::Passkey, serde_json::Error> {
+        serde_json::from_str(&self.credential)
+    }
+
+    pub fn inject_values(&self, string: &str) -> String {
+        string
+            .replace("{passkey.id}", &self.id.to_string())
+            .replace("{passkey.name}", &self.name)
+            .replace(
+                "{passkey.created_at}",
+                &self.created_at.format("%Y-%m-%d").to_string(),
+            )
+            .replace(
+                "{passkey.last_used_at}",
+                &match self.last_used_at {
+                    Some(last_used_at) => last_used_at.format("%Y-%m-%d").to_string(),
+                    None => String::from("never"),
+                },
+            )
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NewPasskeyApi {
+    pub name: String,
+    pub credential: webauthn::RegisterPublicKeyCredential,
+}
+
+#[derive(Deserialize)]
+pub struct PasskeyLoginApi {
+    pub username: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = passkey)]
+pub struct NewPasskey {
+    pub user_id: i32,
+    pub name: String,
+    pub credential: String,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl NewPasskey {
+    pub fn new(
+        user_id: i32,
+        name: String,
+        credential: &webauthn::Passkey,
+    ) -> Result<Self, serde_json::Error> {
+        Ok(NewPasskey {
+            user_id,
+            name: sanitize_html(&name),
+            credential: serde_json::to_string(credential)?,
+            last_used_at: None,
+            created_at: now(),
+            updated_at: None,
+            deleted_at: None,
+        })
+    }
+
+    pub fn insert(&self, conn: &mut PgConnection) -> Result<Passkey, diesel::result::Error> {
+        create(conn, self)
+    }
+}
+
+#[derive(Clone, Debug, Identifiable, Associations, Selectable, Queryable)]
+#[diesel(belongs_to(models::user::User))]
+#[diesel(table_name = passkey_challenge)]
+pub struct PasskeyChallenge {
+    pub id: i32,
+    pub user_id: i32,
+    pub token_hash: String,
+    pub state: String,
+    pub valid_until: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl PasskeyChallenge {
+    pub fn state<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_str(&self.state)
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = passkey_challenge)]
+pub struct NewPasskeyChallenge {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub state: String,
+    pub valid_until: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl NewPasskeyChallenge {
+    pub fn new<T: Serialize>(
+        user_id: i32,
+        token_hash: String,
+        state: &T,
+    ) -> Result<Self, serde_json::Error> {
+        Ok(NewPasskeyChallenge {
+            user_id,
+            token_hash,
+            state: serde_json::to_string(state)?,
+            valid_until: now() + chrono::Duration::minutes(CHALLENGE_DURATION_MINUTES),
+            created_at: now(),
+            updated_at: None,
+            deleted_at: None,
+        })
+    }
+
+    pub fn insert(
+        &self,
+        conn: &mut PgConnection,
+    ) -> Result<PasskeyChallenge, diesel::result::Error> {
+        create_challenge(conn, self)
+    }
+}
+
+pub fn webauthn(config: &Config) -> Result<webauthn::Webauthn, webauthn::WebauthnError> {
+    let origin = webauthn::Url::parse(&config.webauthn_rp_origin)
+        .expect("WEBAUTHN_RP_ORIGIN must be a valid url");
+
+    webauthn::WebauthnBuilder::new(&config.webauthn_rp_id, &origin)?
+        .rp_name("Digitheque")
+        .build()
+}
+
+// webauthn wants a uuid for the user handle, ours are serial ids
+pub fn user_handle(user_id: i32) -> webauthn::Uuid {
+    webauthn::Uuid::from_u128(user_id as u128)
+}
+
+pub fn start_registration(
+    webauthn: &webauthn::Webauthn,
+    user: &models::user::User,
+    existing: &[webauthn::Passkey],
+) -> Result<
+    (
+        webauthn::CreationChallengeResponse,
+        webauthn::PasskeyRegistration,
+    ),
+    webauthn::WebauthnError,
+> {
+    let exclude_credentials = existing
+        .iter()
+        .map(|passkey| passkey.cred_id().clone())
+        .collect::<Vec<webauthn::CredentialID>>();
+
+    webauthn.start_passkey_registration(
+        user_handle(user.id),
+        &user.username,
+        &user.username,
+        Some(exclude_credentials),
+    )
+}
+
+pub fn create(
+    conn: &mut PgConnection,
+    new_passkey: &NewPasskey,
+) -> Result<Passkey, diesel::result::Error> {
+    diesel::insert_into(passkey::table)
+        .values(new_passkey)
+        .get_result(conn)
+}
+
+pub fn read_by_user_id(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<Vec<Passkey>, diesel::result::Error> {
+    passkey::table
+        .filter(passkey::user_id.eq(user_id))
+        .filter(passkey::deleted_at.is_null())
+        .load::<Passkey>(conn)
+}
+
+pub fn read_by_id_and_user_id(
+    conn: &mut PgConnection,
+    id: i32,
+    user_id: i32,
+) -> Result<Passkey, diesel::result::Error> {
+    passkey::table
+        .filter(passkey::id.eq(id))
+        .filter(passkey::user_id.eq(user_id))
+        .filter(passkey::deleted_at.is_null())
+        .first::<Passkey>(conn)
+}
+
+pub fn update_credential(
+    conn: &mut PgConnection,
+    passkey: &Passkey,
+    credential: &webauthn::Passkey,
+) -> QueryResult<usize> {
+    let credential = serde_json::to_string(credential)
+        .map_err(|e| diesel::result::Error::SerializationError(Box::new(e)))?;
+
+    diesel::update(passkey)
+        .set((
+            passkey::credential.eq(credential),
+            passkey::last_used_at.eq(Some(now())),
+            passkey::updated_at.eq(Some(now())),
+        ))
+        .execute(conn)
+}
+
+pub fn delete(conn: &mut PgConnection, passkey: &Passkey) -> QueryResult<usize> {
+    diesel::update(passkey)
+        .set((passkey::deleted_at.eq(Some(now())),))
+        .execute(conn)
+}
+
+pub fn delete_by_user_id(conn: &mut PgConnection, user_id: i32) -> QueryResult<usize> {
+    diesel::update(passkey::table)
+        .filter(passkey::user_id.eq(user_id))
+        .filter(passkey::deleted_at.is_null())
+        .set((passkey::deleted_at.eq(Some(now())),))
+        .execute(conn)
+}
+
+pub fn create_challenge(
+    conn: &mut PgConnection,
+    new_challenge: &NewPasskeyChallenge,
+) -> Result<PasskeyChallenge, diesel::result::Error> {
+    diesel::insert_into(passkey_challenge::table)
+        .values(new_challenge)
+        .get_result(conn)
+}
+
+pub fn read_challenge_by_token_hash(
+    conn: &mut PgConnection,
+    token_hash: &str,
+) -> Result<PasskeyChallenge, diesel::result::Error> {
+    passkey_challenge::table
+        .filter(passkey_challenge::token_hash.eq(token_hash))
+        .filter(passkey_challenge::valid_until.gt(now()))
+        .filter(passkey_challenge::deleted_at.is_null())
+        .first::<PasskeyChallenge>(conn)
+}
+
+// a challenge can only be answered once
+pub fn delete_challenge(
+    conn: &mut PgConnection,
+    challenge: &PasskeyChallenge,
+) -> QueryResult<usize> {
+    diesel::update(challenge)
+        .set((passkey_challenge::deleted_at.eq(Some(now())),))
+        .execute(conn)
+}
+
+#[test]
+fn test_passkey_ceremony() {
+    use webauthn_authenticator_rs::{softpasskey::SoftPasskey, WebauthnAuthenticator};
+
+    let Some(mut conn) = crate::db_conn::test_connection() else {
+        return;
+    };
+    let origin = webauthn::Url::parse("https://localhost:4000").unwrap();
+    let webauthn = webauthn::WebauthnBuilder::new("localhost", &origin)
+        .unwrap()
+        .build()
+        .unwrap();
+    let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new());
+
+    conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+        let user = models::user::insert_test_user(conn, "passkey", "password");
+
+        // the registration state waits in the database between the two requests
+        let (creation, registration) = start_registration(&webauthn, &user, &[]).unwrap();
+        NewPasskeyChallenge::new(user.id, String::from("register"), &registration)
+            .unwrap()
+            .insert(conn)?;
+        let credential = authenticator
+            .do_registration(origin.clone(), creation)
+            .unwrap();
+        let challenge = read_challenge_by_token_hash(conn, "register")?;
+        let credential = webauthn
+            .finish_passkey_registration(&credential, &challenge.state().unwrap())
+            .unwrap();
+        delete_challenge(conn, &challenge)?;
+        assert!(read_challenge_by_token_hash(conn, "register").is_err());
+
+        let passkey = NewPasskey::new(user.id, String::from("laptop"), &credential)
+            .unwrap()
+            .insert(conn)?;
+        let stored = read_by_user_id(conn, user.id)?
+            .into_iter()
+            .map(|passkey| passkey.credential().unwrap())
+            .collect::<Vec<webauthn::Passkey>>();
+
+        let (request, authentication) = webauthn.start_passkey_authentication(&stored).unwrap();
+        let assertion = authenticator
+            .do_authentication(origin.clone(), request)
+            .unwrap();
+        let result = webauthn
+            .finish_passkey_authentication(&assertion, &authentication)
+            .unwrap();
+
+        let mut credential = passkey.credential().unwrap();
+        assert!(credential.update_credential(&result).is_some());
+        update_credential(conn, &passkey, &credential)?;
+        let passkey = read_by_id_and_user_id(conn, passkey.id, user.id)?;
+        assert!(passkey.last_used_at.is_some());
+        Ok(())
+    });
+}
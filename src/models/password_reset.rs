@@ -0,0 +1,98 @@
+use crate::{models, schema::password_reset, utils::now};
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+
+pub const RESET_DURATION_MINUTES: i64 = 30;
+
+#[derive(Clone, Debug, Identifiable, Associations, Selectable, Queryable)]
+#[diesel(belongs_to(models::user::User))]
+#[diesel(table_name = password_reset)]
+pub struct PasswordReset {
+    pub id: i32,
+    pub user_id: i32,
+    pub token_hash: String,
+    pub valid_until: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = password_reset)]
+pub struct NewPasswordReset {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub valid_until: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl NewPasswordReset {
+    pub fn new(user_id: i32, token_hash: String) -> Self {
+        NewPasswordReset {
+            user_id,
+            token_hash,
+            valid_until: now() + chrono::Duration::minutes(RESET_DURATION_MINUTES),
+            used_at: None,
+            created_at: now(),
+            updated_at: None,
+            deleted_at: None,
+        }
+    }
+
+    pub fn insert(&self, conn: &mut PgConnection) -> Result<PasswordReset, diesel::result::Error> {
+        create(conn, self)
+    }
+}
+
+pub fn create(
+    conn: &mut PgConnection,
+    new_reset: &NewPasswordReset,
+) -> Result<PasswordReset, diesel::result::Error> {
+    diesel::insert_into(password_reset::table)
+        .values(new_reset)
+        .get_result(conn)
+}
+
+// only resets that are unused and still fresh can be redeemed
+pub fn read_valid_by_token_hash(
+    conn: &mut PgConnection,
+    token_hash: &str,
+) -> Result<PasswordReset, diesel::result::Error> {
+    password_reset::table
+        .filter(password_reset::token_hash.eq(token_hash))
+        .filter(password_reset::valid_until.gt(now()))
+        .filter(password_reset::used_at.is_null())
+        .filter(password_reset::deleted_at.is_null())
+        .first::<PasswordReset>(conn)
+}
+
+// checking and using up the token is one statement, so a link only ever works once
+pub fn redeem(
+    conn: &mut PgConnection,
+    token_hash: &str,
+) -> Result<PasswordReset, diesel::result::Error> {
+    diesel::update(password_reset::table)
+        .filter(password_reset::token_hash.eq(token_hash))
+        .filter(password_reset::valid_until.gt(now()))
+        .filter(password_reset::used_at.is_null())
+        .filter(password_reset::deleted_at.is_null())
+        .set((
+            password_reset::used_at.eq(Some(now())),
+            password_reset::updated_at.eq(Some(now())),
+        ))
+        .get_result(conn)
+}
+
+// asking for a new link retires the old ones
+pub fn delete_by_user_id(conn: &mut PgConnection, user_id: i32) -> QueryResult<usize> {
+    diesel::update(password_reset::table)
+        .filter(password_reset::user_id.eq(user_id))
+        .filter(password_reset::used_at.is_null())
+        .filter(password_reset::deleted_at.is_null())
+        .set((password_reset::deleted_at.eq(Some(now())),))
+        .execute(conn)
+}
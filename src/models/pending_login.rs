@@ -0,0 +1,102 @@
+use crate::{models, schema::pending_login, utils::now};
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+
+pub const PENDING_LOGIN_DURATION_MINUTES: i64 = 5;
+pub const PENDING_LOGIN_MAX_ATTEMPTS: i32 = 5;
+
+// a password that checked out, waiting on the second factor before we start a session
+#[derive(Clone, Debug, Identifiable, Associations, Selectable, Queryable)]
+#[diesel(belongs_to(models::user::User))]
+#[diesel(table_name = pending_login)]
+pub struct PendingLogin {
+    pub id: i32,
+    pub user_id: i32,
+    pub token_hash: String,
+    pub remember: bool,
+    pub attempts: i32,
+    pub valid_until: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = pending_login)]
+pub struct NewPendingLogin {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub remember: bool,
+    pub attempts: i32,
+    pub valid_until: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl NewPendingLogin {
+    pub fn new(user_id: i32, token_hash: String, remember: bool) -> Self {
+        NewPendingLogin {
+            user_id,
+            token_hash,
+            remember,
+            attempts: 0,
+            valid_until: now() + chrono::Duration::minutes(PENDING_LOGIN_DURATION_MINUTES),
+            created_at: now(),
+            updated_at: None,
+            deleted_at: None,
+        }
+    }
+
+    pub fn insert(&self, conn: &mut PgConnection) -> Result<PendingLogin, diesel::result::Error> {
+        create(conn, self)
+    }
+}
+
+pub fn create(
+    conn: &mut PgConnection,
+    new_pending_login: &NewPendingLogin,
+) -> Result<PendingLogin, diesel::result::Error> {
+    diesel::insert_into(pending_login::table)
+        .values(new_pending_login)
+        .get_result(conn)
+}
+
+// a pending login dies when it's stale or has been guessed at too often
+pub fn read_valid_by_token_hash(
+    conn: &mut PgConnection,
+    token_hash: &str,
+) -> Result<PendingLogin, diesel::result::Error> {
+    pending_login::table
+        .filter(pending_login::token_hash.eq(token_hash))
+        .filter(pending_login::valid_until.gt(now()))
+        .filter(pending_login::attempts.lt(PENDING_LOGIN_MAX_ATTEMPTS))
+        .filter(pending_login::deleted_at.is_null())
+        .first::<PendingLogin>(conn)
+}
+
+pub fn increment_attempts(
+    conn: &mut PgConnection,
+    pending_login: &PendingLogin,
+) -> QueryResult<PendingLogin> {
+    diesel::update(pending_login)
+        .set((
+            pending_login::attempts.eq(pending_login::attempts + 1),
+            pending_login::updated_at.eq(Some(now())),
+        ))
+        .get_result(conn)
+}
+
+pub fn delete(conn: &mut PgConnection, pending_login: &PendingLogin) -> QueryResult<usize> {
+    diesel::update(pending_login)
+        .set((pending_login::deleted_at.eq(Some(now())),))
+        .execute(conn)
+}
+
+pub fn delete_by_user_id(conn: &mut PgConnection, user_id: i32) -> QueryResult<usize> {
+    diesel::update(pending_login::table)
+        .filter(pending_login::user_id.eq(user_id))
+        .filter(pending_login::deleted_at.is_null())
+        .set((pending_login::deleted_at.eq(Some(now())),))
+        .execute(conn)
+}
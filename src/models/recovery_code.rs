@@ -0,0 +1,126 @@
+use crate::{
+    models,
+    schema::recovery_code,
+    utils::{hash_token, now},
+};
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+use rand::Rng;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+// no vowels or lookalikes, so a code can't spell anything or be misread
+const RECOVERY_CODE_ALPHABET: &[u8] = b"23456789bcdfghjkmnpqrstvwxz";
+
+#[derive(Clone, Debug, Identifiable, Associations, Selectable, Queryable)]
+#[diesel(belongs_to(models::user::User))]
+#[diesel(table_name = recovery_code)]
+pub struct RecoveryCode {
+    pub id: i32,
+    pub user_id: i32,
+    pub code_hash: String,
+    pub used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = recovery_code)]
+pub struct NewRecoveryCode {
+    pub user_id: i32,
+    pub code_hash: String,
+    pub used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl NewRecoveryCode {
+    pub fn new(user_id: i32, code: &str) -> Self {
+        NewRecoveryCode {
+            user_id,
+            code_hash: hash_token(&normalize(code)),
+            used_at: None,
+            created_at: now(),
+            updated_at: None,
+            deleted_at: None,
+        }
+    }
+}
+
+// formatted like "xxxxx-xxxxx" so it is easy to copy down by hand
+pub fn generate_code() -> String {
+    let mut rng = rand::thread_rng();
+    let mut code: String = (0..10)
+        .map(|_| RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+        .collect();
+    code.insert(5, '-');
+    code
+}
+
+pub fn normalize(code: &str) -> String {
+    code.trim().to_lowercase().replace(' ', "")
+}
+
+// replaces any codes the user had before, the plain codes are only ever returned here
+pub fn create_for_user(conn: &mut PgConnection, user_id: i32) -> QueryResult<Vec<String>> {
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| generate_code()).collect();
+    let new_codes: Vec<NewRecoveryCode> = codes
+        .iter()
+        .map(|code| NewRecoveryCode::new(user_id, code))
+        .collect();
+
+    conn.transaction(|conn| {
+        delete_by_user_id(conn, user_id)?;
+        diesel::insert_into(recovery_code::table)
+            .values(&new_codes)
+            .execute(conn)?;
+        Ok(codes)
+    })
+}
+
+pub fn count_unused_by_user_id(conn: &mut PgConnection, user_id: i32) -> QueryResult<i64> {
+    recovery_code::table
+        .filter(recovery_code::user_id.eq(user_id))
+        .filter(recovery_code::used_at.is_null())
+        .filter(recovery_code::deleted_at.is_null())
+        .count()
+        .get_result(conn)
+}
+
+// each code works once, returns whether one was used up
+pub fn redeem(conn: &mut PgConnection, user_id: i32, code: &str) -> QueryResult<bool> {
+    let used = diesel::update(recovery_code::table)
+        .filter(recovery_code::user_id.eq(user_id))
+        .filter(recovery_code::code_hash.eq(hash_token(&normalize(code))))
+        .filter(recovery_code::used_at.is_null())
+        .filter(recovery_code::deleted_at.is_null())
+        .set((
+            recovery_code::used_at.eq(Some(now())),
+            recovery_code::updated_at.eq(Some(now())),
+        ))
+        .execute(conn)?;
+
+    Ok(used > 0)
+}
+
+pub fn delete_by_user_id(conn: &mut PgConnection, user_id: i32) -> QueryResult<usize> {
+    diesel::update(recovery_code::table)
+        .filter(recovery_code::user_id.eq(user_id))
+        .filter(recovery_code::deleted_at.is_null())
+        .set((recovery_code::deleted_at.eq(Some(now())),))
+        .execute(conn)
+}
+
+#[test]
+fn test_recovery_codes() {
+    let code = generate_code();
+    assert_eq!(code.len(), 11);
+    assert_eq!(code.chars().nth(5), Some('-'));
+    assert_eq!(normalize(&format!("  {} ", code.to_uppercase())), code);
+    assert_eq!(
+        NewRecoveryCode::new(1, &code.to_uppercase()).code_hash,
+        NewRecoveryCode::new(1, &code).code_hash
+    );
+}
@@ -1,9 +1,11 @@
-use crate::{models, schema::session, utils::now};
-use chrono::naive::NaiveDateTime;
+use crate::{
+    models,
+    schema::session,
+    utils::{generate_token, now, sanitize_html},
+};
+use chrono::{naive::NaiveDateTime, Duration};
 use diesel::prelude::*;
 
-const SESSION_DURATION_MINUTES: i64 = 60;
-
 #[derive(Clone, Debug, Identifiable, Associations, Selectable, Queryable, AsChangeset)]
 #[diesel(belongs_to(models::user::User))]
 #[diesel(table_name = session)]
@@ -14,6 +16,13 @@ pub struct Session {
     pub created_at: NaiveDateTime,
     pub updated_at: Option<NaiveDateTime>,
     pub deleted_at: Option<NaiveDateTime>,
+    pub token_hash: String,
+    pub expires_at: NaiveDateTime,
+    pub remember: bool,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub last_seen_at: NaiveDateTime,
+    pub csrf_token: String,
 }
 
 impl Session {
@@ -25,6 +34,55 @@ impl Session {
             created_at: self.created_at.clone(),
             updated_at: Some(now()),
             deleted_at: self.deleted_at.clone(),
+            token_hash: self.token_hash.clone(),
+            expires_at: self.expires_at.clone(),
+            remember: self.remember,
+            user_agent: self.user_agent.clone(),
+            ip: self.ip.clone(),
+            last_seen_at: self.last_seen_at.clone(),
+            csrf_token: self.csrf_token.clone(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.valid_until < now() || self.expires_at < now()
+    }
+
+    pub fn inject_values(&self, string: &str) -> String {
+        string
+            .replace("{session.id}", &self.id.to_string())
+            // both come straight from request headers
+            .replace(
+                "{session.user_agent}",
+                &sanitize_html(self.user_agent.as_deref().unwrap_or("Unknown device")),
+            )
+            .replace(
+                "{session.ip}",
+                &sanitize_html(self.ip.as_deref().unwrap_or("unknown")),
+            )
+            .replace(
+                "{session.created_at}",
+                &self.created_at.format("%Y-%m-%d %H:%M").to_string(),
+            )
+            .replace(
+                "{session.last_seen_at}",
+                &self.last_seen_at.format("%Y-%m-%d %H:%M").to_string(),
+            )
+    }
+}
+
+// where a session was started from, so a user can tell their devices apart
+#[derive(Clone, Debug)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+impl ClientInfo {
+    pub fn new(user_agent: Option<String>, ip: Option<String>) -> Self {
+        ClientInfo {
+            user_agent: user_agent.map(|user_agent| user_agent.chars().take(512).collect()),
+            ip: ip.map(|ip| ip.chars().take(64).collect()),
         }
     }
 }
@@ -37,16 +95,38 @@ pub struct NewSession {
     pub created_at: NaiveDateTime,
     pub updated_at: Option<NaiveDateTime>,
     pub deleted_at: Option<NaiveDateTime>,
+    pub token_hash: String,
+    pub expires_at: NaiveDateTime,
+    pub remember: bool,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub last_seen_at: NaiveDateTime,
+    pub csrf_token: String,
 }
 
 impl NewSession {
-    pub fn new(user_id: i32) -> Self {
+    pub fn new(
+        user_id: i32,
+        token_hash: String,
+        idle: Duration,
+        lifetime: Duration,
+        remember: bool,
+        client: ClientInfo,
+    ) -> Self {
         NewSession {
             user_id: user_id,
-            valid_until: now() + chrono::Duration::minutes(SESSION_DURATION_MINUTES),
+            valid_until: now() + idle.min(lifetime),
             created_at: now(),
             updated_at: None,
             deleted_at: None,
+            token_hash,
+            expires_at: now() + lifetime,
+            remember,
+            user_agent: client.user_agent,
+            ip: client.ip,
+            last_seen_at: now(),
+            // forms and htmx requests echo this back to prove they came from us
+            csrf_token: generate_token(),
         }
     }
 
@@ -74,6 +154,16 @@ pub fn read_by_id(conn: &mut PgConnection, id: i32) -> Result<Session, diesel::r
         .first::<Session>(conn)
 }
 
+pub fn read_by_token_hash(
+    conn: &mut PgConnection,
+    token_hash: &str,
+) -> Result<Session, diesel::result::Error> {
+    session::table
+        .filter(session::token_hash.eq(token_hash))
+        .filter(session::deleted_at.is_null())
+        .first::<Session>(conn)
+}
+
 pub fn read_by_user_id(
     conn: &mut PgConnection,
     user_id: i32,
@@ -84,16 +174,67 @@ pub fn read_by_user_id(
         .first::<Session>(conn)
 }
 
+// push the idle timeout forward, but never past the absolute lifetime
+pub fn refresh(conn: &mut PgConnection, session: &Session, idle: Duration) -> QueryResult<Session> {
+    let valid_until = (now() + idle).min(session.expires_at);
+
+    diesel::update(session)
+        .set((
+            session::valid_until.eq(valid_until),
+            session::last_seen_at.eq(now()),
+            session::updated_at.eq(Some(now())),
+        ))
+        .get_result(conn)
+}
+
 pub fn delete(conn: &mut PgConnection, session: &Session) -> QueryResult<usize> {
     diesel::update(session)
         .set((session::deleted_at.eq(Some(now())),))
         .execute(conn)
 }
 
+pub fn read_active_by_user_id(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<Vec<Session>, diesel::result::Error> {
+    session::table
+        .filter(session::user_id.eq(user_id))
+        .filter(session::deleted_at.is_null())
+        .filter(session::valid_until.gt(now()))
+        .filter(session::expires_at.gt(now()))
+        .order(session::last_seen_at.desc())
+        .load::<Session>(conn)
+}
+
+pub fn read_by_id_and_user_id(
+    conn: &mut PgConnection,
+    id: i32,
+    user_id: i32,
+) -> Result<Session, diesel::result::Error> {
+    session::table
+        .filter(session::id.eq(id))
+        .filter(session::user_id.eq(user_id))
+        .filter(session::deleted_at.is_null())
+        .first::<Session>(conn)
+}
+
 pub fn delete_by_user_id(conn: &mut PgConnection, user_id: i32) -> QueryResult<usize> {
     diesel::update(session::dsl::session)
         .filter(session::user_id.eq(user_id))
-        .filter(session::deleted_at.is_not_null())
+        .filter(session::deleted_at.is_null())
+        .set((session::deleted_at.eq(Some(now())),))
+        .execute(conn)
+}
+
+pub fn delete_other_by_user_id(
+    conn: &mut PgConnection,
+    user_id: i32,
+    session_id: i32,
+) -> QueryResult<usize> {
+    diesel::update(session::dsl::session)
+        .filter(session::user_id.eq(user_id))
+        .filter(session::id.ne(session_id))
+        .filter(session::deleted_at.is_null())
         .set((session::deleted_at.eq(Some(now())),))
         .execute(conn)
 }
@@ -103,3 +244,88 @@ pub fn update(conn: &mut PgConnection, session: &mut Session) -> QueryResult<usi
         .set(&session.for_update())
         .execute(conn)
 }
+
+#[cfg(test)]
+pub fn insert_test_session(
+    conn: &mut PgConnection,
+    user_id: i32,
+    idle: Duration,
+    lifetime: Duration,
+) -> Session {
+    NewSession::new(
+        user_id,
+        crate::utils::hash_token(&generate_token()),
+        idle,
+        lifetime,
+        false,
+        ClientInfo::new(None, None),
+    )
+    .insert(conn)
+    .unwrap()
+}
+
+#[test]
+fn test_refresh_slides_up_to_the_lifetime() {
+    let Some(mut conn) = crate::db_conn::test_connection() else {
+        return;
+    };
+    conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+        let user = models::user::insert_test_user(conn, "sliding", "password");
+        let idle = Duration::minutes(30);
+
+        // close to idling out, activity pushes it a full idle period ahead
+        let session = insert_test_session(conn, user.id, Duration::minutes(1), Duration::hours(8));
+        let refreshed = refresh(conn, &session, idle)?;
+        assert!(refreshed.valid_until > session.valid_until);
+        assert!(refreshed.valid_until >= now() + idle - Duration::minutes(1));
+        assert!(refreshed.last_seen_at >= session.last_seen_at);
+        assert!(!refreshed.is_expired());
+
+        // near the end of its life it only slides as far as the lifetime allows
+        let session =
+            insert_test_session(conn, user.id, Duration::minutes(1), Duration::minutes(10));
+        let refreshed = refresh(conn, &session, idle)?;
+        assert_eq!(refreshed.valid_until, session.expires_at);
+        assert_eq!(refreshed.expires_at, session.expires_at);
+        Ok(())
+    });
+}
+
+#[test]
+fn test_expired_sessions_are_refused() {
+    let Some(mut conn) = crate::db_conn::test_connection() else {
+        return;
+    };
+    conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+        let user = models::user::insert_test_user(conn, "expired", "password");
+        let idle = Duration::minutes(30);
+        let idled = insert_test_session(conn, user.id, idle, Duration::hours(8));
+        let idled: Session = diesel::update(&idled)
+            .set(session::valid_until.eq(now() - Duration::minutes(1)))
+            .get_result(conn)?;
+        let lived = insert_test_session(conn, user.id, idle, Duration::hours(8));
+        let lived: Session = diesel::update(&lived)
+            .set(session::expires_at.eq(now() - Duration::minutes(1)))
+            .get_result(conn)?;
+        let fresh = insert_test_session(conn, user.id, idle, Duration::hours(8));
+
+        assert!(idled.is_expired());
+        assert!(lived.is_expired());
+        assert!(!fresh.is_expired());
+        assert_eq!(
+            read_active_by_user_id(conn, user.id)?
+                .iter()
+                .map(|session| session.id)
+                .collect::<Vec<_>>(),
+            vec![fresh.id]
+        );
+
+        // the cookie filter drops an expired session so its cookie stops resolving
+        let found = models::user::read_user_by_session(conn, &idled.token_hash)?;
+        assert!(found.session.is_expired());
+        delete(conn, &found.session)?;
+        assert!(models::user::read_user_by_session(conn, &idled.token_hash).is_err());
+        assert!(models::user::read_user_by_session(conn, &fresh.token_hash).is_ok());
+        Ok(())
+    });
+}
@@ -1,13 +1,19 @@
 use crate::{
     models,
     schema::{background, session, user},
-    utils::{encrypt, now, sanitize_html, verify},
+    utils::{encrypt, needs_rehash, now, sanitize_html, verify},
 };
+use argon2::Params;
 use chrono::naive::NaiveDateTime;
 use diesel::prelude::*;
+use diesel::sql_types::{Nullable, Varchar};
 use serde::Deserialize;
+use std::fmt;
+use totp_rs::{Algorithm, Secret, TotpUrlError, TOTP};
 
-#[derive(Clone, Debug, Identifiable, Queryable, AsChangeset, Selectable)]
+sql_function!(fn lower(x: Nullable<Varchar>) -> Nullable<Varchar>);
+
+#[derive(Clone, Identifiable, Queryable, AsChangeset, Selectable)]
 #[diesel(belongs_to(models::background::Background))]
 #[diesel(table_name = user)]
 pub struct User {
@@ -18,6 +24,42 @@ pub struct User {
     pub updated_at: Option<NaiveDateTime>,
     pub deleted_at: Option<NaiveDateTime>,
     pub background_id: i32,
+    pub email: Option<String>,
+    pub totp_secret: Option<String>,
+    pub totp_enabled_at: Option<NaiveDateTime>,
+    pub role: String,
+    pub suspended_reason: Option<String>,
+    pub quota_plan: Option<String>,
+    pub max_pages: Option<i32>,
+    pub max_links: Option<i32>,
+    pub totp_last_step: Option<i64>,
+}
+
+// users end up in logs through rejections, so the secrets stay out of it
+impl fmt::Debug for User {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("User")
+            .field("id", &self.id)
+            .field("username", &self.username)
+            .field("password", &"[redacted]")
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .field("deleted_at", &self.deleted_at)
+            .field("background_id", &self.background_id)
+            .field("email", &self.email)
+            .field(
+                "totp_secret",
+                &self.totp_secret.as_ref().map(|_| "[redacted]"),
+            )
+            .field("totp_enabled_at", &self.totp_enabled_at)
+            .field("role", &self.role)
+            .field("suspended_reason", &self.suspended_reason)
+            .field("quota_plan", &self.quota_plan)
+            .field("max_pages", &self.max_pages)
+            .field("max_links", &self.max_links)
+            .field("totp_last_step", &self.totp_last_step)
+            .finish()
+    }
 }
 
 impl User {
@@ -30,6 +72,15 @@ impl User {
             updated_at: Some(now()),
             deleted_at: self.deleted_at.clone(),
             background_id: self.background_id,
+            email: self.email.clone(),
+            totp_secret: self.totp_secret.clone(),
+            totp_enabled_at: self.totp_enabled_at.clone(),
+            role: self.role.clone(),
+            suspended_reason: self.suspended_reason.clone(),
+            quota_plan: self.quota_plan.clone(),
+            max_pages: self.max_pages,
+            max_links: self.max_links,
+            totp_last_step: self.totp_last_step,
         }
     }
 
@@ -37,7 +88,89 @@ impl User {
         string
             .replace("{user.id}", &self.id.to_string())
             .replace("{user.username}", &self.username)
+            .replace("{user.email}", self.email.as_deref().unwrap_or(""))
+            .replace("{user.role}", &self.role)
     }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        role_rank(&self.role) >= role_rank(role)
+    }
+
+    pub fn verify_password(&self, password: &str) -> bool {
+        verify(password, &self.password)
+    }
+
+    pub fn has_two_factor(&self) -> bool {
+        self.totp_enabled_at.is_some()
+    }
+
+    pub fn totp(&self) -> Option<TOTP> {
+        let secret = self.totp_secret.as_ref()?;
+        totp(secret, &self.username)
+            .map_err(|e| log::error!("{:?}", e))
+            .ok()
+    }
+
+    // the time step the code was made for, within the allowed skew, checks against the
+    // pending secret too, so enrollment can be confirmed
+    pub fn totp_step(&self, code: &str, time: u64) -> Option<i64> {
+        let code = code.trim().replace(' ', "");
+        let totp = self.totp()?;
+        let exact = TOTP {
+            skew: 0,
+            ..totp.clone()
+        };
+        let current = time / totp.step;
+        let skew = totp.skew as u64;
+        (current.saturating_sub(skew)..=current + skew)
+            .find(|step| exact.check(&code, step * totp.step))
+            .map(|step| step as i64)
+    }
+}
+
+pub const ROLE_USER: &str = "user";
+pub const ROLE_MODERATOR: &str = "moderator";
+pub const ROLE_ADMIN: &str = "admin";
+
+// each role can do everything the ones below it can
+fn role_rank(role: &str) -> u8 {
+    match role {
+        ROLE_ADMIN => 2,
+        ROLE_MODERATOR => 1,
+        _ => 0,
+    }
+}
+
+pub fn parse_role(role: &str) -> Option<&'static str> {
+    match role {
+        ROLE_USER => Some(ROLE_USER),
+        ROLE_MODERATOR => Some(ROLE_MODERATOR),
+        ROLE_ADMIN => Some(ROLE_ADMIN),
+        _ => None,
+    }
+}
+
+pub const TOTP_ISSUER: &str = "Digitheque";
+
+pub fn totp(secret: &str, username: &str) -> Result<TOTP, TotpUrlError> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|_| TotpUrlError::Secret(secret.to_string()))?;
+
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        secret,
+        Some(String::from(TOTP_ISSUER)),
+        username.to_string(),
+    )
+}
+
+// base32, the way authenticator apps expect to see it
+pub fn generate_totp_secret() -> String {
+    Secret::generate_secret().to_encoded().to_string()
 }
 
 #[derive(Deserialize)]
@@ -45,13 +178,17 @@ pub struct NewUserApi {
     pub username: String,
     pub password: String,
     pub confirm_password: String,
+    pub email: Option<String>,
+    pub invite: Option<String>,
 }
 
-impl Into<UserCredentialsEncrypted> for NewUserApi {
-    fn into(self) -> UserCredentialsEncrypted {
+impl NewUserApi {
+    pub fn encrypt(self, params: &Params) -> UserCredentialsEncrypted {
         UserCredentialsEncrypted {
             username: sanitize_html(&self.username),
-            password: encrypt(&self.password),
+            password: encrypt(&self.password, params),
+            // a typed-in address waits for its verification link
+            email: None,
         }
     }
 }
@@ -60,20 +197,97 @@ impl Into<UserCredentialsEncrypted> for NewUserApi {
 pub struct UserCredentialsApi {
     pub username: String,
     pub password: String,
+    pub remember_me: Option<String>,
 }
 
-impl Into<UserCredentialsEncrypted> for UserCredentialsApi {
-    fn into(self) -> UserCredentialsEncrypted {
-        UserCredentialsEncrypted {
-            username: self.username,
-            password: encrypt(&self.password),
-        }
-    }
+#[derive(Deserialize)]
+pub struct ChangePasswordApi {
+    pub current_password: String,
+    pub password: String,
+    pub confirm_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ChangeUsernameApi {
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailApi {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteAccountApi {
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorCodeApi {
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorSetupApi {}
+
+#[derive(Deserialize)]
+pub struct ConfirmPasswordApi {
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct SuspendUserApi {
+    pub reason: String,
+}
+
+#[derive(Deserialize)]
+pub struct ChangeRoleApi {
+    pub role: String,
+}
+
+// blank limits go back to whatever the plan allows
+#[derive(Deserialize)]
+pub struct ChangeQuotaApi {
+    pub plan: String,
+    pub max_pages: Option<String>,
+    pub max_links: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordApi {
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetTokenQuery {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailQuery {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordApi {
+    pub token: String,
+    pub password: String,
+    pub confirm_password: String,
 }
 
 pub struct UserCredentialsEncrypted {
     pub username: String,
     pub password: String,
+    pub email: Option<String>,
+}
+
+// an empty input means no email at all
+pub fn clean_email(email: Option<&str>) -> Option<String> {
+    email
+        .map(|email| email.trim())
+        .filter(|email| !email.is_empty())
+        .map(sanitize_html)
 }
 
 #[derive(Clone, Debug)]
@@ -81,6 +295,8 @@ pub struct ExpandedUser {
     pub user: User,
     pub background: models::background::Background,
     pub session: models::session::Session,
+    // set when the request came in with a bearer token rather than a cookie
+    pub api_token: Option<models::api_token::ApiToken>,
 }
 
 #[derive(Insertable)]
@@ -92,6 +308,7 @@ pub struct NewUser {
     pub updated_at: Option<NaiveDateTime>,
     pub deleted_at: Option<NaiveDateTime>,
     pub background_id: i32,
+    pub email: Option<String>,
 }
 
 impl NewUser {
@@ -103,6 +320,7 @@ impl NewUser {
             updated_at: None,
             deleted_at: None,
             background_id: background_id,
+            email: new_user.email,
         }
     }
 
@@ -117,6 +335,16 @@ pub fn create(conn: &mut PgConnection, new_user: &NewUser) -> Result<User, diese
         .get_result(conn)
 }
 
+// every new account, however it signed up, gets its own random background
+pub fn create_with_background(
+    conn: &mut PgConnection,
+    credentials: UserCredentialsEncrypted,
+) -> Result<(User, models::background::Background), diesel::result::Error> {
+    let background = models::background::random_bg().insert(conn)?;
+    let user = NewUser::new(credentials, background.id).insert(conn)?;
+    Ok((user, background))
+}
+
 pub fn read(conn: &mut PgConnection) -> Result<Vec<User>, diesel::result::Error> {
     user::table.load::<User>(conn)
 }
@@ -128,13 +356,65 @@ pub fn read_by_id(conn: &mut PgConnection, id: i32) -> Result<User, diesel::resu
         .first::<User>(conn)
 }
 
+// holds the row until the transaction ends, so per user limits are counted one request at a time
+pub fn lock_by_id(conn: &mut PgConnection, id: i32) -> Result<User, diesel::result::Error> {
+    user::table
+        .filter(user::id.eq(id))
+        .filter(user::deleted_at.is_null())
+        .for_update()
+        .first::<User>(conn)
+}
+
+pub fn read_by_username(
+    conn: &mut PgConnection,
+    username: &str,
+) -> Result<User, diesel::result::Error> {
+    user::table
+        .filter(user::username.eq(username))
+        .filter(user::deleted_at.is_null())
+        .first::<User>(conn)
+}
+
+// addresses are unique among live accounts, whatever their case
+pub fn read_by_email(conn: &mut PgConnection, email: &str) -> Result<User, diesel::result::Error> {
+    user::table
+        .filter(lower(user::email).eq(email.to_lowercase()))
+        .filter(user::deleted_at.is_null())
+        .first::<User>(conn)
+}
+
+// the forgot password form accepts either, and a username wins over an email
+pub fn read_by_username_or_email(
+    conn: &mut PgConnection,
+    username_or_email: &str,
+) -> Result<User, diesel::result::Error> {
+    read_by_username(conn, username_or_email).or_else(|_| read_by_email(conn, username_or_email))
+}
+
+pub fn read_with_background_by_id(
+    conn: &mut PgConnection,
+    id: i32,
+) -> Result<(User, models::background::Background), diesel::result::Error> {
+    user::table
+        .inner_join(background::table.on(user::background_id.eq(background::id)))
+        .filter(user::id.eq(id))
+        .filter(user::deleted_at.is_null())
+        .select((
+            User::as_select(),
+            models::background::Background::as_select(),
+        ))
+        .first(conn)
+}
+
+// a correct password on an outdated hash is our one chance to upgrade it
 pub fn read_by_credentials(
     conn: &mut PgConnection,
     credentials: UserCredentialsApi,
+    params: &Params,
 ) -> Result<(User, models::background::Background), diesel::result::Error> {
     let (user, background) = user::table
         .inner_join(background::table.on(user::background_id.eq(background::id)))
-        .filter(user::username.eq(credentials.username))
+        .filter(user::username.eq(&credentials.username))
         .filter(user::deleted_at.is_null())
         .select((
             User::as_select(),
@@ -142,10 +422,21 @@ pub fn read_by_credentials(
         ))
         .first(conn)?;
 
-    if verify(&credentials.password, &user.password) {
-        Ok((user, background))
-    } else {
-        Err(diesel::NotFound)
+    if !verify(&credentials.password, &user.password) {
+        return Err(diesel::NotFound);
+    }
+
+    if !needs_rehash(&user.password, params) {
+        return Ok((user, background));
+    }
+
+    log::info!("Upgrading password hash of {}", user.username);
+    match update_password(conn, &user, &credentials.password, params) {
+        Ok(user) => Ok((user, background)),
+        Err(e) => {
+            log::error!("{:?}", e);
+            Ok((user, background))
+        }
     }
 }
 
@@ -155,6 +446,150 @@ pub fn delete(conn: &mut PgConnection, user: &User) -> QueryResult<usize> {
         .execute(conn)
 }
 
+// suspended users keep their row so the reason can be looked up later
+pub fn suspend(conn: &mut PgConnection, user: &User, reason: &str) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        diesel::update(user)
+            .set((user::suspended_reason.eq(Some(sanitize_html(reason))),))
+            .execute(conn)?;
+        models::session::delete_by_user_id(conn, user.id)?;
+        delete(conn, user)
+    })
+}
+
+pub fn update_role(conn: &mut PgConnection, user: &User, role: &str) -> QueryResult<User> {
+    diesel::update(user)
+        .set((user::role.eq(role), user::updated_at.eq(Some(now()))))
+        .get_result(conn)
+}
+
+pub fn update_quota(
+    conn: &mut PgConnection,
+    user: &User,
+    quota_plan: &str,
+    max_pages: Option<i32>,
+    max_links: Option<i32>,
+) -> QueryResult<User> {
+    diesel::update(user)
+        .set((
+            user::quota_plan.eq(Some(quota_plan)),
+            user::max_pages.eq(max_pages),
+            user::max_links.eq(max_links),
+            user::updated_at.eq(Some(now())),
+        ))
+        .get_result(conn)
+}
+
+// includes suspended users, moderators need to see them too
+pub fn read_recent(conn: &mut PgConnection, limit: i64) -> QueryResult<Vec<User>> {
+    user::table
+        .order(user::created_at.desc())
+        .limit(limit)
+        .load::<User>(conn)
+}
+
+pub fn get_count_of_users(conn: &mut PgConnection) -> Result<usize, diesel::result::Error> {
+    user::table
+        .filter(user::deleted_at.is_null())
+        .count()
+        .get_result(conn)
+        .map(|v: i64| v as usize)
+}
+
+pub fn update_password(
+    conn: &mut PgConnection,
+    user: &User,
+    password: &str,
+    params: &Params,
+) -> QueryResult<User> {
+    diesel::update(user)
+        .set((
+            user::password.eq(encrypt(password, params)),
+            user::updated_at.eq(Some(now())),
+        ))
+        .get_result(conn)
+}
+
+pub fn update_username(conn: &mut PgConnection, user: &User, username: &str) -> QueryResult<User> {
+    diesel::update(user)
+        .set((
+            user::username.eq(sanitize_html(username)),
+            user::updated_at.eq(Some(now())),
+        ))
+        .get_result(conn)
+}
+
+pub fn update_email(
+    conn: &mut PgConnection,
+    user: &User,
+    email: Option<String>,
+) -> QueryResult<User> {
+    diesel::update(user)
+        .set((user::email.eq(email), user::updated_at.eq(Some(now()))))
+        .get_result(conn)
+}
+
+// the secret waits here unconfirmed until the user proves their app has it
+pub fn update_totp_secret(
+    conn: &mut PgConnection,
+    user: &User,
+    secret: Option<String>,
+) -> QueryResult<User> {
+    diesel::update(user)
+        .set((
+            user::totp_secret.eq(secret),
+            user::totp_enabled_at.eq(None::<NaiveDateTime>),
+            user::totp_last_step.eq(None::<i64>),
+            user::updated_at.eq(Some(now())),
+        ))
+        .get_result(conn)
+}
+
+// a code is only good once, its step has to be newer than the last one used and the
+// update only lands if it still is, so two requests racing with one code can't both get in
+pub fn verify_totp(conn: &mut PgConnection, user: &User, code: &str) -> QueryResult<bool> {
+    let Some(step) = user.totp_step(code, chrono::Utc::now().timestamp() as u64) else {
+        return Ok(false);
+    };
+    diesel::update(user::table.find(user.id))
+        .filter(
+            user::totp_last_step
+                .is_null()
+                .or(user::totp_last_step.lt(step)),
+        )
+        .set((
+            user::totp_last_step.eq(Some(step)),
+            user::updated_at.eq(Some(now())),
+        ))
+        .execute(conn)
+        .map(|updated| updated == 1)
+}
+
+pub fn enable_totp(conn: &mut PgConnection, user: &User) -> QueryResult<User> {
+    diesel::update(user)
+        .set((
+            user::totp_enabled_at.eq(Some(now())),
+            user::updated_at.eq(Some(now())),
+        ))
+        .get_result(conn)
+}
+
+// soft delete the user along with everything that hangs off of them
+pub fn delete_account(conn: &mut PgConnection, user: &User) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        models::page::delete_by_user_id(conn, user.id)?;
+        models::api_token::delete_by_user_id(conn, user.id)?;
+        models::passkey::delete_by_user_id(conn, user.id)?;
+        models::password_reset::delete_by_user_id(conn, user.id)?;
+        models::pending_login::delete_by_user_id(conn, user.id)?;
+        models::recovery_code::delete_by_user_id(conn, user.id)?;
+        models::identity::delete_by_user_id(conn, user.id)?;
+        models::invite::delete_by_user_id(conn, user.id)?;
+        models::session::delete_by_user_id(conn, user.id)?;
+        delete(conn, user)
+    })
+}
+
 pub fn update(conn: &mut PgConnection, user: &mut User) -> QueryResult<usize> {
     diesel::update(user::table)
         .set(&user.for_update())
@@ -163,14 +598,14 @@ pub fn update(conn: &mut PgConnection, user: &mut User) -> QueryResult<usize> {
 
 pub fn read_user_by_session(
     conn: &mut PgConnection,
-    session_id: i32,
+    token_hash: &str,
 ) -> Result<ExpandedUser, diesel::result::Error> {
     let r = user::table
         .inner_join(background::table.on(user::background_id.eq(background::id)))
         .inner_join(session::table.on(user::id.eq(session::user_id)))
         // .filter(session::valid_until.gt(now()))
         .filter(session::deleted_at.is_null())
-        .filter(session::id.eq(session_id))
+        .filter(session::token_hash.eq(token_hash))
         .filter(user::deleted_at.is_null())
         .select((
             User::as_select(),
@@ -183,9 +618,136 @@ pub fn read_user_by_session(
         user,
         session,
         background,
+        api_token: None,
     })
 }
 
 pub fn cleanup_table(conn: &mut PgConnection) {
     diesel::delete(user::table).execute(conn).unwrap();
 }
+
+// a real account made the way signup makes one, for tests that need a user row
+#[cfg(test)]
+pub fn insert_test_user(conn: &mut PgConnection, username: &str, password: &str) -> User {
+    let credentials = UserCredentialsEncrypted {
+        username: username.to_string(),
+        password: encrypt(password, &Params::default()),
+        email: None,
+    };
+    create_with_background(conn, credentials).unwrap().0
+}
+
+#[test]
+fn test_roles() {
+    let Some(mut conn) = crate::db_conn::test_connection() else {
+        return;
+    };
+    conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+        let user = insert_test_user(conn, "roles", "password");
+        assert!(user.has_role(ROLE_USER));
+        assert!(!user.has_role(ROLE_MODERATOR));
+
+        let user = update_role(conn, &user, ROLE_ADMIN)?;
+        assert!(user.has_role(ROLE_MODERATOR));
+        assert!(user.has_role(ROLE_ADMIN));
+        Ok(())
+    });
+
+    assert_eq!(parse_role("moderator"), Some(ROLE_MODERATOR));
+    assert_eq!(parse_role("root"), None);
+}
+
+#[test]
+fn test_quota_for() {
+    let Some(mut conn) = crate::db_conn::test_connection() else {
+        return;
+    };
+    let config = crate::config::test_config();
+    conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+        let user = insert_test_user(conn, "quotas", "password");
+        let free = config.quota_for(&user);
+        assert_eq!(free.plan, "free");
+        assert_eq!(free.max_pages, config.quota_plans[0].max_pages);
+
+        let user = update_quota(conn, &user, "pro", None, None)?;
+        assert_eq!(config.quota_for(&user).max_links, 200);
+
+        // a plan that was taken out of the config lands back on the default
+        let user = update_quota(conn, &user, "gone", None, None)?;
+        assert_eq!(config.quota_for(&user), free);
+
+        let user = update_quota(conn, &user, "pro", Some(3), Some(0))?;
+        assert_eq!(
+            config.quota_for(&user),
+            crate::config::Quota {
+                plan: String::from("pro"),
+                max_pages: 3,
+                max_links: 0,
+            }
+        );
+        Ok(())
+    });
+}
+
+#[test]
+fn test_totp_round_trip() {
+    let secret = generate_totp_secret();
+    let totp = totp(&secret, "tester").unwrap();
+    assert_eq!(totp.get_secret_base32(), secret);
+
+    let code = totp.generate_current().unwrap();
+    assert!(totp.check_current(&code).unwrap());
+    assert!(!totp.check_current("not a code").unwrap());
+}
+
+#[test]
+fn test_totp_codes_are_used_once() {
+    let Some(mut conn) = crate::db_conn::test_connection() else {
+        return;
+    };
+    conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+        let user = insert_test_user(conn, "replay", "password");
+        let user = update_totp_secret(conn, &user, Some(generate_totp_secret()))?;
+        let totp = user.totp().unwrap();
+        let time = chrono::Utc::now().timestamp() as u64;
+
+        // a step either side of now is allowed for clock drift, further out is not
+        let step = (time / totp.step) as i64;
+        assert_eq!(user.totp_step(&totp.generate(time), time), Some(step));
+        assert_eq!(
+            user.totp_step(&totp.generate(time - totp.step), time),
+            Some(step - 1)
+        );
+        assert_eq!(
+            user.totp_step(&totp.generate(time - 3 * totp.step), time),
+            None
+        );
+
+        let code = totp.generate(time);
+        assert!(verify_totp(conn, &user, &code)?);
+        assert!(!verify_totp(conn, &user, &code)?);
+
+        // an older code from inside the skew window is refused once a newer one was used
+        let user = read_by_id(conn, user.id)?;
+        assert_eq!(user.totp_last_step, Some(step));
+        assert!(!verify_totp(conn, &user, &totp.generate(time - totp.step))?);
+        Ok(())
+    });
+}
+
+#[test]
+fn test_debug_hides_secrets() {
+    let Some(mut conn) = crate::db_conn::test_connection() else {
+        return;
+    };
+    conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+        let user = insert_test_user(conn, "hg", "password");
+        let user = update_totp_secret(conn, &user, Some(generate_totp_secret()))?;
+
+        let debug = format!("{:?}", user);
+        assert!(debug.contains("hg"));
+        assert!(!debug.contains(&user.password));
+        assert!(!debug.contains(user.totp_secret.as_deref().unwrap()));
+        Ok(())
+    });
+}
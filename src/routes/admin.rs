@@ -0,0 +1,346 @@
+use crate::{
+    models::{
+        self,
+        page_link::HideLinkApi,
+        user::{
+            ChangeQuotaApi, ChangeRoleApi, ExpandedUser, SuspendUserApi, ROLE_ADMIN, ROLE_MODERATOR,
+        },
+    },
+    server::Context,
+    MalformedForm, NotAuthorized, NotFound,
+};
+use serde::de::DeserializeOwned;
+use warp::{filters::BoxedFilter, reject, Filter};
+
+use super::user::{authenticate, authenticate_csrf, authenticate_csrf_form};
+
+// matches the VARCHAR(255) suspended_reason and hidden_reason columns
+const MAX_REASON_LENGTH: usize = 255;
+
+fn path_prefix() -> BoxedFilter<()> {
+    warp::path("admin").boxed()
+}
+
+pub fn console() -> BoxedFilter<(Context, ExpandedUser)> {
+    path_prefix()
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(authorize(ROLE_MODERATOR))
+        .boxed()
+}
+
+pub fn suspend_user() -> BoxedFilter<(Context, ExpandedUser)> {
+    path_prefix()
+        .and(warp::path("users"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("suspend"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authorize_csrf_form::<SuspendUserApi>(ROLE_ADMIN))
+        .and_then(suspend)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn change_role() -> BoxedFilter<(Context, ExpandedUser)> {
+    path_prefix()
+        .and(warp::path("users"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("role"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authorize_csrf_form::<ChangeRoleApi>(ROLE_ADMIN))
+        .and_then(update_role)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn change_quota() -> BoxedFilter<(Context, ExpandedUser)> {
+    path_prefix()
+        .and(warp::path("users"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("quota"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authorize_csrf_form::<ChangeQuotaApi>(ROLE_ADMIN))
+        .and_then(update_quota)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn hide_page() -> BoxedFilter<(Context, ExpandedUser)> {
+    warp::delete()
+        .and(path_prefix())
+        .and(warp::path("pages"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(authorize_csrf(ROLE_MODERATOR))
+        .and_then(remove_page)
+        .untuple_one()
+        .boxed()
+}
+
+// one page's copy of the link, the same url saved elsewhere is left alone
+pub fn hide_link() -> BoxedFilter<(Context, ExpandedUser)> {
+    path_prefix()
+        .and(warp::path("pages"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("links"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("hide"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authorize_csrf_form::<HideLinkApi>(ROLE_MODERATOR))
+        .and_then(remove_link)
+        .untuple_one()
+        .boxed()
+}
+
+// everything under /admin goes through one of these
+pub fn authorize(role: &'static str) -> BoxedFilter<(Context, ExpandedUser)> {
+    authenticate()
+        .and_then(move |context, expanded_user| with_role(context, expanded_user, role))
+        .untuple_one()
+        .boxed()
+}
+
+fn authorize_csrf(role: &'static str) -> BoxedFilter<(Context, ExpandedUser)> {
+    authenticate_csrf()
+        .and_then(move |context, expanded_user| with_role(context, expanded_user, role))
+        .untuple_one()
+        .boxed()
+}
+
+fn authorize_csrf_form<T>(role: &'static str) -> BoxedFilter<(Context, ExpandedUser, T)>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    authenticate_csrf_form::<T>()
+        .and_then(move |context, expanded_user, form| async move {
+            let (context, expanded_user) = with_role(context, expanded_user, role).await?;
+            Ok::<_, warp::Rejection>((context, expanded_user, form))
+        })
+        .untuple_one()
+        .boxed()
+}
+
+async fn with_role(
+    context: Context,
+    expanded_user: ExpandedUser,
+    role: &'static str,
+) -> Result<(Context, ExpandedUser), warp::Rejection> {
+    if expanded_user.user.has_role(role) {
+        Ok((context, expanded_user))
+    } else {
+        log::warn!(
+            "{} tried to use the {} console",
+            expanded_user.user.username,
+            role
+        );
+        Err(reject::custom(NotAuthorized))
+    }
+}
+
+// nobody suspends themselves or another admin by accident
+fn read_target(
+    conn: &mut diesel::PgConnection,
+    id: i32,
+    expanded_user: &ExpandedUser,
+) -> Result<models::user::User, warp::Rejection> {
+    let user = models::user::read_by_id(conn, id).map_err(|_| reject::custom(NotFound))?;
+    if user.id == expanded_user.user.id || user.has_role(ROLE_ADMIN) {
+        return Err(reject::custom(NotAuthorized));
+    }
+    Ok(user)
+}
+
+async fn suspend(
+    id: i32,
+    context: Context,
+    expanded_user: ExpandedUser,
+    form: SuspendUserApi,
+) -> Result<(Context, ExpandedUser), warp::Rejection> {
+    let reason = check_reason(&form.reason)?;
+
+    let mut conn = context.db_conn.get_conn();
+    let user = read_target(&mut conn, id, &expanded_user)?;
+    models::user::suspend(&mut conn, &user, reason).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    models::audit_log::record(
+        &mut conn,
+        Some(user.id),
+        models::audit_log::USER_SUSPENDED,
+        format!(
+            "{} suspended {}: {}",
+            expanded_user.user.username, user.username, reason
+        ),
+        expanded_user.session.ip.clone(),
+    );
+    Ok((context, expanded_user))
+}
+
+async fn update_role(
+    id: i32,
+    context: Context,
+    expanded_user: ExpandedUser,
+    form: ChangeRoleApi,
+) -> Result<(Context, ExpandedUser), warp::Rejection> {
+    let role = models::user::parse_role(&form.role)
+        .ok_or_else(|| reject::custom(MalformedForm(String::from("Unknown role"))))?;
+
+    let mut conn = context.db_conn.get_conn();
+    let user = read_target(&mut conn, id, &expanded_user)?;
+    models::user::update_role(&mut conn, &user, role).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    models::audit_log::record(
+        &mut conn,
+        Some(user.id),
+        models::audit_log::ROLE_CHANGED,
+        format!(
+            "{} made {} a {}",
+            expanded_user.user.username, user.username, role
+        ),
+        expanded_user.session.ip.clone(),
+    );
+    Ok((context, expanded_user))
+}
+
+// a blank limit clears the override so the plan's own limit applies again
+fn parse_limit(value: &Option<String>) -> Result<Option<i32>, warp::Rejection> {
+    match value.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(value) => match value.parse::<i32>() {
+            Ok(limit) if limit >= 0 => Ok(Some(limit)),
+            _ => Err(reject::custom(MalformedForm(String::from(
+                "Limits must be whole numbers of zero or more",
+            )))),
+        },
+    }
+}
+
+async fn update_quota(
+    id: i32,
+    context: Context,
+    expanded_user: ExpandedUser,
+    form: ChangeQuotaApi,
+) -> Result<(Context, ExpandedUser), warp::Rejection> {
+    let plan = context
+        .config
+        .quota_plan(form.plan.trim())
+        .ok_or_else(|| reject::custom(MalformedForm(String::from("Unknown plan"))))?
+        .name
+        .clone();
+    let max_pages = parse_limit(&form.max_pages)?;
+    let max_links = parse_limit(&form.max_links)?;
+
+    let mut conn = context.db_conn.get_conn();
+    let user = read_target(&mut conn, id, &expanded_user)?;
+    let user =
+        models::user::update_quota(&mut conn, &user, &plan, max_pages, max_links).map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    let quota = context.config.quota_for(&user);
+    models::audit_log::record(
+        &mut conn,
+        Some(user.id),
+        models::audit_log::QUOTA_CHANGED,
+        format!(
+            "{} put {} on the {} plan with {} groups of {} links",
+            expanded_user.user.username,
+            user.username,
+            quota.plan,
+            quota.max_pages,
+            quota.max_links
+        ),
+        expanded_user.session.ip.clone(),
+    );
+    Ok((context, expanded_user))
+}
+
+async fn remove_page(
+    id: i32,
+    context: Context,
+    expanded_user: ExpandedUser,
+) -> Result<(Context, ExpandedUser), warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+
+    let expanded_page =
+        models::page::read_by_id(&mut conn, id).map_err(|_| reject::custom(NotFound))?;
+    models::page::hide(&mut conn, &expanded_page.page).map_err(|_| reject::custom(NotFound))?;
+
+    models::audit_log::record(
+        &mut conn,
+        Some(expanded_page.page.user_id),
+        models::audit_log::PAGE_HIDDEN,
+        format!(
+            "{} hid page {} ({})",
+            expanded_user.user.username, expanded_page.page.id, expanded_page.page.name
+        ),
+        expanded_user.session.ip.clone(),
+    );
+    Ok((context, expanded_user))
+}
+
+async fn remove_link(
+    page_id: i32,
+    link_id: i32,
+    context: Context,
+    expanded_user: ExpandedUser,
+    form: HideLinkApi,
+) -> Result<(Context, ExpandedUser), warp::Rejection> {
+    let reason = check_reason(&form.reason)?;
+
+    let mut conn = context.db_conn.get_conn();
+    let expanded_page =
+        models::page::read_by_id(&mut conn, page_id).map_err(|_| reject::custom(NotFound))?;
+    let hidden = models::page_link::hide(&mut conn, page_id, link_id, reason).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+    let page_link = hidden.first().ok_or_else(|| reject::custom(NotFound))?;
+
+    models::audit_log::record(
+        &mut conn,
+        Some(expanded_page.page.user_id),
+        models::audit_log::LINK_HIDDEN,
+        format!(
+            "{} hid link {} ({}) from page {}: {}",
+            expanded_user.user.username, link_id, page_link.name, page_id, reason
+        ),
+        expanded_user.session.ip.clone(),
+    );
+    Ok((context, expanded_user))
+}
+
+// the owner is shown it, so an empty one or one that won't fit the column is refused
+fn check_reason(reason: &str) -> Result<&str, warp::Rejection> {
+    let reason = reason.trim();
+    if reason.is_empty() || reason.chars().count() > MAX_REASON_LENGTH {
+        return Err(reject::custom(MalformedForm(format!(
+            "A reason of up to {} characters is required",
+            MAX_REASON_LENGTH
+        ))));
+    }
+    Ok(reason)
+}
+
+#[test]
+fn test_parse_limit() {
+    assert_eq!(parse_limit(&None).unwrap(), None);
+    assert_eq!(parse_limit(&Some(String::from("  "))).unwrap(), None);
+    assert_eq!(parse_limit(&Some(String::from(" 25 "))).unwrap(), Some(25));
+    assert_eq!(parse_limit(&Some(String::from("0"))).unwrap(), Some(0));
+
+    for bad in ["-1", "ten", "2.5", "99999999999"] {
+        let rejection = parse_limit(&Some(String::from(bad))).unwrap_err();
+        assert!(rejection.find::<MalformedForm>().is_some());
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{
+    models::{self, api_token::NewApiTokenApi},
+    server::Context,
+    utils::hash_token,
+    MalformedForm, NotFound,
+};
+use warp::{filters::BoxedFilter, reject, Filter};
+
+use super::user::{authenticate_csrf, authenticate_csrf_form};
+
+// matches the VARCHAR(64) name column
+const MAX_NAME_LENGTH: usize = 64;
+
+fn path_prefix() -> BoxedFilter<()> {
+    warp::path("tokens").boxed()
+}
+
+pub fn create() -> BoxedFilter<(Context, models::user::ExpandedUser, String)> {
+    path_prefix()
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authenticate_csrf_form::<NewApiTokenApi>())
+        .and_then(insert_api_token)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn delete() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    warp::delete()
+        .and(path_prefix())
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(authenticate_csrf())
+        .and_then(revoke_api_token)
+        .untuple_one()
+        .boxed()
+}
+
+async fn insert_api_token(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    form: NewApiTokenApi,
+) -> Result<(Context, models::user::ExpandedUser, String), warp::Rejection> {
+    let name = form.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(reject::custom(MalformedForm(format!(
+            "Token names must be between 1 and {} characters",
+            MAX_NAME_LENGTH
+        ))));
+    }
+    let scope = models::api_token::parse_scope(&form.scope)
+        .ok_or_else(|| reject::custom(MalformedForm(String::from("Unknown token scope"))))?;
+
+    let mut conn = context.db_conn.get_conn();
+    let token = models::api_token::generate();
+    models::api_token::NewApiToken::new(expanded_user.user.id, name, hash_token(&token), scope)
+        .insert(&mut conn)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    log::info!("Created API token for {}", expanded_user.user.username);
+    Ok((context, expanded_user, token))
+}
+
+async fn revoke_api_token(
+    id: i32,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<(Context, models::user::ExpandedUser), warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+
+    let api_token = models::api_token::read_by_id_and_user_id(&mut conn, id, expanded_user.user.id)
+        .map_err(|_| reject::custom(NotFound))?;
+    models::api_token::delete(&mut conn, &api_token).map_err(|_| reject::custom(NotFound))?;
+
+    log::info!("Revoked API token {}", api_token.id);
+    Ok((context, expanded_user))
+}
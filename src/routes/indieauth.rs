@@ -0,0 +1,173 @@
+use crate::{
+    indieauth, models,
+    server::Context,
+    utils::{constant_time_eq, generate_token, hash_token},
+    MalformedForm, NotAuthorized, NotFound,
+};
+use url::Url;
+use warp::{
+    filters::{self, BoxedFilter},
+    reject, Filter,
+};
+
+use super::{
+    oauth::insert_user_for_identity,
+    user::{client_info, with_new_session},
+};
+
+// identities from indieauth are keyed by profile url under this provider name
+pub const PROVIDER: &str = "indieauth";
+
+fn path_prefix() -> BoxedFilter<()> {
+    warp::path("indieauth").boxed()
+}
+
+pub fn start() -> BoxedFilter<(String, String)> {
+    path_prefix()
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(filters::ext::get::<Context>())
+        .and(warp::body::form::<models::identity::IndieAuthLoginApi>())
+        .and_then(start_authorization)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn callback() -> BoxedFilter<(Context, models::user::ExpandedUser, String)> {
+    path_prefix()
+        .and(warp::path("callback"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(filters::ext::get::<Context>())
+        .and(warp::cookie::<String>("indieauth_state"))
+        .and(warp::query::<models::identity::OAuthCallbackQuery>())
+        .and_then(finish_authorization)
+        .untuple_one()
+        .and(client_info())
+        .and_then(with_new_session)
+        .untuple_one()
+        .boxed()
+}
+
+async fn start_authorization(
+    context: Context,
+    login: models::identity::IndieAuthLoginApi,
+) -> Result<(String, String), warp::Rejection> {
+    let me = indieauth::normalize_profile_url(&login.url)
+        .map_err(|message| reject::custom(MalformedForm(message)))?;
+
+    log::info!("Discovering authorization endpoint of {}", me);
+    let discovery = context
+        .profile_client
+        .discover_authorization_endpoint(&me)
+        .await
+        .map_err(|e| {
+            log::info!("{:?}", e);
+            reject::custom(NotAuthorized)
+        })?;
+    // a profile that redirected elsewhere is known by where it landed
+    let (me, endpoint) = (discovery.me, discovery.authorization_endpoint);
+
+    let state = generate_token();
+    let code_verifier = generate_token();
+    let url = indieauth::authorize_url(
+        &endpoint,
+        &context.config.app_url,
+        &me,
+        &state,
+        &code_verifier,
+    )
+    .map_err(|e| {
+        log::info!("{:?}", e);
+        reject::custom(NotAuthorized)
+    })?;
+
+    let mut conn = context.db_conn.get_conn();
+    models::oauth_state::NewOAuthState::new(PROVIDER, hash_token(&state), code_verifier)
+        .with_profile(me, endpoint)
+        .insert(&mut conn)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    Ok((url, state))
+}
+
+async fn finish_authorization(
+    context: Context,
+    cookie: String,
+    query: models::identity::OAuthCallbackQuery,
+) -> Result<(Context, models::user::User, models::background::Background), warp::Rejection> {
+    if let Some(error) = &query.error {
+        log::info!("IndieAuth sign in refused: {}", error);
+        return Err(reject::custom(NotAuthorized));
+    }
+    let (code, state) = match (query.code, query.state) {
+        (Some(code), Some(state)) => (code, state),
+        _ => return Err(reject::custom(NotAuthorized)),
+    };
+    if !constant_time_eq(&state, &cookie) {
+        log::warn!("IndieAuth state mismatch");
+        return Err(reject::custom(NotAuthorized));
+    }
+
+    let oauth_state = {
+        let mut conn = context.db_conn.get_conn();
+        let oauth_state =
+            models::oauth_state::read_valid_by_state_hash(&mut conn, PROVIDER, &hash_token(&state))
+                .map_err(|_| reject::custom(NotFound))?;
+        models::oauth_state::delete(&mut conn, &oauth_state).map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+        oauth_state
+    };
+    let (entered, endpoint) = match (oauth_state.me, oauth_state.authorization_endpoint) {
+        (Some(me), Some(endpoint)) => (me, endpoint),
+        _ => return Err(reject::custom(NotFound)),
+    };
+
+    let profile = context
+        .profile_client
+        .redeem_code(
+            &endpoint,
+            &context.config.app_url,
+            &code,
+            &oauth_state.code_verifier,
+        )
+        .await
+        .map_err(|e| {
+            log::info!("{:?}", e);
+            reject::custom(NotAuthorized)
+        })?;
+    let me = context
+        .profile_client
+        .verify_profile(&entered, &profile.me, &endpoint)
+        .await
+        .map_err(|e| {
+            log::warn!("{:?}", e);
+            reject::custom(NotAuthorized)
+        })?;
+
+    let mut conn = context.db_conn.get_conn();
+    let user_id = match models::identity::read_by_provider_and_subject(&mut conn, PROVIDER, &me) {
+        Ok(identity) => identity.user_id,
+        Err(diesel::NotFound) => {
+            let host = Url::parse(&me)
+                .ok()
+                .and_then(|url| url.host_str().map(String::from));
+            insert_user_for_identity(&context, &mut conn, PROVIDER, &me, None, &[host.as_deref()])?
+        }
+        Err(e) => {
+            log::error!("{:?}", e);
+            return Err(warp::reject());
+        }
+    };
+
+    let (user, background) = models::user::read_with_background_by_id(&mut conn, user_id)
+        .map_err(|_| reject::custom(NotFound))?;
+
+    log::info!("Recognized user {} by {}", user.username, me);
+    Ok((context, user, background))
+}
@@ -0,0 +1,102 @@
+use crate::{
+    config::SignupMode, models, server::Context, NotFound, ResourceError, ResourceErrorData,
+};
+use diesel::Connection;
+use warp::{filters::BoxedFilter, reject, Filter};
+
+use super::user::{authenticate, authenticate_csrf};
+
+fn path_prefix() -> BoxedFilter<()> {
+    warp::path("invites").boxed()
+}
+
+pub fn list() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    path_prefix()
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(authenticate())
+        .boxed()
+}
+
+pub fn create() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    path_prefix()
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authenticate_csrf())
+        .and_then(insert_invite)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn delete() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    warp::delete()
+        .and(path_prefix())
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(authenticate_csrf())
+        .and_then(revoke_invite)
+        .untuple_one()
+        .boxed()
+}
+
+async fn insert_invite(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<(Context, models::user::ExpandedUser), warp::Rejection> {
+    if context.config.signup_mode != SignupMode::InviteOnly {
+        return Err(reject::custom(NotFound));
+    }
+    let mut conn = context.db_conn.get_conn();
+
+    // the user row is locked while counting, so parallel requests can't all slip under the limit
+    let created = conn
+        .transaction(|conn| {
+            models::user::lock_by_id(conn, expanded_user.user.id)?;
+            let issued =
+                models::invite::get_count_of_invites_per_issuer(conn, expanded_user.user.id)?;
+            if issued >= context.config.invites_per_user
+                && !expanded_user.user.has_role(models::user::ROLE_ADMIN)
+            {
+                return Ok(None);
+            }
+            models::invite::NewInvite::new(
+                expanded_user.user.id,
+                context.config.invite_max_uses,
+                context.config.invite_duration_days,
+            )
+            .insert(conn)
+            .map(Some)
+        })
+        .map_err(|e: diesel::result::Error| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+    if created.is_none() {
+        return Err(reject::custom(ResourceError::TooMany(
+            ResourceErrorData {
+                context: Some(context.clone()),
+                expanded_user: Some(expanded_user),
+                expanded_page: None,
+            },
+            context.config.invites_per_user,
+        )));
+    }
+
+    log::info!("{} created an invite", expanded_user.user.username);
+    Ok((context, expanded_user))
+}
+
+async fn revoke_invite(
+    id: i32,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<(Context, models::user::ExpandedUser), warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+
+    let invite = models::invite::read_by_id_and_issuer(&mut conn, id, expanded_user.user.id)
+        .map_err(|_| reject::custom(NotFound))?;
+    models::invite::delete(&mut conn, &invite).map_err(|_| reject::custom(NotFound))?;
+
+    log::info!("Revoked invite {}", invite.id);
+    Ok((context, expanded_user))
+}
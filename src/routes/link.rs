@@ -1,27 +1,30 @@
-use crate::{models, routes, server::Context, utils, NotFound};
+use crate::{models, routes, server::Context, utils, NotFound, PAGE_SIZE};
 use warp::{
     filters::{self, BoxedFilter},
     reject, Filter,
 };
 
-use super::user::authenticate_cookie;
+use super::user::{authenticate_csrf_form_or_token, authenticate_or_token};
 
 fn path_prefix() -> BoxedFilter<()> {
     warp::path("link").boxed()
 }
 
+#[allow(clippy::type_complexity)]
 pub fn get_by_id() -> BoxedFilter<(
     Context,
-    models::user::User,
+    models::user::ExpandedUser,
     models::link::Link,
+    Vec<(models::page::Page, String)>,
+    Option<i32>,
     Vec<models::page::Page>,
-    Vec<models::page::Page>,
+    Option<i32>,
 )> {
     path_prefix()
         .and(warp::get())
         .and(warp::path::param::<i32>())
         .and(warp::path::end())
-        .and(authenticate_cookie())
+        .and(authenticate_or_token())
         .and_then(with_link)
         .untuple_one()
         .and_then(with_pages_containing_link)
@@ -31,8 +34,13 @@ pub fn get_by_id() -> BoxedFilter<(
         .boxed()
 }
 
-pub fn get_by_id_unauthenticated(
-) -> BoxedFilter<(Context, models::link::Link, Vec<models::page::Page>)> {
+#[allow(clippy::type_complexity)]
+pub fn get_by_id_unauthenticated() -> BoxedFilter<(
+    Context,
+    models::link::Link,
+    Vec<(models::page::Page, String)>,
+    Option<i32>,
+)> {
     path_prefix()
         .and(warp::get())
         .and(warp::path::param::<i32>())
@@ -45,7 +53,13 @@ pub fn get_by_id_unauthenticated(
         .boxed()
 }
 
-pub fn get_random_link() -> BoxedFilter<(Context, models::link::Link, Vec<models::page::Page>)> {
+#[allow(clippy::type_complexity)]
+pub fn get_random_link() -> BoxedFilter<(
+    Context,
+    models::link::Link,
+    Vec<(models::page::Page, String)>,
+    Option<i32>,
+)> {
     path_prefix()
         .and(warp::path("random"))
         .and(warp::get())
@@ -60,18 +74,21 @@ pub fn get_random_link() -> BoxedFilter<(Context, models::link::Link, Vec<models
         .boxed()
 }
 
+#[allow(clippy::type_complexity)]
 pub fn get_random_link_authenticated() -> BoxedFilter<(
     Context,
-    models::user::User,
+    models::user::ExpandedUser,
     models::link::Link,
+    Vec<(models::page::Page, String)>,
+    Option<i32>,
     Vec<models::page::Page>,
-    Vec<models::page::Page>,
+    Option<i32>,
 )> {
     path_prefix()
         .and(warp::path("random"))
         .and(warp::get())
         .and(warp::path::end())
-        .and(authenticate_cookie())
+        .and(authenticate_or_token())
         .and_then(with_random_link_authenticated)
         .untuple_one()
         .and_then(with_link)
@@ -83,6 +100,45 @@ pub fn get_random_link_authenticated() -> BoxedFilter<(
         .boxed()
 }
 
+// the next screen of groups containing the link, for htmx to append
+#[allow(clippy::type_complexity)]
+pub fn get_pages() -> BoxedFilter<(
+    Context,
+    models::link::Link,
+    Vec<(models::page::Page, String)>,
+    Option<i32>,
+)> {
+    path_prefix()
+        .and(warp::get())
+        .and(warp::path::param::<i32>())
+        .and(warp::path("pages"))
+        .and(warp::path::end())
+        .and(warp::query::<models::page::CursorQuery>())
+        .and(authenticate_or_token())
+        .and_then(with_more_pages_containing_link)
+        .untuple_one()
+        .boxed()
+}
+
+#[allow(clippy::type_complexity)]
+pub fn get_pages_unauthenticated() -> BoxedFilter<(
+    Context,
+    models::link::Link,
+    Vec<(models::page::Page, String)>,
+    Option<i32>,
+)> {
+    path_prefix()
+        .and(warp::get())
+        .and(warp::path::param::<i32>())
+        .and(warp::path("pages"))
+        .and(warp::path::end())
+        .and(warp::query::<models::page::CursorQuery>())
+        .and(filters::ext::get::<Context>())
+        .and_then(with_more_pages_containing_link_unauthenticated)
+        .untuple_one()
+        .boxed()
+}
+
 pub fn add_link_to_my_page() -> BoxedFilter<(
     Context,
     models::user::ExpandedUser,
@@ -93,8 +149,9 @@ pub fn add_link_to_my_page() -> BoxedFilter<(
         .and(warp::path("page"))
         .and(warp::post())
         .and(warp::path::end())
-        .and(authenticate_cookie())
-        .and(warp::body::form::<models::link::AddLinkToPageApi>())
+        .and(authenticate_csrf_form_or_token::<
+            models::link::AddLinkToPageApi,
+        >())
         .and_then(with_authenticated_page)
         .untuple_one()
         .and_then(routes::page::insert_new_link)
@@ -103,7 +160,7 @@ pub fn add_link_to_my_page() -> BoxedFilter<(
 }
 
 async fn with_authenticated_page(
-    link_id: i32,
+    _link_id: i32,
     context: Context,
     expanded_user: models::user::ExpandedUser,
     add_link_to_page: models::link::AddLinkToPageApi,
@@ -163,11 +220,11 @@ async fn with_link(
     id: i32,
     context: Context,
     expanded_user: models::user::ExpandedUser,
-) -> Result<(Context, models::user::User, models::link::Link), warp::Rejection> {
+) -> Result<(Context, models::user::ExpandedUser, models::link::Link), warp::Rejection> {
     let mut conn = context.db_conn.get_conn();
     log::info!("Looking for link with id of {}", id);
     let link = models::link::read_by_id(&mut conn, id).map_err(|_| reject::custom(NotFound))?;
-    Ok((context, expanded_user.user, link))
+    Ok((context, expanded_user, link))
 }
 
 async fn with_link_unauthenticated(
@@ -182,56 +239,131 @@ async fn with_link_unauthenticated(
 
 async fn with_pages_containing_link(
     context: Context,
-    user: models::user::User,
+    expanded_user: models::user::ExpandedUser,
     link: models::link::Link,
 ) -> Result<
     (
         Context,
-        models::user::User,
+        models::user::ExpandedUser,
         models::link::Link,
-        Vec<models::page::Page>,
+        Vec<(models::page::Page, String)>,
+        Option<i32>,
     ),
     warp::Rejection,
 > {
-    let mut conn = context.db_conn.get_conn();
+    let (pages, cursor) =
+        read_pages_containing_link(&context, &link, Some(expanded_user.user.id), None)?;
 
-    let pages =
-        models::page::read_pages_by_link(&mut conn, &link).map_err(|_| reject::custom(NotFound))?;
-
-    Ok((context, user, link, pages))
+    Ok((context, expanded_user, link, pages, cursor))
 }
 
 async fn with_pages_containing_link_unauthenticated(
     context: Context,
     link: models::link::Link,
-) -> Result<(Context, models::link::Link, Vec<models::page::Page>), warp::Rejection> {
-    let mut conn = context.db_conn.get_conn();
+) -> Result<
+    (
+        Context,
+        models::link::Link,
+        Vec<(models::page::Page, String)>,
+        Option<i32>,
+    ),
+    warp::Rejection,
+> {
+    let (pages, cursor) = read_pages_containing_link(&context, &link, None, None)?;
+
+    Ok((context, link, pages, cursor))
+}
+
+async fn with_more_pages_containing_link(
+    id: i32,
+    query: models::page::CursorQuery,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<
+    (
+        Context,
+        models::link::Link,
+        Vec<(models::page::Page, String)>,
+        Option<i32>,
+    ),
+    warp::Rejection,
+> {
+    let after = routes::page::parse_page_cursor(&query)?;
+    let (context, expanded_user, link) = with_link(id, context, expanded_user).await?;
+    let (pages, cursor) =
+        read_pages_containing_link(&context, &link, Some(expanded_user.user.id), after)?;
+
+    Ok((context, link, pages, cursor))
+}
+
+async fn with_more_pages_containing_link_unauthenticated(
+    id: i32,
+    query: models::page::CursorQuery,
+    context: Context,
+) -> Result<
+    (
+        Context,
+        models::link::Link,
+        Vec<(models::page::Page, String)>,
+        Option<i32>,
+    ),
+    warp::Rejection,
+> {
+    let after = routes::page::parse_page_cursor(&query)?;
+    let (context, link) = with_link_unauthenticated(id, context).await?;
+    let (pages, cursor) = read_pages_containing_link(&context, &link, None, after)?;
 
-    let pages =
-        models::page::read_pages_by_link(&mut conn, &link).map_err(|_| reject::custom(NotFound))?;
+    Ok((context, link, pages, cursor))
+}
+
+#[allow(clippy::type_complexity)]
+fn read_pages_containing_link(
+    context: &Context,
+    link: &models::link::Link,
+    user_id: Option<i32>,
+    after: Option<i32>,
+) -> Result<(Vec<(models::page::Page, String)>, Option<i32>), warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
 
-    Ok((context, link, pages))
+    models::page::read_pages_by_link(&mut conn, link, user_id, after, PAGE_SIZE)
+        .map_err(|_| reject::custom(NotFound))
 }
 
 async fn with_my_pages(
     context: Context,
-    user: models::user::User,
+    expanded_user: models::user::ExpandedUser,
     link: models::link::Link,
-    pages: Vec<models::page::Page>,
+    pages: Vec<(models::page::Page, String)>,
+    cursor: Option<i32>,
 ) -> Result<
     (
         Context,
-        models::user::User,
+        models::user::ExpandedUser,
         models::link::Link,
+        Vec<(models::page::Page, String)>,
+        Option<i32>,
         Vec<models::page::Page>,
-        Vec<models::page::Page>,
+        Option<i32>,
     ),
     warp::Rejection,
 > {
     let mut conn = context.db_conn.get_conn();
 
-    let my_pages = models::page::read_pages_by_user_id(&mut conn, user.id)
-        .map_err(|_| reject::custom(NotFound))?;
+    let (my_pages, my_cursor) = models::page::read_pages_by_user_id_after(
+        &mut conn,
+        expanded_user.user.id,
+        None,
+        PAGE_SIZE,
+    )
+    .map_err(|_| reject::custom(NotFound))?;
 
-    Ok((context, user, link, pages, my_pages))
+    Ok((
+        context,
+        expanded_user,
+        link,
+        pages,
+        cursor,
+        my_pages,
+        my_cursor,
+    ))
 }
@@ -0,0 +1,257 @@
+use crate::{
+    config::{OAuthProviderConfig, SignupMode},
+    models, oauth,
+    server::Context,
+    utils::{constant_time_eq, encrypt, generate_token, hash_token, random},
+    validation, NotAuthorized, NotFound, SignupClosed,
+};
+use diesel::{Connection, PgConnection};
+use warp::{
+    filters::{self, BoxedFilter},
+    reject, Filter,
+};
+
+use super::user::{client_info, new_user_error, with_new_session};
+
+fn path_prefix() -> BoxedFilter<()> {
+    warp::path("oauth").boxed()
+}
+
+pub fn providers() -> BoxedFilter<(Context,)> {
+    path_prefix()
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(filters::ext::get::<Context>())
+        .boxed()
+}
+
+pub fn start() -> BoxedFilter<(String, String)> {
+    path_prefix()
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(filters::ext::get::<Context>())
+        .and_then(start_authorization)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn callback() -> BoxedFilter<(Context, models::user::ExpandedUser, String)> {
+    path_prefix()
+        .and(warp::path::param::<String>())
+        .and(warp::path("callback"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(filters::ext::get::<Context>())
+        .and(warp::cookie::<String>("oauth_state"))
+        .and(warp::query::<models::identity::OAuthCallbackQuery>())
+        .and_then(finish_authorization)
+        .untuple_one()
+        .and(client_info())
+        .and_then(with_new_session)
+        .untuple_one()
+        .boxed()
+}
+
+fn get_provider(context: &Context, slug: &str) -> Result<OAuthProviderConfig, warp::Rejection> {
+    context
+        .config
+        .oauth_provider(slug)
+        .cloned()
+        .ok_or_else(|| reject::custom(NotFound))
+}
+
+// the state goes to the provider and into a cookie, the database only sees its hash
+async fn start_authorization(
+    slug: String,
+    context: Context,
+) -> Result<(String, String), warp::Rejection> {
+    let provider = get_provider(&context, &slug)?;
+    let mut conn = context.db_conn.get_conn();
+
+    let state = generate_token();
+    let code_verifier = generate_token();
+    let nonce = generate_token();
+    models::oauth_state::NewOAuthState::new(
+        &provider.slug,
+        hash_token(&state),
+        code_verifier.clone(),
+    )
+    .with_nonce(nonce.clone())
+    .insert(&mut conn)
+    .map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    let redirect_uri = oauth::redirect_uri(&context.config.app_url, &provider);
+    let url = oauth::authorize_url(&provider, &redirect_uri, &state, &code_verifier, &nonce)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    Ok((url, state))
+}
+
+async fn finish_authorization(
+    slug: String,
+    context: Context,
+    cookie: String,
+    query: models::identity::OAuthCallbackQuery,
+) -> Result<(Context, models::user::User, models::background::Background), warp::Rejection> {
+    let provider = get_provider(&context, &slug)?;
+
+    if let Some(error) = &query.error {
+        log::info!("{} refused the sign in: {}", provider.slug, error);
+        return Err(reject::custom(NotAuthorized));
+    }
+    let (code, state) = match (query.code, query.state) {
+        (Some(code), Some(state)) => (code, state),
+        _ => return Err(reject::custom(NotAuthorized)),
+    };
+    // the callback has to land in the same browser that started the flow
+    if !constant_time_eq(&state, &cookie) {
+        log::warn!("OAuth state mismatch for {}", provider.slug);
+        return Err(reject::custom(NotAuthorized));
+    }
+
+    let oauth_state = take_state(&context, &provider, &state)?;
+
+    let redirect_uri = oauth::redirect_uri(&context.config.app_url, &provider);
+    let token = oauth::exchange_code(&provider, &redirect_uri, &code, &oauth_state.code_verifier)
+        .await
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            reject::custom(NotAuthorized)
+        })?;
+    let claims = match (&token.id_token, &oauth_state.nonce) {
+        (Some(id_token), Some(nonce)) => oauth::verify_id_token(&provider, id_token, nonce)
+            .map_err(|e| {
+                log::warn!("{:?}", e);
+                reject::custom(NotAuthorized)
+            })?,
+        _ => {
+            log::warn!("{} sent no id token to check", provider.slug);
+            return Err(reject::custom(NotAuthorized));
+        }
+    };
+    let info = oauth::fetch_userinfo(&provider, &token.access_token)
+        .await
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            reject::custom(NotAuthorized)
+        })?;
+    // userinfo has to describe the same person the id token was issued for
+    if info.sub != claims.sub {
+        log::warn!("{} userinfo and id token disagree on sub", provider.slug);
+        return Err(reject::custom(NotAuthorized));
+    }
+
+    let mut conn = context.db_conn.get_conn();
+    let user_id = match models::identity::read_by_provider_and_subject(
+        &mut conn,
+        &provider.slug,
+        &info.sub,
+    ) {
+        Ok(identity) => identity.user_id,
+        Err(diesel::NotFound) => {
+            let email = info.verified_email();
+            insert_user_for_identity(
+                &context,
+                &mut conn,
+                &provider.slug,
+                &info.sub,
+                email.clone(),
+                &[
+                    info.preferred_username.as_deref(),
+                    email.as_deref(),
+                    info.name.as_deref(),
+                ],
+            )?
+        }
+        Err(e) => {
+            log::error!("{:?}", e);
+            return Err(warp::reject());
+        }
+    };
+
+    let (user, background) = models::user::read_with_background_by_id(&mut conn, user_id)
+        .map_err(|_| reject::custom(NotFound))?;
+
+    log::info!("Recognized user {} by {}", user.username, provider.slug);
+    Ok((context, user, background))
+}
+
+fn take_state(
+    context: &Context,
+    provider: &OAuthProviderConfig,
+    state: &str,
+) -> Result<models::oauth_state::OAuthState, warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+    let oauth_state = models::oauth_state::read_valid_by_state_hash(
+        &mut conn,
+        &provider.slug,
+        &hash_token(state),
+    )
+    .map_err(|_| reject::custom(NotFound))?;
+
+    models::oauth_state::delete(&mut conn, &oauth_state).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    Ok(oauth_state)
+}
+
+// first time we see this identity, so it gets an account of its own
+pub fn insert_user_for_identity(
+    context: &Context,
+    conn: &mut PgConnection,
+    provider: &str,
+    subject: &str,
+    email: Option<String>,
+    username_hints: &[Option<&str>],
+) -> Result<i32, warp::Rejection> {
+    // providers have nowhere to put an invite, so only open instances make accounts here
+    if context.config.signup_mode != SignupMode::Open {
+        log::info!("Refusing new {} account, signups are not open", provider);
+        return Err(reject::custom(SignupClosed));
+    }
+
+    let username = available_username(conn, &validation::suggest_username(username_hints));
+
+    // an address another account already resets with stays on the identity only
+    let account_email = email
+        .clone()
+        .filter(|email| models::user::read_by_email(conn, email).is_err());
+
+    // nobody knows this password, a reset link can set a real one later
+    let credentials = models::user::UserCredentialsEncrypted {
+        username,
+        password: encrypt(&generate_token(), &context.config.password_params),
+        email: account_email,
+    };
+    // an account without its identity could never be signed into, so both or neither
+    let user = conn
+        .transaction(|conn| {
+            let (user, _) = models::user::create_with_background(conn, credentials)?;
+            models::identity::NewIdentity::new(user.id, provider, subject, email).insert(conn)?;
+            Ok(user)
+        })
+        .map_err(|e: diesel::result::Error| new_user_error(context, e))?;
+
+    log::info!("Created user {} for {} identity", user.username, provider);
+    Ok(user.id)
+}
+
+fn available_username(conn: &mut PgConnection, username: &str) -> String {
+    let mut candidate = username.to_string();
+    for _ in 0..5 {
+        if models::user::read_by_username(conn, &candidate).is_err() {
+            return candidate;
+        }
+        candidate = format!("{}-{}", username, random(9000, 1000));
+    }
+    format!("{}-{}", username, &generate_token()[..8])
+}
@@ -1,13 +1,19 @@
 use crate::{
-    models, routes, server::Context, NotFound, ResourceError, ResourceErrorData, MAX_LINK_COUNT,
-    MAX_PAGE_COUNT,
+    models, routes, server::Context, MalformedForm, NotFound, ResourceError, ResourceErrorData,
+    PAGE_SIZE,
+};
+use diesel::{
+    result::{DatabaseErrorKind, Error::DatabaseError},
+    Connection,
 };
-use diesel::result::{DatabaseErrorKind, Error::DatabaseError};
 use warp::{
     filters::{self, BoxedFilter},
     reject, Filter,
 };
 
+// a few paragraphs, the card is not the place for an essay
+const MAX_NOTES_LENGTH: usize = 2000;
+
 pub fn get() -> BoxedFilter<(
     Context,
     models::user::ExpandedUser,
@@ -16,7 +22,7 @@ pub fn get() -> BoxedFilter<(
     warp::path::param::<i32>()
         .and(warp::path::end())
         .and(warp::get())
-        .and(routes::user::authenticate_cookie())
+        .and(routes::user::authenticate_or_token())
         .and_then(with_page)
         .untuple_one()
         .boxed()
@@ -30,7 +36,7 @@ pub fn get_authenticated() -> BoxedFilter<(
     warp::path::param::<i32>()
         .and(warp::path::end())
         .and(warp::get())
-        .and(routes::user::authenticate_cookie())
+        .and(routes::user::authenticate_or_token())
         .and_then(with_authenticated_page)
         .untuple_one()
         .boxed()
@@ -46,6 +52,127 @@ pub fn get_unauthenticated() -> BoxedFilter<(Context, models::page::ExpandedPage
         .boxed()
 }
 
+// /u/{username}/{slug} reaches the same pages as /page/{id}
+pub fn get_by_slug() -> BoxedFilter<(
+    Context,
+    models::user::ExpandedUser,
+    models::page::ExpandedPage,
+)> {
+    warp::path::param::<String>()
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(routes::user::authenticate_or_token())
+        .and_then(with_page_by_slug)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn get_by_slug_authenticated() -> BoxedFilter<(
+    Context,
+    models::user::ExpandedUser,
+    models::page::ExpandedPage,
+)> {
+    warp::path::param::<String>()
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(routes::user::authenticate_or_token())
+        .and_then(with_authenticated_page_by_slug)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn get_by_slug_unauthenticated() -> BoxedFilter<(Context, models::page::ExpandedPage)> {
+    warp::path::param::<String>()
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(filters::ext::get::<Context>())
+        .and_then(with_page_by_slug_unauthenticated)
+        .untuple_one()
+        .boxed()
+}
+
+// a slug the page has since been renamed away from
+pub fn get_by_old_slug() -> BoxedFilter<(String,)> {
+    warp::path::param::<String>()
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(routes::user::authenticate_or_token())
+        .and_then(with_current_url)
+        .boxed()
+}
+
+pub fn get_by_old_slug_unauthenticated() -> BoxedFilter<(String,)> {
+    warp::path::param::<String>()
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(filters::ext::get::<Context>())
+        .and_then(with_current_url_unauthenticated)
+        .boxed()
+}
+
+// the next screen of a page's links, for htmx to append
+#[allow(clippy::type_complexity)]
+pub fn get_links() -> BoxedFilter<(
+    Context,
+    models::page::ExpandedPage,
+    Vec<(models::link::Link, models::page_link::PageLink)>,
+    Option<models::page_link::LinkCursor>,
+)> {
+    warp::path::param::<i32>()
+        .and(warp::path("links"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<models::page::CursorQuery>())
+        .and(routes::user::authenticate_or_token())
+        .and_then(with_more_links)
+        .untuple_one()
+        .boxed()
+}
+
+// the owner's next screen, with the forms to edit each link
+#[allow(clippy::type_complexity)]
+pub fn get_links_authenticated() -> BoxedFilter<(
+    Context,
+    models::user::ExpandedUser,
+    models::page::ExpandedPage,
+    Vec<(models::link::Link, models::page_link::PageLink)>,
+    Option<models::page_link::LinkCursor>,
+)> {
+    warp::path::param::<i32>()
+        .and(warp::path("links"))
+        .and(warp::path("edit"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<models::page::CursorQuery>())
+        .and(routes::user::authenticate_or_token())
+        .and_then(with_more_links_authenticated)
+        .untuple_one()
+        .boxed()
+}
+
+#[allow(clippy::type_complexity)]
+pub fn get_links_unauthenticated() -> BoxedFilter<(
+    Context,
+    models::page::ExpandedPage,
+    Vec<(models::link::Link, models::page_link::PageLink)>,
+    Option<models::page_link::LinkCursor>,
+)> {
+    warp::path::param::<i32>()
+        .and(warp::path("links"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<models::page::CursorQuery>())
+        .and(filters::ext::get::<Context>())
+        .and_then(with_more_links_unauthenticated)
+        .untuple_one()
+        .boxed()
+}
+
 pub fn create() -> BoxedFilter<(
     Context,
     models::user::ExpandedUser,
@@ -53,17 +180,37 @@ pub fn create() -> BoxedFilter<(
 )> {
     warp::path::end()
         .and(warp::post())
-        .and(routes::user::authenticate_cookie())
-        .and(warp::body::form::<models::page::NewPageApi>())
+        .and(routes::user::authenticate_csrf_form_or_token::<
+            models::page::NewPageApi,
+        >())
         .and_then(insert_new_page)
         .untuple_one()
         .boxed()
 }
 
-pub fn delete() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+// htmx sends a PUT, a plain form falls back to POST
+pub fn update() -> BoxedFilter<(
+    Context,
+    models::user::ExpandedUser,
+    models::page::ExpandedPage,
+)> {
+    warp::path::param::<i32>()
+        .and(warp::path::end())
+        .and(warp::put().or(warp::post()).unify())
+        .and(routes::user::authenticate_csrf_form_or_token::<
+            models::page::NewPageApi,
+        >())
+        .and_then(with_authenticated_page_and_form)
+        .untuple_one()
+        .and_then(update_page)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn delete() -> BoxedFilter<(Context, models::user::ExpandedUser, String)> {
     warp::delete()
         .and(warp::path::param::<i32>())
-        .and(routes::user::authenticate_cookie())
+        .and(routes::user::authenticate_csrf_or_token())
         .and_then(with_authenticated_page)
         .untuple_one()
         .and(warp::path::end())
@@ -81,10 +228,11 @@ pub fn create_link() -> BoxedFilter<(
         .and(warp::path("link"))
         .and(warp::path::end())
         .and(warp::post())
-        .and(routes::user::authenticate_cookie())
-        .and_then(with_authenticated_page)
+        .and(routes::user::authenticate_csrf_form_or_token::<
+            models::link::NewLinkApi,
+        >())
+        .and_then(with_authenticated_page_and_form)
         .untuple_one()
-        .and(warp::body::form::<models::link::NewLinkApi>())
         .and_then(insert_new_link)
         .untuple_one()
         .boxed()
@@ -94,11 +242,12 @@ pub fn delete_link() -> BoxedFilter<(
     Context,
     models::user::ExpandedUser,
     models::page::ExpandedPage,
+    String,
 )> {
     warp::delete()
         .and(warp::path::param::<i32>())
         .and(warp::path("link"))
-        .and(routes::user::authenticate_cookie())
+        .and(routes::user::authenticate_csrf_or_token())
         .and_then(with_authenticated_page)
         .untuple_one()
         .and(warp::path::param::<i32>())
@@ -108,6 +257,86 @@ pub fn delete_link() -> BoxedFilter<(
         .boxed()
 }
 
+pub fn reorder_links() -> BoxedFilter<(
+    Context,
+    models::user::ExpandedUser,
+    models::page::ExpandedPage,
+)> {
+    warp::patch()
+        .and(warp::path::param::<i32>())
+        .and(warp::path("links"))
+        .and(warp::path("order"))
+        .and(warp::path::end())
+        .and(routes::user::authenticate_csrf_form_or_token::<
+            models::page_link::LinkOrderApi,
+        >())
+        .and_then(with_authenticated_page_and_form)
+        .untuple_one()
+        .and_then(update_link_order)
+        .untuple_one()
+        .boxed()
+}
+
+// the up/down buttons for when dragging isn't an option
+pub fn move_link() -> BoxedFilter<(
+    Context,
+    models::user::ExpandedUser,
+    models::page::ExpandedPage,
+)> {
+    warp::post()
+        .and(warp::path::param::<i32>())
+        .and(warp::path("links"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("move"))
+        .and(warp::path::end())
+        .and(routes::user::authenticate_csrf_form_or_token::<
+            models::page_link::MoveLinkApi,
+        >())
+        .and_then(shift_link)
+        .untuple_one()
+        .boxed()
+}
+
+// htmx sends a PUT, a plain form falls back to POST
+pub fn update_link() -> BoxedFilter<(
+    Context,
+    models::user::ExpandedUser,
+    models::page::ExpandedPage,
+)> {
+    warp::path::param::<i32>()
+        .and(warp::path("link"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(warp::put().or(warp::post()).unify())
+        .and(routes::user::authenticate_csrf_form_or_token::<
+            models::page_link::EditPageLinkApi,
+        >())
+        .and_then(edit_link)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn bulk_links() -> BoxedFilter<(
+    Context,
+    models::user::ExpandedUser,
+    models::page::ExpandedPage,
+    Vec<models::page_link::BulkLinkResult>,
+)> {
+    warp::post()
+        .and(warp::path::param::<i32>())
+        .and(warp::path("links"))
+        .and(warp::path("bulk"))
+        .and(warp::path::end())
+        .and(routes::user::authenticate_csrf_form_or_token::<
+            Vec<(String, String)>,
+        >())
+        .and_then(with_authenticated_page_and_form)
+        .untuple_one()
+        .and_then(update_links_in_bulk)
+        .untuple_one()
+        .boxed()
+}
+
 async fn with_page(
     id: i32,
     context: Context,
@@ -123,6 +352,9 @@ async fn with_page(
     let mut conn = context.db_conn.get_conn();
     log::info!("Looking for page with id of {}", id);
     let page = models::page::read_by_id(&mut conn, id).map_err(|_| reject::custom(NotFound))?;
+    if !page.page.is_visible_to(Some(expanded_user.user.id)) {
+        return Err(reject::custom(NotFound));
+    }
     Ok((context, expanded_user, page))
 }
 
@@ -133,9 +365,210 @@ async fn with_page_unauthenticated(
     let mut conn = context.db_conn.get_conn();
     log::info!("Looking for page with id of {}", id);
     let page = models::page::read_by_id(&mut conn, id).map_err(|_| reject::custom(NotFound))?;
+    if !page.page.is_visible_to(None) {
+        return Err(reject::custom(NotFound));
+    }
+    Ok((context, page))
+}
+
+async fn with_page_by_slug(
+    username: String,
+    slug: String,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<
+    (
+        Context,
+        models::user::ExpandedUser,
+        models::page::ExpandedPage,
+    ),
+    warp::Rejection,
+> {
+    let mut conn = context.db_conn.get_conn();
+    log::info!("Looking for page {} of {}", slug, username);
+    let page = models::page::read_by_username_and_slug(&mut conn, &username, &slug)
+        .map_err(|_| reject::custom(NotFound))?;
+    if !page.page.is_visible_to(Some(expanded_user.user.id)) {
+        return Err(reject::custom(NotFound));
+    }
+    Ok((context, expanded_user, page))
+}
+
+async fn with_authenticated_page_by_slug(
+    username: String,
+    slug: String,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<
+    (
+        Context,
+        models::user::ExpandedUser,
+        models::page::ExpandedPage,
+    ),
+    warp::Rejection,
+> {
+    if username != expanded_user.user.username {
+        return Err(reject::custom(NotFound));
+    }
+    with_page_by_slug(username, slug, context, expanded_user).await
+}
+
+async fn with_page_by_slug_unauthenticated(
+    username: String,
+    slug: String,
+    context: Context,
+) -> Result<(Context, models::page::ExpandedPage), warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+    log::info!("Looking for page {} of {}", slug, username);
+    let page = models::page::read_by_username_and_slug(&mut conn, &username, &slug)
+        .map_err(|_| reject::custom(NotFound))?;
+    if !page.page.is_visible_to(None) {
+        return Err(reject::custom(NotFound));
+    }
     Ok((context, page))
 }
 
+async fn with_current_url(
+    username: String,
+    slug: String,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<String, warp::Rejection> {
+    read_current_url(&context, &username, &slug, Some(expanded_user.user.id))
+}
+
+async fn with_current_url_unauthenticated(
+    username: String,
+    slug: String,
+    context: Context,
+) -> Result<String, warp::Rejection> {
+    read_current_url(&context, &username, &slug, None)
+}
+
+fn read_current_url(
+    context: &Context,
+    username: &str,
+    slug: &str,
+    user_id: Option<i32>,
+) -> Result<String, warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+    let page = models::page_slug::read_page_by_username_and_slug(&mut conn, username, slug)
+        .map_err(|_| reject::custom(NotFound))?;
+    if !page.is_visible_to(user_id) {
+        return Err(reject::custom(NotFound));
+    }
+    Ok(page.url(username))
+}
+
+pub fn parse_page_cursor(
+    query: &models::page::CursorQuery,
+) -> Result<Option<i32>, warp::Rejection> {
+    match query.cursor.as_deref() {
+        None | Some("") => Ok(None),
+        Some(cursor) => cursor
+            .trim()
+            .parse::<i32>()
+            .map(Some)
+            .map_err(|_| reject::custom(MalformedForm(String::from("Unknown cursor")))),
+    }
+}
+
+fn parse_link_cursor(
+    query: &models::page::CursorQuery,
+) -> Result<Option<models::page_link::LinkCursor>, warp::Rejection> {
+    match query.cursor.as_deref() {
+        None | Some("") => Ok(None),
+        Some(cursor) => models::page_link::LinkCursor::parse(cursor)
+            .map(Some)
+            .ok_or_else(|| reject::custom(MalformedForm(String::from("Unknown cursor")))),
+    }
+}
+
+async fn with_more_links(
+    id: i32,
+    query: models::page::CursorQuery,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<
+    (
+        Context,
+        models::page::ExpandedPage,
+        Vec<(models::link::Link, models::page_link::PageLink)>,
+        Option<models::page_link::LinkCursor>,
+    ),
+    warp::Rejection,
+> {
+    let after = parse_link_cursor(&query)?;
+    let (context, _, expanded_page) = with_page(id, context, expanded_user).await?;
+    let (links, cursor) = read_links_after(&context, &expanded_page, after)?;
+
+    Ok((context, expanded_page, links, cursor))
+}
+
+async fn with_more_links_authenticated(
+    id: i32,
+    query: models::page::CursorQuery,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<
+    (
+        Context,
+        models::user::ExpandedUser,
+        models::page::ExpandedPage,
+        Vec<(models::link::Link, models::page_link::PageLink)>,
+        Option<models::page_link::LinkCursor>,
+    ),
+    warp::Rejection,
+> {
+    let after = parse_link_cursor(&query)?;
+    let (context, expanded_user, expanded_page) =
+        with_authenticated_page(id, context, expanded_user).await?;
+    let (links, cursor) = read_links_after(&context, &expanded_page, after)?;
+
+    Ok((context, expanded_user, expanded_page, links, cursor))
+}
+
+async fn with_more_links_unauthenticated(
+    id: i32,
+    query: models::page::CursorQuery,
+    context: Context,
+) -> Result<
+    (
+        Context,
+        models::page::ExpandedPage,
+        Vec<(models::link::Link, models::page_link::PageLink)>,
+        Option<models::page_link::LinkCursor>,
+    ),
+    warp::Rejection,
+> {
+    let after = parse_link_cursor(&query)?;
+    let (context, expanded_page) = with_page_unauthenticated(id, context).await?;
+    let (links, cursor) = read_links_after(&context, &expanded_page, after)?;
+
+    Ok((context, expanded_page, links, cursor))
+}
+
+#[allow(clippy::type_complexity)]
+fn read_links_after(
+    context: &Context,
+    expanded_page: &models::page::ExpandedPage,
+    after: Option<models::page_link::LinkCursor>,
+) -> Result<
+    (
+        Vec<(models::link::Link, models::page_link::PageLink)>,
+        Option<models::page_link::LinkCursor>,
+    ),
+    warp::Rejection,
+> {
+    let mut conn = context.db_conn.get_conn();
+
+    models::link::read_links_by_page_after(&mut conn, &expanded_page.page, after, PAGE_SIZE)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject::not_found()
+        })
+}
+
 async fn with_authenticated_page(
     id: i32,
     context: Context,
@@ -155,6 +588,35 @@ async fn with_authenticated_page(
     Ok((context, expanded_user, page))
 }
 
+async fn with_authenticated_page_and_form<T>(
+    id: i32,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    form: T,
+) -> Result<
+    (
+        Context,
+        models::user::ExpandedUser,
+        models::page::ExpandedPage,
+        T,
+    ),
+    warp::Rejection,
+> {
+    let (context, expanded_user, page) =
+        with_authenticated_page(id, context, expanded_user).await?;
+    Ok((context, expanded_user, page, form))
+}
+
+// leaving it out is fine, a typo is not
+fn check_visibility(details: &models::page::NewPageApi) -> Result<(), warp::Rejection> {
+    match details.visibility.as_deref() {
+        Some(visibility) if models::page::parse_visibility(visibility).is_none() => Err(
+            reject::custom(MalformedForm(String::from("Unknown page visibility"))),
+        ),
+        _ => Ok(()),
+    }
+}
+
 async fn insert_new_page(
     context: Context,
     expanded_user: models::user::ExpandedUser,
@@ -168,54 +630,110 @@ async fn insert_new_page(
     warp::Rejection,
 > {
     log::info!("Saving Page");
+    check_visibility(&new_page)?;
     let mut conn = context.db_conn.get_conn();
 
-    let count = models::page::get_count_of_pages_per_user(&mut conn, expanded_user.user.id)
-        .map_err(|e| {
-            log::error!("{:?}", e);
-            warp::reject()
-        })?;
+    // the user row is locked while counting, so parallel requests can't all slip under the limit
+    let max_pages = context.config.quota_for(&expanded_user.user).max_pages;
+    let created = conn
+        .transaction(|conn| {
+            models::user::lock_by_id(conn, expanded_user.user.id)?;
+            if models::page::get_count_of_pages_per_user(conn, expanded_user.user.id)? >= max_pages
+            {
+                return Ok(None);
+            }
 
-    if count >= MAX_PAGE_COUNT {
+            let background = models::background::random_bg().insert(conn)?;
+            let taken = models::page::read_slugs_by_user_id(conn, expanded_user.user.id)?;
+            let page = models::page::NewPage::new(new_page, expanded_user.user.id, background.id)
+                .with_slug(&taken)
+                .insert(conn)?;
+            Ok(Some((page, background)))
+        })
+        .map_err(|e: diesel::result::Error| match e {
+            DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                warp::reject::custom(ResourceError::Duplicate(ResourceErrorData {
+                    context: Some(context.clone()),
+                    expanded_user: Some(expanded_user.clone()),
+                    expanded_page: None,
+                }))
+            }
+            err => {
+                log::error!("{:?}", err);
+                warp::reject()
+            }
+        })?;
+    let Some((page, background)) = created else {
         return Err(warp::reject::custom(ResourceError::TooMany(
             ResourceErrorData {
                 context: Some(context),
                 expanded_user: Some(expanded_user),
                 expanded_page: None,
             },
+            max_pages,
         )));
-    }
+    };
+    log::info!("Saved Page");
+    Ok((
+        context,
+        expanded_user,
+        models::page::expand(page, background),
+    ))
+}
 
-    let background = models::background::random_bg()
-        .insert(&mut conn)
-        .map_err(|e| {
-            log::error!("{:?}", e);
-            warp::reject()
-        })?;
+async fn update_page(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    expanded_page: models::page::ExpandedPage,
+    details: models::page::NewPageApi,
+) -> Result<
+    (
+        Context,
+        models::user::ExpandedUser,
+        models::page::ExpandedPage,
+    ),
+    warp::Rejection,
+> {
+    log::info!("Updating Page");
+    check_visibility(&details)?;
+    let mut conn = context.db_conn.get_conn();
 
-    let page = models::page::NewPage::new(new_page, expanded_user.user.id, background.id)
-        .insert(&mut conn)
-        .map_err(|e| {
-            log::error!("{:?}", e);
-            match e {
-                DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
-                    warp::reject::custom(ResourceError::Duplicate(ResourceErrorData {
-                        context: Some(context.clone()),
-                        expanded_user: Some(expanded_user.clone()),
-                        expanded_page: None,
-                    }))
-                }
-                err => {
-                    log::error!("{:?}", err);
-                    warp::reject()
-                }
+    let mut page = expanded_page.page.with_details(details);
+    if page.slug != expanded_page.page.slug {
+        let taken = models::page::read_slugs_by_user_id(&mut conn, expanded_user.user.id)
+            .map_err(|e| {
+                log::error!("{:?}", e);
+                warp::reject()
+            })?
+            .into_iter()
+            .filter(|slug| *slug != expanded_page.page.slug)
+            .collect::<Vec<_>>();
+        page.slug = models::page::unique_slug(&page.slug, &taken);
+    }
+
+    models::page::update_with_slug_history(&mut conn, &mut page, &expanded_page.page.slug)
+        .map_err(|e| match e {
+            DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                warp::reject::custom(ResourceError::Duplicate(ResourceErrorData {
+                    context: Some(context.clone()),
+                    expanded_user: Some(expanded_user.clone()),
+                    expanded_page: Some(expanded_page.clone()),
+                }))
+            }
+            err => {
+                log::error!("{:?}", err);
+                warp::reject()
             }
         })?;
-    log::info!("Saved Page");
+
+    log::info!("Updated Page");
     Ok((
         context,
         expanded_user,
-        models::page::expand(page, background),
+        models::page::ExpandedPage {
+            page,
+            ..expanded_page
+        },
     ))
 }
 
@@ -223,13 +741,17 @@ async fn remove_page(
     context: Context,
     expanded_user: models::user::ExpandedUser,
     expanded_page: models::page::ExpandedPage,
-) -> Result<(Context, models::user::ExpandedUser), warp::Rejection> {
+) -> Result<(Context, models::user::ExpandedUser, String), warp::Rejection> {
     log::info!("Removing Page");
     let mut conn = context.db_conn.get_conn();
     models::page::delete(&mut conn, &expanded_page.page).map_err(|_| reject::custom(NotFound))?;
 
     log::info!("Removed Page");
-    Ok((context, expanded_user))
+    Ok((
+        context,
+        expanded_user,
+        routes::trash::restore_page_path(expanded_page.page.id),
+    ))
 }
 
 pub async fn insert_new_link(
@@ -247,42 +769,40 @@ pub async fn insert_new_link(
 > {
     log::info!("Saving Link");
     let mut conn = context.db_conn.get_conn();
-    let count = models::page_link::get_count_of_links_per_page(&mut conn, expanded_page.page.id)
-        .map_err(|err| {
-            log::error!("{:?}", err);
-            warp::reject()
-        })?;
-
-    log::error!("{} {}", count, MAX_LINK_COUNT);
-    if count >= MAX_LINK_COUNT {
-        return Err(warp::reject::custom(ResourceError::TooMany(
-            ResourceErrorData {
-                context: Some(context),
-                expanded_user: Some(expanded_user),
-                expanded_page: Some(expanded_page),
-            },
-        )));
-    }
 
+    // fetching a new link's metadata can be slow, so it's done before anything is locked
     let name = new_link.name.clone();
-
-    let link = match models::link::read_by_url(&mut conn, new_link.url.clone()) {
-        Err(diesel::NotFound) => models::link::NewLink::new(new_link, expanded_user.user.id)
-            .insert(&mut conn)
-            .map_err(|err| {
-                log::error!("{:?}", err);
-                warp::reject()
-            }),
-        Ok(link) => Ok(link),
-        err => {
+    let url = new_link.url.clone();
+    let fetched = match models::link::read_by_url(&mut conn, url.clone()) {
+        Err(diesel::NotFound) => Some(models::link::NewLink::new(new_link, expanded_user.user.id)),
+        Ok(_) => None,
+        Err(err) => {
             log::error!("{:?}", err);
-            Err(warp::reject())
+            return Err(warp::reject());
         }
-    }?;
+    };
 
-    models::page_link::NewPageLink::new(expanded_page.page.id, link.id, name)
-        .insert(&mut conn)
-        .map_err(|e| match e {
+    // the owner's row is locked while counting, so parallel requests can't all slip under the limit
+    let max_links = context.config.quota_for(&expanded_user.user).max_links;
+    let saved = conn
+        .transaction(|conn| {
+            models::user::lock_by_id(conn, expanded_user.user.id)?;
+            let count =
+                models::page_link::get_count_of_links_per_page(conn, expanded_page.page.id)?;
+            if count >= max_links {
+                return Ok(None);
+            }
+
+            let link = match (models::link::read_by_url(conn, url.clone()), &fetched) {
+                (Err(diesel::NotFound), Some(fetched)) => fetched.insert(conn)?,
+                (link, _) => link?,
+            };
+            let position = models::page_link::next_position(conn, expanded_page.page.id)?;
+            models::page_link::NewPageLink::new(expanded_page.page.id, link.id, name, position)
+                .insert(conn)
+                .map(Some)
+        })
+        .map_err(|e: diesel::result::Error| match e {
             DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
                 reject::custom(ResourceError::Duplicate(ResourceErrorData {
                     context: Some(context.clone()),
@@ -295,6 +815,16 @@ pub async fn insert_new_link(
                 warp::reject()
             }
         })?;
+    if saved.is_none() {
+        return Err(warp::reject::custom(ResourceError::TooMany(
+            ResourceErrorData {
+                context: Some(context),
+                expanded_user: Some(expanded_user),
+                expanded_page: Some(expanded_page),
+            },
+            max_links,
+        )));
+    }
 
     log::info!("Saved Link");
     Ok((context, expanded_user, expanded_page))
@@ -310,18 +840,263 @@ async fn remove_link(
         Context,
         models::user::ExpandedUser,
         models::page::ExpandedPage,
+        String,
     ),
     warp::Rejection,
 > {
     log::info!("Removing PageLink");
     let mut conn = context.db_conn.get_conn();
-    models::page_link::remove_link_by_page_id_and_link_id(
+    let removed = models::page_link::remove_link_by_page_id_and_link_id(
         &mut conn,
         expanded_page.page.id,
         link_id,
     )
     .map_err(|_| reject::custom(NotFound))?;
+    let id = removed.first().ok_or_else(|| reject::custom(NotFound))?;
 
     log::info!("Removed PageLink");
+    Ok((
+        context,
+        expanded_user,
+        expanded_page,
+        routes::trash::restore_link_path(*id),
+    ))
+}
+
+async fn update_link_order(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    expanded_page: models::page::ExpandedPage,
+    form: models::page_link::LinkOrderApi,
+) -> Result<
+    (
+        Context,
+        models::user::ExpandedUser,
+        models::page::ExpandedPage,
+    ),
+    warp::Rejection,
+> {
+    let link_ids = models::page_link::parse_order(&form.links)
+        .ok_or_else(|| reject::custom(MalformedForm(String::from("Unknown link order"))))?;
+
+    log::info!("Reordering PageLinks");
+    let mut conn = context.db_conn.get_conn();
+    models::page_link::reorder(&mut conn, expanded_page.page.id, &link_ids).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    log::info!("Reordered PageLinks");
+    Ok((context, expanded_user, expanded_page))
+}
+
+async fn shift_link(
+    id: i32,
+    link_id: i32,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    form: models::page_link::MoveLinkApi,
+) -> Result<
+    (
+        Context,
+        models::user::ExpandedUser,
+        models::page::ExpandedPage,
+    ),
+    warp::Rejection,
+> {
+    let (context, expanded_user, expanded_page) =
+        with_authenticated_page(id, context, expanded_user).await?;
+
+    log::info!("Moving PageLink");
+    let mut conn = context.db_conn.get_conn();
+    let link_ids = models::page_link::read_link_ids_by_page_id(&mut conn, expanded_page.page.id)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    // already at the top or bottom, nothing to do
+    if let Some(link_ids) = models::page_link::shift(&link_ids, link_id, &form.direction) {
+        models::page_link::reorder(&mut conn, expanded_page.page.id, &link_ids).map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+    }
+
+    log::info!("Moved PageLink");
+    Ok((context, expanded_user, expanded_page))
+}
+
+async fn edit_link(
+    id: i32,
+    link_id: i32,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    form: models::page_link::EditPageLinkApi,
+) -> Result<
+    (
+        Context,
+        models::user::ExpandedUser,
+        models::page::ExpandedPage,
+    ),
+    warp::Rejection,
+> {
+    if form.notes.chars().count() > MAX_NOTES_LENGTH {
+        return Err(reject::custom(MalformedForm(format!(
+            "Notes can be up to {} characters",
+            MAX_NOTES_LENGTH
+        ))));
+    }
+    let (context, expanded_user, expanded_page) =
+        with_authenticated_page(id, context, expanded_user).await?;
+
+    log::info!("Updating PageLink");
+    let mut conn = context.db_conn.get_conn();
+    let updated = models::page_link::update_by_page_id_and_link_id(
+        &mut conn,
+        expanded_page.page.id,
+        link_id,
+        form,
+    )
+    .map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+    if updated == 0 {
+        return Err(reject::custom(NotFound));
+    }
+
+    log::info!("Updated PageLink");
     Ok((context, expanded_user, expanded_page))
 }
+
+async fn update_links_in_bulk(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    expanded_page: models::page::ExpandedPage,
+    form: Vec<(String, String)>,
+) -> Result<
+    (
+        Context,
+        models::user::ExpandedUser,
+        models::page::ExpandedPage,
+        Vec<models::page_link::BulkLinkResult>,
+    ),
+    warp::Rejection,
+> {
+    let bulk = models::page_link::BulkLinksApi::from_form(form).ok_or_else(|| {
+        reject::custom(MalformedForm(String::from(
+            "Pick some links, an action and, unless removing, a group",
+        )))
+    })?;
+
+    let mut conn = context.db_conn.get_conn();
+    // only ever into another of the user's own groups
+    if let Some(target_page_id) = bulk.target_page_id {
+        if target_page_id == expanded_page.page.id {
+            return Err(reject::custom(MalformedForm(String::from(
+                "Pick a different group",
+            ))));
+        }
+        models::page::read_by_id_and_user_id(&mut conn, target_page_id, expanded_user.user.id)
+            .map_err(|_| reject::custom(NotFound))?;
+    }
+
+    log::info!("Updating PageLinks in bulk");
+    let max_links = context.config.quota_for(&expanded_user.user).max_links;
+    let results = models::page_link::bulk_update(
+        &mut conn,
+        expanded_user.user.id,
+        expanded_page.page.id,
+        &bulk,
+        max_links,
+    )
+    .map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    log::info!("Updated PageLinks in bulk");
+    Ok((context, expanded_user, expanded_page, results))
+}
+
+#[tokio::test]
+async fn test_update_page() {
+    let Some(context) = crate::server::test_context() else {
+        return;
+    };
+    let owner = routes::user::insert_test_login(&context, "renamer", "velvet compass tide");
+    let stranger = routes::user::insert_test_login(&context, "onlooker", "amber lichen ferry");
+    let (page, other) = {
+        let mut conn = context.db_conn.get_conn();
+        (
+            models::page::insert_test_page(&mut conn, &owner.user, "Reading"),
+            models::page::insert_test_page(&mut conn, &owner.user, "Recipes"),
+        )
+    };
+    let details = |name: &str| models::page::NewPageApi {
+        name: name.to_string(),
+        description: String::from("For the weekend"),
+        visibility: None,
+    };
+
+    // someone else's page is as good as missing
+    let rejection = with_authenticated_page(page.id, context.clone(), stranger)
+        .await
+        .unwrap_err();
+    assert!(rejection.find::<NotFound>().is_some());
+
+    let (context, owner, expanded_page) = with_authenticated_page(page.id, context, owner)
+        .await
+        .unwrap();
+    let (_, _, renamed) = update_page(
+        context.clone(),
+        owner.clone(),
+        expanded_page,
+        details("Reading Later"),
+    )
+    .await
+    .unwrap();
+    assert_eq!(renamed.page.name, "Reading Later");
+    assert_eq!(renamed.page.slug, "reading-later");
+    assert_eq!(renamed.page.description, "For the weekend");
+    {
+        // the old address still finds the page
+        let mut conn = context.db_conn.get_conn();
+        let moved =
+            models::page_slug::read_page_by_username_and_slug(&mut conn, "renamer", "reading")
+                .unwrap();
+        assert_eq!(moved.id, page.id);
+    }
+
+    // another of the owner's pages already has the name
+    let rejection = update_page(
+        context.clone(),
+        owner.clone(),
+        renamed.clone(),
+        details("Recipes"),
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(
+        rejection.find::<ResourceError>(),
+        Some(ResourceError::Duplicate(_))
+    ));
+    let (context, owner, unchanged) = with_authenticated_page(page.id, context, owner)
+        .await
+        .unwrap();
+    assert_eq!(unchanged.page.name, "Reading Later");
+
+    // a different name on a slug that's taken gets a free one next to it
+    let (_, _, renamed) = update_page(
+        context.clone(),
+        owner.clone(),
+        unchanged,
+        details("recipes"),
+    )
+    .await
+    .unwrap();
+    assert_eq!(renamed.page.name, "recipes");
+    assert_eq!(renamed.page.slug, "recipes-2");
+    assert_eq!(other.slug, "recipes");
+}
@@ -0,0 +1,306 @@
+use crate::{
+    models::{self, passkey::PasskeyChallenge},
+    server::Context,
+    utils::{generate_token, hash_token},
+    NotAuthorized, NotFound,
+};
+use diesel::PgConnection;
+use warp::{
+    filters::{self, BoxedFilter},
+    reject, Filter,
+};
+use webauthn_rs::prelude as webauthn;
+
+use super::user::{authenticate_csrf, client_info, with_new_session};
+
+fn path_prefix() -> BoxedFilter<()> {
+    warp::path("passkey").boxed()
+}
+
+pub fn register_start() -> BoxedFilter<(webauthn::CreationChallengeResponse, String)> {
+    path_prefix()
+        .and(warp::path("register"))
+        .and(warp::path("start"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authenticate_csrf())
+        .and_then(start_registration)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn register_finish() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    path_prefix()
+        .and(warp::path("register"))
+        .and(warp::path("finish"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authenticate_csrf())
+        .and(warp::cookie::<String>("passkey_challenge"))
+        .and(warp::body::json::<models::passkey::NewPasskeyApi>())
+        .and_then(finish_registration)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn login_start() -> BoxedFilter<(webauthn::RequestChallengeResponse, String)> {
+    path_prefix()
+        .and(warp::path("login"))
+        .and(warp::path("start"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(filters::ext::get::<Context>())
+        .and(warp::body::form::<models::passkey::PasskeyLoginApi>())
+        .and_then(start_authentication)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn login_finish() -> BoxedFilter<(Context, models::user::ExpandedUser, String)> {
+    path_prefix()
+        .and(warp::path("login"))
+        .and(warp::path("finish"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(filters::ext::get::<Context>())
+        .and(warp::cookie::<String>("passkey_challenge"))
+        .and(warp::body::json::<webauthn::PublicKeyCredential>())
+        .and_then(finish_authentication)
+        .untuple_one()
+        .and(client_info())
+        .and_then(with_new_session)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn delete() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    warp::delete()
+        .and(path_prefix())
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(authenticate_csrf())
+        .and_then(remove_passkey)
+        .untuple_one()
+        .boxed()
+}
+
+fn get_webauthn(context: &Context) -> Result<webauthn::Webauthn, warp::Rejection> {
+    models::passkey::webauthn(&context.config).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })
+}
+
+fn get_credentials(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<Vec<(models::passkey::Passkey, webauthn::Passkey)>, warp::Rejection> {
+    let passkeys = models::passkey::read_by_user_id(conn, user_id).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    passkeys
+        .into_iter()
+        .map(|passkey| {
+            let credential = passkey.credential().map_err(|e| {
+                log::error!("{:?}", e);
+                warp::reject()
+            })?;
+            Ok((passkey, credential))
+        })
+        .collect()
+}
+
+fn insert_challenge<T: serde::Serialize>(
+    conn: &mut PgConnection,
+    user_id: i32,
+    state: &T,
+) -> Result<String, warp::Rejection> {
+    let token = generate_token();
+
+    models::passkey::NewPasskeyChallenge::new(user_id, hash_token(&token), state)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?
+        .insert(conn)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    Ok(token)
+}
+
+fn take_challenge(
+    conn: &mut PgConnection,
+    token: &str,
+) -> Result<PasskeyChallenge, warp::Rejection> {
+    let challenge = models::passkey::read_challenge_by_token_hash(conn, &hash_token(token))
+        .map_err(|_| reject::custom(NotAuthorized))?;
+
+    models::passkey::delete_challenge(conn, &challenge).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    Ok(challenge)
+}
+
+async fn start_registration(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<(webauthn::CreationChallengeResponse, String), warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+    let webauthn = get_webauthn(&context)?;
+
+    let existing = get_credentials(&mut conn, expanded_user.user.id)?
+        .into_iter()
+        .map(|(_, credential)| credential)
+        .collect::<Vec<webauthn::Passkey>>();
+
+    let (creation, registration) =
+        models::passkey::start_registration(&webauthn, &expanded_user.user, &existing).map_err(
+            |e| {
+                log::error!("{:?}", e);
+                warp::reject()
+            },
+        )?;
+
+    let token = insert_challenge(&mut conn, expanded_user.user.id, &registration)?;
+
+    Ok((creation, token))
+}
+
+async fn finish_registration(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    token: String,
+    new_passkey: models::passkey::NewPasskeyApi,
+) -> Result<(Context, models::user::ExpandedUser), warp::Rejection> {
+    log::info!("Saving Passkey");
+    let mut conn = context.db_conn.get_conn();
+    let webauthn = get_webauthn(&context)?;
+
+    let challenge = take_challenge(&mut conn, &token)?;
+    if challenge.user_id != expanded_user.user.id {
+        return Err(reject::custom(NotAuthorized));
+    }
+
+    let registration = challenge
+        .state::<webauthn::PasskeyRegistration>()
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    let credential = webauthn
+        .finish_passkey_registration(&new_passkey.credential, &registration)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            reject::custom(NotAuthorized)
+        })?;
+
+    models::passkey::NewPasskey::new(expanded_user.user.id, new_passkey.name, &credential)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?
+        .insert(&mut conn)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    log::info!("Saved Passkey");
+    Ok((context, expanded_user))
+}
+
+async fn start_authentication(
+    context: Context,
+    login: models::passkey::PasskeyLoginApi,
+) -> Result<(webauthn::RequestChallengeResponse, String), warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+    let webauthn = get_webauthn(&context)?;
+
+    log::info!("Looking for passkeys of {}", login.username);
+    let user = models::user::read_by_username(&mut conn, &login.username)
+        .map_err(|_| reject::custom(NotFound))?;
+
+    let credentials = get_credentials(&mut conn, user.id)?
+        .into_iter()
+        .map(|(_, credential)| credential)
+        .collect::<Vec<webauthn::Passkey>>();
+
+    if credentials.is_empty() {
+        return Err(reject::custom(NotFound));
+    }
+
+    let (request, authentication) = webauthn
+        .start_passkey_authentication(&credentials)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    let token = insert_challenge(&mut conn, user.id, &authentication)?;
+
+    Ok((request, token))
+}
+
+async fn finish_authentication(
+    context: Context,
+    token: String,
+    assertion: webauthn::PublicKeyCredential,
+) -> Result<(Context, models::user::User, models::background::Background), warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+    let webauthn = get_webauthn(&context)?;
+
+    let challenge = take_challenge(&mut conn, &token)?;
+    let authentication = challenge
+        .state::<webauthn::PasskeyAuthentication>()
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    let result = webauthn
+        .finish_passkey_authentication(&assertion, &authentication)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            reject::custom(NotAuthorized)
+        })?;
+
+    // persist the new signature counter on whichever passkey answered
+    for (passkey, mut credential) in get_credentials(&mut conn, challenge.user_id)? {
+        if credential.update_credential(&result).is_some() {
+            models::passkey::update_credential(&mut conn, &passkey, &credential).map_err(|e| {
+                log::error!("{:?}", e);
+                warp::reject()
+            })?;
+        }
+    }
+
+    let (user, background) = models::user::read_with_background_by_id(&mut conn, challenge.user_id)
+        .map_err(|_| reject::custom(NotFound))?;
+
+    log::info!("Recognized user {} by passkey", user.username);
+    Ok((context, user, background))
+}
+
+async fn remove_passkey(
+    id: i32,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<(Context, models::user::ExpandedUser), warp::Rejection> {
+    log::info!("Removing Passkey");
+    let mut conn = context.db_conn.get_conn();
+
+    let passkey = models::passkey::read_by_id_and_user_id(&mut conn, id, expanded_user.user.id)
+        .map_err(|_| reject::custom(NotFound))?;
+    models::passkey::delete(&mut conn, &passkey).map_err(|_| reject::custom(NotFound))?;
+
+    log::info!("Removed Passkey");
+    Ok((context, expanded_user))
+}
@@ -0,0 +1,125 @@
+use crate::{models, server::Context, NotFound};
+use warp::{filters::BoxedFilter, reject, Filter};
+
+use super::user::{authenticate, authenticate_csrf};
+
+fn path_prefix() -> BoxedFilter<()> {
+    warp::path("sessions").boxed()
+}
+
+pub fn list() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    path_prefix()
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(authenticate())
+        .boxed()
+}
+
+pub fn delete() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    warp::delete()
+        .and(path_prefix())
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(authenticate_csrf())
+        .and_then(revoke_session)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn delete_all() -> BoxedFilter<()> {
+    warp::delete()
+        .and(path_prefix())
+        .and(warp::path::end())
+        .and(authenticate_csrf())
+        .and_then(revoke_all_sessions)
+        .untuple_one()
+        .boxed()
+}
+
+async fn revoke_session(
+    id: i32,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<(Context, models::user::ExpandedUser), warp::Rejection> {
+    log::info!("Revoking Session");
+    let mut conn = context.db_conn.get_conn();
+
+    let session = models::session::read_by_id_and_user_id(&mut conn, id, expanded_user.user.id)
+        .map_err(|_| reject::custom(NotFound))?;
+    models::session::delete(&mut conn, &session).map_err(|_| reject::custom(NotFound))?;
+
+    log::info!("Revoked Session");
+    Ok((context, expanded_user))
+}
+
+async fn revoke_all_sessions(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<(), warp::Rejection> {
+    log::info!("Revoking all Sessions");
+    let mut conn = context.db_conn.get_conn();
+
+    models::session::delete_by_user_id(&mut conn, expanded_user.user.id).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    log::info!("Revoked all Sessions");
+    Ok(())
+}
+
+#[test]
+fn test_revoked_sessions_stop_resolving() {
+    use chrono::Duration;
+    use diesel::Connection;
+
+    let Some(mut conn) = crate::db_conn::test_connection() else {
+        return;
+    };
+    conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+        let user = models::user::insert_test_user(conn, "devices", "password");
+        let other = models::user::insert_test_user(conn, "stranger", "password");
+        let (idle, lifetime) = (Duration::minutes(30), Duration::hours(8));
+        let current = models::session::insert_test_session(conn, user.id, idle, lifetime);
+        let laptop = models::session::insert_test_session(conn, user.id, idle, lifetime);
+        let phone = models::session::insert_test_session(conn, user.id, idle, lifetime);
+        let tablet = models::session::insert_test_session(conn, user.id, idle, lifetime);
+
+        // what the cookie filter looks up before letting a request through
+        let resolves = |conn: &mut diesel::PgConnection, session: &models::session::Session| {
+            models::user::read_user_by_session(conn, &session.token_hash)
+                .map(|expanded_user| !expanded_user.session.is_expired())
+                .unwrap_or(false)
+        };
+
+        // another account can't find the session, let alone revoke it
+        assert!(models::session::read_by_id_and_user_id(conn, laptop.id, other.id).is_err());
+
+        let session = models::session::read_by_id_and_user_id(conn, laptop.id, user.id)?;
+        models::session::delete(conn, &session)?;
+        assert!(!resolves(conn, &laptop));
+        assert!(resolves(conn, &current));
+        assert!(resolves(conn, &phone));
+        assert!(models::session::read_by_id_and_user_id(conn, laptop.id, user.id).is_err());
+
+        assert_eq!(
+            models::session::delete_other_by_user_id(conn, user.id, current.id)?,
+            2
+        );
+        assert!(!resolves(conn, &phone));
+        assert!(!resolves(conn, &tablet));
+        assert!(resolves(conn, &current));
+        assert_eq!(
+            models::session::read_active_by_user_id(conn, user.id)?
+                .iter()
+                .map(|session| session.id)
+                .collect::<Vec<_>>(),
+            vec![current.id]
+        );
+
+        // logging out everywhere takes the current device with it
+        models::session::delete_by_user_id(conn, user.id)?;
+        assert!(!resolves(conn, &current));
+        Ok(())
+    });
+}
@@ -0,0 +1,206 @@
+use crate::{models, server::Context, NotFound};
+use diesel::{
+    result::{DatabaseErrorKind, Error::DatabaseError},
+    Connection,
+};
+use warp::{filters::BoxedFilter, reject, Filter};
+
+use super::user::{authenticate, authenticate_csrf};
+
+fn path_prefix() -> BoxedFilter<()> {
+    warp::path("trash").boxed()
+}
+
+// where the undo toast sends people after a delete
+pub fn restore_page_path(id: i32) -> String {
+    format!("/user/trash/pages/{}/restore", id)
+}
+
+pub fn restore_link_path(id: i32) -> String {
+    format!("/user/trash/links/{}/restore", id)
+}
+
+pub fn list() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    path_prefix()
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(authenticate())
+        .boxed()
+}
+
+pub fn restore_page() -> BoxedFilter<(Context, models::user::ExpandedUser, String)> {
+    path_prefix()
+        .and(warp::path("pages"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("restore"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authenticate_csrf())
+        .and_then(undelete_page)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn purge_page() -> BoxedFilter<(Context, models::user::ExpandedUser, String)> {
+    warp::delete()
+        .and(path_prefix())
+        .and(warp::path("pages"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(authenticate_csrf())
+        .and_then(destroy_page)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn restore_link() -> BoxedFilter<(Context, models::user::ExpandedUser, String)> {
+    path_prefix()
+        .and(warp::path("links"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("restore"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authenticate_csrf())
+        .and_then(undelete_link)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn purge_link() -> BoxedFilter<(Context, models::user::ExpandedUser, String)> {
+    warp::delete()
+        .and(path_prefix())
+        .and(warp::path("links"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(authenticate_csrf())
+        .and_then(destroy_link)
+        .untuple_one()
+        .boxed()
+}
+
+// a restore that can't happen is reported on the trash page rather than rejected
+async fn undelete_page(
+    id: i32,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<(Context, models::user::ExpandedUser, String), warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+    let page = models::page::read_trash_by_id_and_user_id(&mut conn, id, expanded_user.user.id)
+        .map_err(|_| reject::custom(NotFound))?;
+
+    // the user row is locked while counting, so parallel restores can't all slip under the limit
+    let max_pages = context.config.quota_for(&expanded_user.user).max_pages;
+    let restored = conn
+        .transaction(|conn| {
+            models::user::lock_by_id(conn, expanded_user.user.id)?;
+            if models::page::get_count_of_pages_per_user(conn, expanded_user.user.id)? >= max_pages
+            {
+                return Ok(None);
+            }
+
+            let taken_slugs = models::page::read_slugs_by_user_id(conn, expanded_user.user.id)?;
+            let slug = models::page::unique_slug(&page.slug, &taken_slugs);
+            let taken = models::page::read_names_by_user_id(conn, expanded_user.user.id)?;
+            let name = models::page::restored_name(&page.name, &taken);
+            models::page::restore(conn, &page, &name, &slug).map(Some)
+        })
+        .map_err(|e: diesel::result::Error| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+    let Some(restored) = restored else {
+        return Ok((
+            context,
+            expanded_user,
+            format!("Error: You cannot have more than {} groups", max_pages),
+        ));
+    };
+
+    log::info!("Restored Page {}", restored.id);
+    let message = if restored.name == page.name {
+        format!("Restored {}", restored.name)
+    } else {
+        format!("Restored {} as {}", page.name, restored.name)
+    };
+    Ok((context, expanded_user, message))
+}
+
+async fn destroy_page(
+    id: i32,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<(Context, models::user::ExpandedUser, String), warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+    let page = models::page::read_trash_by_id_and_user_id(&mut conn, id, expanded_user.user.id)
+        .map_err(|_| reject::custom(NotFound))?;
+    models::page::purge(&mut conn, &page).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    log::info!("Purged Page {}", page.id);
+    Ok((
+        context,
+        expanded_user,
+        format!("Deleted {} for good", page.name),
+    ))
+}
+
+async fn undelete_link(
+    id: i32,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<(Context, models::user::ExpandedUser, String), warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+    let page_link =
+        models::page_link::read_trash_by_id_and_user_id(&mut conn, id, expanded_user.user.id)
+            .map_err(|_| reject::custom(NotFound))?;
+
+    // the owner's row is locked while counting, so parallel restores can't all slip under the limit
+    let max_links = context.config.quota_for(&expanded_user.user).max_links;
+    let restored = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        models::user::lock_by_id(conn, expanded_user.user.id)?;
+        if models::page_link::get_count_of_links_per_page(conn, page_link.page_id)? >= max_links {
+            return Ok(None);
+        }
+
+        let position = models::page_link::next_position(conn, page_link.page_id)?;
+        models::page_link::restore(conn, &page_link, position).map(Some)
+    });
+    let message = match restored {
+        Ok(Some(_)) => format!("Restored {}", page_link.name),
+        Ok(None) => format!("Error: A group can hold at most {} links", max_links),
+        Err(DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => format!(
+            "Error: {} has been added to that group again since",
+            page_link.name
+        ),
+        Err(e) => {
+            log::error!("{:?}", e);
+            return Err(warp::reject());
+        }
+    };
+
+    Ok((context, expanded_user, message))
+}
+
+async fn destroy_link(
+    id: i32,
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+) -> Result<(Context, models::user::ExpandedUser, String), warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+    let page_link =
+        models::page_link::read_trash_by_id_and_user_id(&mut conn, id, expanded_user.user.id)
+            .map_err(|_| reject::custom(NotFound))?;
+    models::page_link::purge(&mut conn, &page_link).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    log::info!("Purged PageLink {}", page_link.id);
+    Ok((
+        context,
+        expanded_user,
+        format!("Deleted {} for good", page_link.name),
+    ))
+}
@@ -1,15 +1,29 @@
 use crate::{
-    models::{self, user::ExpandedUser},
-    server::Context,
-    utils::now,
-    NotAuthorized, NotFound, OldCookie, ResourceError, ResourceErrorData,
+    config::{Config, SignupMode},
+    mailer,
+    models::{self, login_attempt::ThrottleRules, session::ClientInfo, user::ExpandedUser},
+    routes,
+    server::{Context, PeerAddr},
+    utils::{client_ip, constant_time_eq, generate_token, hash_token, now},
+    validation, CredentialError, CsrfMismatch, InvalidPasswordReset, InvalidTwoFactorCode,
+    LoginThrottled, MalformedForm, NotAuthorized, NotFound, OldCookie, ResourceError,
+    ResourceErrorData, SignupClosed, TwoFactorRequired, ValidationError, PAGE_SIZE,
 };
-use diesel::result::{DatabaseErrorKind, Error::DatabaseError};
+use bytes::Bytes;
+use chrono::Duration;
+use diesel::{
+    result::{DatabaseErrorKind, Error::DatabaseError},
+    Connection, PgConnection,
+};
+use serde::{de::DeserializeOwned, Deserialize};
 use warp::{
     filters::{self, BoxedFilter},
     reject, Filter,
 };
 
+// none of our forms come close to this
+const MAX_FORM_BYTES: u64 = 16 * 1024;
+
 pub fn logout() -> BoxedFilter<()> {
     warp::path("logout")
         .and(warp::path::end())
@@ -20,7 +34,7 @@ pub fn logout() -> BoxedFilter<()> {
         .boxed()
 }
 
-pub fn signup() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+pub fn signup() -> BoxedFilter<(Context, models::user::ExpandedUser, String)> {
     warp::path("signup")
         .and(warp::path::end())
         .and(warp::post())
@@ -28,20 +42,40 @@ pub fn signup() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
         .and(warp::body::form::<models::user::NewUserApi>())
         .and_then(insert_new_user)
         .untuple_one()
+        .and(client_info())
         .and_then(with_new_session)
         .untuple_one()
         .boxed()
 }
 
-pub fn login() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+pub fn login() -> BoxedFilter<(Context, models::user::ExpandedUser, String)> {
     warp::path("login")
         .and(warp::path::end())
         .and(warp::post())
         .and(filters::ext::get::<Context>())
         .and(warp::body::form::<models::user::UserCredentialsApi>())
+        .and(client_info())
         .and_then(with_user_by_credentials)
         .untuple_one()
-        .and_then(with_new_session)
+        .and_then(with_second_factor_check)
+        .untuple_one()
+        .and_then(with_new_remembered_session)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn login_verify() -> BoxedFilter<(Context, models::user::ExpandedUser, String)> {
+    warp::path("login")
+        .and(warp::path("verify"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(filters::ext::get::<Context>())
+        .and(warp::cookie::<String>("pending_login"))
+        .and(warp::body::form::<models::user::TwoFactorCodeApi>())
+        .and(client_info())
+        .and_then(with_user_by_second_factor)
+        .untuple_one()
+        .and_then(with_new_remembered_session)
         .untuple_one()
         .boxed()
 }
@@ -49,54 +83,1078 @@ pub fn login() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
 pub fn get_by_cookie() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
     warp::path::end()
         .and(warp::get())
-        .and(authenticate_cookie())
+        .and(authenticate())
+        .boxed()
+}
+
+// the next screen of the profile's groups, for htmx to append
+pub fn pages() -> BoxedFilter<(
+    Context,
+    models::user::ExpandedUser,
+    Vec<models::page::Page>,
+    Option<i32>,
+)> {
+    warp::path("pages")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<models::page::CursorQuery>())
+        .and(authenticate())
+        .and_then(with_more_pages)
+        .untuple_one()
+        .boxed()
+}
+
+// the next screen of groups for a picker, as options to append to its select
+pub fn page_options() -> BoxedFilter<(
+    Context,
+    models::user::ExpandedUser,
+    Vec<models::page::Page>,
+    Option<i32>,
+)> {
+    warp::path("pages")
+        .and(warp::path("options"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<models::page::CursorQuery>())
+        .and(authenticate())
+        .and_then(with_more_pages)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn settings() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    warp::path("settings")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(authenticate())
+        .boxed()
+}
+
+pub fn change_password() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    warp::path("settings")
+        .and(warp::path("password"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authenticate_csrf_form::<models::user::ChangePasswordApi>())
+        .and_then(update_password)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn change_username() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    warp::path("settings")
+        .and(warp::path("username"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authenticate_csrf_form::<models::user::ChangeUsernameApi>())
+        .and_then(update_username)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn change_email() -> BoxedFilter<(Context, models::user::ExpandedUser, Option<String>)> {
+    warp::path("settings")
+        .and(warp::path("email"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authenticate_csrf_form::<models::user::ChangeEmailApi>())
+        .and_then(update_email)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn verify_email() -> BoxedFilter<()> {
+    warp::path("settings")
+        .and(warp::path("email"))
+        .and(warp::path("verify"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(filters::ext::get::<Context>())
+        .and(warp::query::<models::user::VerifyEmailQuery>())
+        .and_then(confirm_email)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn delete_account() -> BoxedFilter<()> {
+    warp::path("settings")
+        .and(warp::path("delete"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authenticate_csrf_form::<models::user::DeleteAccountApi>())
+        .and_then(remove_account)
+        .untuple_one()
         .boxed()
 }
 
+pub fn two_factor_settings() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    warp::path("settings")
+        .and(warp::path("two-factor"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(authenticate())
+        .boxed()
+}
+
+pub fn two_factor_setup() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    warp::path("settings")
+        .and(warp::path("two-factor"))
+        .and(warp::path("setup"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authenticate_csrf_form::<models::user::TwoFactorSetupApi>())
+        .and_then(start_two_factor_setup)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn two_factor_enable() -> BoxedFilter<(Context, models::user::ExpandedUser, Vec<String>)> {
+    warp::path("settings")
+        .and(warp::path("two-factor"))
+        .and(warp::path("enable"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authenticate_csrf_form::<models::user::TwoFactorCodeApi>())
+        .and_then(confirm_two_factor_setup)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn two_factor_disable() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    warp::path("settings")
+        .and(warp::path("two-factor"))
+        .and(warp::path("disable"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authenticate_csrf_form::<models::user::ConfirmPasswordApi>())
+        .and_then(remove_two_factor)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn regenerate_recovery_codes() -> BoxedFilter<(Context, models::user::ExpandedUser, Vec<String>)>
+{
+    warp::path("settings")
+        .and(warp::path("two-factor"))
+        .and(warp::path("recovery-codes"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(authenticate_csrf_form::<models::user::ConfirmPasswordApi>())
+        .and_then(replace_recovery_codes)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn forgot_password_form() -> BoxedFilter<()> {
+    warp::path("forgot")
+        .and(warp::path::end())
+        .and(warp::get())
+        .boxed()
+}
+
+pub fn forgot_password() -> BoxedFilter<()> {
+    warp::path("forgot")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(filters::ext::get::<Context>())
+        .and(warp::body::form::<models::user::ForgotPasswordApi>())
+        .and_then(send_password_reset)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn reset_password_form() -> BoxedFilter<(models::user::ResetTokenQuery,)> {
+    warp::path("reset")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<models::user::ResetTokenQuery>())
+        .boxed()
+}
+
+pub fn reset_password() -> BoxedFilter<()> {
+    warp::path("reset")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(filters::ext::get::<Context>())
+        .and(warp::body::form::<models::user::ResetPasswordApi>())
+        .and_then(redeem_password_reset)
+        .untuple_one()
+        .boxed()
+}
+
+async fn with_more_pages(
+    query: models::page::CursorQuery,
+    context: Context,
+    expanded_user: ExpandedUser,
+) -> Result<(Context, ExpandedUser, Vec<models::page::Page>, Option<i32>), warp::Rejection> {
+    let after = routes::page::parse_page_cursor(&query)?;
+    let mut conn = context.db_conn.get_conn();
+    let (pages, cursor) = models::page::read_pages_by_user_id_after(
+        &mut conn,
+        expanded_user.user.id,
+        after,
+        PAGE_SIZE,
+    )
+    .map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    Ok((context, expanded_user, pages, cursor))
+}
+
 async fn with_user_by_credentials(
     context: Context,
     credentials: models::user::UserCredentialsApi,
-) -> Result<(Context, models::user::User, models::background::Background), warp::Rejection> {
+    client: ClientInfo,
+) -> Result<
+    (
+        Context,
+        models::user::User,
+        models::background::Background,
+        bool,
+        ClientInfo,
+    ),
+    warp::Rejection,
+> {
     let mut conn = context.db_conn.get_conn();
     log::info!("Looking for user {}", credentials.username);
-    let (user, background) = models::user::read_by_credentials(&mut conn, credentials)
-        .map_err(|_| reject::custom(NotFound))?;
-    Ok((context, user, background))
+    let username = credentials.username.clone();
+    check_login_throttle(&context, &mut conn, &username, &client)?;
+
+    let remember = credentials.remember_me.is_some();
+    let params = &context.config.password_params;
+    match models::user::read_by_credentials(&mut conn, credentials, params) {
+        Ok((user, background)) => {
+            // with a second factor still to come, the slate is wiped once that passes too
+            if !user.has_two_factor() {
+                models::login_attempt::delete_by_username(&mut conn, &username).map_err(|e| {
+                    log::error!("{:?}", e);
+                    warp::reject()
+                })?;
+            }
+            Ok((context, user, background, remember, client))
+        }
+        Err(_) => {
+            record_failed_login(&context, &mut conn, &username, &client);
+            Err(reject::custom(NotFound))
+        }
+    }
+}
+
+// users with a second factor don't get a session yet, only a short lived pending login
+async fn with_second_factor_check(
+    context: Context,
+    user: models::user::User,
+    background: models::background::Background,
+    remember: bool,
+    client: ClientInfo,
+) -> Result<
+    (
+        Context,
+        models::user::User,
+        models::background::Background,
+        bool,
+        ClientInfo,
+    ),
+    warp::Rejection,
+> {
+    if !user.has_two_factor() {
+        return Ok((context, user, background, remember, client));
+    }
+
+    let mut conn = context.db_conn.get_conn();
+    let token = generate_token();
+    models::pending_login::NewPendingLogin::new(user.id, hash_token(&token), remember)
+        .insert(&mut conn)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    log::info!("Waiting on second factor for {}", user.username);
+    Err(reject::custom(TwoFactorRequired(token)))
+}
+
+async fn with_user_by_second_factor(
+    context: Context,
+    token: String,
+    form: models::user::TwoFactorCodeApi,
+    client: ClientInfo,
+) -> Result<
+    (
+        Context,
+        models::user::User,
+        models::background::Background,
+        bool,
+        ClientInfo,
+    ),
+    warp::Rejection,
+> {
+    let mut conn = context.db_conn.get_conn();
+    let pending_login =
+        models::pending_login::read_valid_by_token_hash(&mut conn, &hash_token(&token))
+            .map_err(|_| reject::custom(NotFound))?;
+    let (user, background) =
+        models::user::read_with_background_by_id(&mut conn, pending_login.user_id)
+            .map_err(|_| reject::custom(NotFound))?;
+    // guessing codes counts against the same limit as guessing passwords
+    check_login_throttle(&context, &mut conn, &user.username, &client)?;
+
+    let code_verified = models::user::verify_totp(&mut conn, &user, &form.code).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+    let verified = if code_verified {
+        true
+    } else {
+        let redeemed =
+            models::recovery_code::redeem(&mut conn, user.id, &form.code).map_err(|e| {
+                log::error!("{:?}", e);
+                warp::reject()
+            })?;
+        if redeemed {
+            models::audit_log::record(
+                &mut conn,
+                Some(user.id),
+                models::audit_log::RECOVERY_CODE_USED,
+                format!("{} logged in with a recovery code", user.username),
+                client.ip.clone(),
+            );
+        }
+        redeemed
+    };
+
+    if !verified {
+        log::info!("Wrong second factor for {}", user.username);
+        record_failed_login(&context, &mut conn, &user.username, &client);
+        models::pending_login::increment_attempts(&mut conn, &pending_login).map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+        return Err(reject::custom(InvalidTwoFactorCode(ResourceErrorData {
+            context: Some(context),
+            expanded_user: None,
+            expanded_page: None,
+        })));
+    }
+
+    models::pending_login::delete(&mut conn, &pending_login).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+    models::login_attempt::delete_by_username(&mut conn, &user.username).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    Ok((context, user, background, pending_login.remember, client))
+}
+
+fn login_throttle_rules(config: &Config) -> (ThrottleRules, ThrottleRules) {
+    let backoff = Duration::seconds(config.login_backoff_seconds);
+    let lockout = Duration::minutes(config.login_lockout_minutes);
+    (
+        ThrottleRules {
+            max_attempts: config.login_max_attempts,
+            backoff,
+            lockout,
+        },
+        ThrottleRules {
+            max_attempts: config.login_max_attempts_per_ip,
+            backoff,
+            lockout,
+        },
+    )
+}
+
+// failures are tracked against both the username and the address they came from
+fn check_login_throttle(
+    context: &Context,
+    conn: &mut PgConnection,
+    username: &str,
+    client: &ClientInfo,
+) -> Result<(), warp::Rejection> {
+    let (username_rules, ip_rules) = login_throttle_rules(&context.config);
+    let since = now() - Duration::minutes(context.config.login_window_minutes);
+
+    let failures =
+        models::login_attempt::read_recent_by_username(conn, username, since).map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+    let mut wait = models::login_attempt::retry_after(&failures, username_rules, now());
+
+    if let Some(ip) = &client.ip {
+        let failures = models::login_attempt::read_recent_by_ip(conn, ip, since).map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+        wait = wait.max(models::login_attempt::retry_after(
+            &failures,
+            ip_rules,
+            now(),
+        ));
+    }
+
+    match wait {
+        Some(wait) => {
+            log::info!("Throttling login of {} for {}", username, wait);
+            Err(reject::custom(LoginThrottled(wait.num_seconds().max(1))))
+        }
+        None => Ok(()),
+    }
+}
+
+fn record_failed_login(
+    context: &Context,
+    conn: &mut PgConnection,
+    username: &str,
+    client: &ClientInfo,
+) {
+    let config = &context.config;
+    if let Err(e) =
+        models::login_attempt::NewLoginAttempt::new(username, client.ip.clone()).insert(conn)
+    {
+        log::error!("{:?}", e);
+        return;
+    }
+
+    let since = now() - Duration::minutes(config.login_window_minutes);
+    let user_id = models::user::read_by_username(conn, username)
+        .ok()
+        .map(|user| user.id);
+
+    // only the failure that crosses the threshold gets written down
+    if let Ok(failures) = models::login_attempt::read_recent_by_username(conn, username, since)
+        && failures.len() == config.login_max_attempts
+    {
+        models::audit_log::record(
+            conn,
+            user_id,
+            models::audit_log::LOGIN_LOCKOUT,
+            format!(
+                "Locked out username {} for {} minutes after {} failed logins",
+                username, config.login_lockout_minutes, config.login_max_attempts
+            ),
+            client.ip.clone(),
+        );
+    }
+
+    if let Some(ip) = &client.ip
+        && let Ok(failures) = models::login_attempt::read_recent_by_ip(conn, ip, since)
+        && failures.len() == config.login_max_attempts_per_ip
+    {
+        models::audit_log::record(
+            conn,
+            user_id,
+            models::audit_log::LOGIN_LOCKOUT,
+            format!(
+                "Locked out address {} for {} minutes after {} failed logins",
+                ip, config.login_lockout_minutes, config.login_max_attempts_per_ip
+            ),
+            client.ip.clone(),
+        );
+    }
 }
 
 async fn insert_new_user(
     context: Context,
     new_user: models::user::NewUserApi,
 ) -> Result<(Context, models::user::User, models::background::Background), warp::Rejection> {
+    let invite_code = match context.config.signup_mode {
+        SignupMode::Open => None,
+        SignupMode::InviteOnly => new_user
+            .invite
+            .as_deref()
+            .map(str::trim)
+            .filter(|code| !code.is_empty())
+            .map(String::from),
+        SignupMode::Closed => return Err(reject::custom(SignupClosed)),
+    };
+
+    let mut errors = validation::validate_signup(
+        &new_user.username,
+        &new_user.password,
+        &new_user.confirm_password,
+    );
+    if context.config.signup_mode == SignupMode::InviteOnly && invite_code.is_none() {
+        errors.invite = Some(String::from("An invite code is required to sign up"));
+    }
+    if !errors.is_empty() {
+        return Err(signup_error(context, errors));
+    }
+
+    // the use is claimed up front and handed back if the account can't be made
+    let mut conn = context.db_conn.get_conn();
+    let invite = match invite_code {
+        Some(code) => match models::invite::redeem(&mut conn, &code) {
+            Ok(invite) => Some(invite),
+            Err(_) => {
+                let errors = validation::FieldErrors {
+                    invite: Some(String::from(
+                        "That invite code is not valid, has expired or has been used up",
+                    )),
+                    ..Default::default()
+                };
+                return Err(signup_error(context, errors));
+            }
+        },
+        None => None,
+    };
+
     log::info!("Saving User");
+    let email = models::user::clean_email(new_user.email.as_deref());
+    let credentials = new_user.encrypt(&context.config.password_params);
+    let (user, background) = insert_user_with_background(&context, &mut conn, credentials)
+        .inspect_err(|_| {
+            if let Some(invite) = &invite
+                && let Err(e) = models::invite::release(&mut conn, invite)
+            {
+                log::error!("{:?}", e);
+            }
+        })?;
+    log::info!("Saved User");
+
+    // the account is made either way, the address can be confirmed again from settings
+    if let Some(email) = email
+        && let Err(e) = send_email_verification(&context, &mut conn, &user, &email).await
+    {
+        log::error!("{:?}", e);
+    }
+    Ok((context, user, background))
+}
+
+fn signup_error(context: Context, errors: validation::FieldErrors) -> warp::Rejection {
+    reject::custom(ValidationError {
+        data: ResourceErrorData {
+            context: Some(context),
+            expanded_user: None,
+            expanded_page: None,
+        },
+        errors,
+    })
+}
+
+pub fn insert_user_with_background(
+    context: &Context,
+    conn: &mut PgConnection,
+    credentials: models::user::UserCredentialsEncrypted,
+) -> Result<(models::user::User, models::background::Background), warp::Rejection> {
+    models::user::create_with_background(conn, credentials).map_err(|e| new_user_error(context, e))
+}
+
+// someone can take the username between our check and the insert
+pub fn new_user_error(context: &Context, e: diesel::result::Error) -> warp::Rejection {
+    match e {
+        DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            reject::custom(ResourceError::Duplicate(ResourceErrorData {
+                context: Some(context.clone()),
+                expanded_user: None,
+                expanded_page: None,
+            }))
+        }
+        err => {
+            log::error!("{:?}", err);
+            warp::reject()
+        }
+    }
+}
+
+fn credential_error_data(
+    context: &Context,
+    expanded_user: &models::user::ExpandedUser,
+) -> ResourceErrorData {
+    ResourceErrorData {
+        context: Some(context.clone()),
+        expanded_user: Some(expanded_user.clone()),
+        expanded_page: None,
+    }
+}
+
+async fn update_password(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    change: models::user::ChangePasswordApi,
+) -> Result<(Context, models::user::ExpandedUser), warp::Rejection> {
+    if !expanded_user.user.verify_password(&change.current_password) {
+        return Err(reject::custom(CredentialError::WrongPassword(
+            credential_error_data(&context, &expanded_user),
+        )));
+    }
+    let errors = validation::validate_new_password(
+        &change.password,
+        &change.confirm_password,
+        &expanded_user.user.username,
+    );
+    if !errors.is_empty() {
+        return Err(reject::custom(ValidationError {
+            data: credential_error_data(&context, &expanded_user),
+            errors,
+        }));
+    }
+
+    log::info!("Changing password of {}", expanded_user.user.username);
     let mut conn = context.db_conn.get_conn();
-    let background = models::background::random_bg()
-        .insert(&mut conn)
+    let user = models::user::update_password(
+        &mut conn,
+        &expanded_user.user,
+        &change.password,
+        &context.config.password_params,
+    )
+    .map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    // anyone holding an old session no longer knows the password
+    models::session::delete_other_by_user_id(&mut conn, user.id, expanded_user.session.id)
         .map_err(|e| {
             log::error!("{:?}", e);
             warp::reject()
         })?;
 
-    let user = models::user::NewUser::new(new_user.into(), background.id)
-        .insert(&mut conn)
-        .map_err(|e| {
+    Ok((
+        context,
+        ExpandedUser {
+            user,
+            ..expanded_user
+        },
+    ))
+}
+
+async fn update_username(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    change: models::user::ChangeUsernameApi,
+) -> Result<(Context, models::user::ExpandedUser), warp::Rejection> {
+    if let Err(error) = validation::validate_username(&change.username) {
+        return Err(reject::custom(ValidationError {
+            data: credential_error_data(&context, &expanded_user),
+            errors: validation::FieldErrors {
+                username: Some(error),
+                ..Default::default()
+            },
+        }));
+    }
+
+    log::info!(
+        "Renaming {} to {}",
+        expanded_user.user.username,
+        change.username
+    );
+    let mut conn = context.db_conn.get_conn();
+    let user = models::user::update_username(&mut conn, &expanded_user.user, &change.username)
+        .map_err(|e| match e {
+            DatabaseError(DatabaseErrorKind::UniqueViolation, _) => reject::custom(
+                ResourceError::Duplicate(credential_error_data(&context, &expanded_user)),
+            ),
+            err => {
+                log::error!("{:?}", err);
+                warp::reject()
+            }
+        })?;
+
+    Ok((
+        context,
+        ExpandedUser {
+            user,
+            ..expanded_user
+        },
+    ))
+}
+
+// a new address only replaces the old one once its link is followed
+async fn update_email(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    change: models::user::ChangeEmailApi,
+) -> Result<(Context, models::user::ExpandedUser, Option<String>), warp::Rejection> {
+    if !expanded_user.user.verify_password(&change.password) {
+        return Err(reject::custom(CredentialError::WrongPassword(
+            credential_error_data(&context, &expanded_user),
+        )));
+    }
+
+    log::info!("Changing email of {}", expanded_user.user.username);
+    let mut conn = context.db_conn.get_conn();
+    models::email_verification::delete_by_user_id(&mut conn, expanded_user.user.id).map_err(
+        |e| {
             log::error!("{:?}", e);
-            match e {
-                DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
-                    reject::custom(ResourceError::Duplicate(ResourceErrorData {
-                        context: Some(context.clone()),
-                        expanded_user: None,
-                        expanded_page: None,
-                    }))
-                }
-                err => {
-                    log::error!("{:?}", err);
+            warp::reject()
+        },
+    )?;
+
+    let (user, pending) = match models::user::clean_email(Some(&change.email)) {
+        None => {
+            let user =
+                models::user::update_email(&mut conn, &expanded_user.user, None).map_err(|e| {
+                    log::error!("{:?}", e);
                     warp::reject()
-                }
+                })?;
+            (user, None)
+        }
+        Some(email) if expanded_user.user.email.as_ref() == Some(&email) => {
+            (expanded_user.user.clone(), None)
+        }
+        Some(email) => {
+            send_email_verification(&context, &mut conn, &expanded_user.user, &email).await?;
+            (expanded_user.user.clone(), Some(email))
+        }
+    };
+
+    Ok((
+        context,
+        ExpandedUser {
+            user,
+            ..expanded_user
+        },
+        pending,
+    ))
+}
+
+async fn send_email_verification(
+    context: &Context,
+    conn: &mut PgConnection,
+    user: &models::user::User,
+    email: &str,
+) -> Result<(), warp::Rejection> {
+    // the email gets the token, the database only ever sees its hash
+    let token = generate_token();
+    models::email_verification::NewEmailVerification::new(
+        user.id,
+        email.to_string(),
+        hash_token(&token),
+    )
+    .insert(conn)
+    .map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    let link = format!(
+        "{}/user/settings/email/verify?token={}",
+        context.config.app_url, token
+    );
+    let body = format!(
+        "Hi {},\n\nSomeone asked to use this address for your Digitheque account. \
+        If it was you, follow this link within {} hours to confirm it:\n\n{}\n\n\
+        If it wasn't, you can ignore this email.",
+        user.username,
+        models::email_verification::VERIFICATION_DURATION_HOURS,
+        link
+    );
+
+    mailer::send(
+        context.mailer.clone(),
+        email,
+        "Confirm your Digitheque email",
+        &body,
+    )
+    .await
+    .map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    log::info!("Sent email verification to {}", user.username);
+    Ok(())
+}
+
+async fn confirm_email(
+    context: Context,
+    query: models::user::VerifyEmailQuery,
+) -> Result<(), warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+
+    let user = conn
+        .transaction(|conn| {
+            let verification = models::email_verification::redeem(conn, &hash_token(&query.token))?;
+            let user = models::user::read_by_id(conn, verification.user_id)?;
+            // links already mailed to the old address stop working
+            models::password_reset::delete_by_user_id(conn, user.id)?;
+            models::user::update_email(conn, &user, Some(verification.email))
+        })
+        .map_err(|e: diesel::result::Error| match e {
+            diesel::NotFound => reject::custom(NotFound),
+            DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                reject::custom(ResourceError::Duplicate(ResourceErrorData {
+                    context: Some(context.clone()),
+                    expanded_user: None,
+                    expanded_page: None,
+                }))
+            }
+            err => {
+                log::error!("{:?}", err);
+                warp::reject()
             }
         })?;
-    log::info!("Saved User");
-    Ok((context, user, background))
+
+    log::info!("Verified email of {}", user.username);
+    Ok(())
+}
+
+async fn remove_account(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    confirmation: models::user::DeleteAccountApi,
+) -> Result<(), warp::Rejection> {
+    if !expanded_user.user.verify_password(&confirmation.password) {
+        return Err(reject::custom(CredentialError::WrongPassword(
+            credential_error_data(&context, &expanded_user),
+        )));
+    }
+
+    log::info!("Deleting account of {}", expanded_user.user.username);
+    let mut conn = context.db_conn.get_conn();
+    models::user::delete_account(&mut conn, &expanded_user.user).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    Ok(())
+}
+
+// the secret is stored right away but only takes effect once a code from it is confirmed
+async fn start_two_factor_setup(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    _setup: models::user::TwoFactorSetupApi,
+) -> Result<(Context, models::user::ExpandedUser), warp::Rejection> {
+    if expanded_user.user.has_two_factor() {
+        return Err(reject::custom(NotAuthorized));
+    }
+
+    let mut conn = context.db_conn.get_conn();
+    let secret = models::user::generate_totp_secret();
+    let user = models::user::update_totp_secret(&mut conn, &expanded_user.user, Some(secret))
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    Ok((
+        context,
+        ExpandedUser {
+            user,
+            ..expanded_user
+        },
+    ))
+}
+
+async fn confirm_two_factor_setup(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    form: models::user::TwoFactorCodeApi,
+) -> Result<(Context, models::user::ExpandedUser, Vec<String>), warp::Rejection> {
+    if expanded_user.user.has_two_factor() {
+        return Err(reject::custom(NotAuthorized));
+    }
+    let mut conn = context.db_conn.get_conn();
+    let verified =
+        models::user::verify_totp(&mut conn, &expanded_user.user, &form.code).map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+    if !verified {
+        return Err(reject::custom(InvalidTwoFactorCode(credential_error_data(
+            &context,
+            &expanded_user,
+        ))));
+    }
+
+    log::info!(
+        "Enabling two factor login for {}",
+        expanded_user.user.username
+    );
+    let (user, codes) = conn
+        .transaction(|conn| {
+            let user = models::user::enable_totp(conn, &expanded_user.user)?;
+            let codes = models::recovery_code::create_for_user(conn, user.id)?;
+            Ok((user, codes))
+        })
+        .map_err(|e: diesel::result::Error| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    Ok((
+        context,
+        ExpandedUser {
+            user,
+            ..expanded_user
+        },
+        codes,
+    ))
+}
+
+async fn remove_two_factor(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    confirmation: models::user::ConfirmPasswordApi,
+) -> Result<(Context, models::user::ExpandedUser), warp::Rejection> {
+    if !expanded_user.user.verify_password(&confirmation.password) {
+        return Err(reject::custom(CredentialError::WrongPassword(
+            credential_error_data(&context, &expanded_user),
+        )));
+    }
+
+    let mut conn = context.db_conn.get_conn();
+    let user = conn
+        .transaction(|conn| {
+            models::recovery_code::delete_by_user_id(conn, expanded_user.user.id)?;
+            models::pending_login::delete_by_user_id(conn, expanded_user.user.id)?;
+            models::user::update_totp_secret(conn, &expanded_user.user, None)
+        })
+        .map_err(|e: diesel::result::Error| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    models::audit_log::record(
+        &mut conn,
+        Some(user.id),
+        models::audit_log::TWO_FACTOR_DISABLED,
+        format!("{} turned off two factor login", user.username),
+        expanded_user.session.ip.clone(),
+    );
+
+    Ok((
+        context,
+        ExpandedUser {
+            user,
+            ..expanded_user
+        },
+    ))
+}
+
+async fn replace_recovery_codes(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    confirmation: models::user::ConfirmPasswordApi,
+) -> Result<(Context, models::user::ExpandedUser, Vec<String>), warp::Rejection> {
+    if !expanded_user.user.verify_password(&confirmation.password) {
+        return Err(reject::custom(CredentialError::WrongPassword(
+            credential_error_data(&context, &expanded_user),
+        )));
+    }
+    if !expanded_user.user.has_two_factor() {
+        return Err(reject::custom(NotAuthorized));
+    }
+
+    let mut conn = context.db_conn.get_conn();
+    let codes =
+        models::recovery_code::create_for_user(&mut conn, expanded_user.user.id).map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    Ok((context, expanded_user, codes))
+}
+
+// we answer the same way whether or not the account exists
+async fn send_password_reset(
+    context: Context,
+    forgot: models::user::ForgotPasswordApi,
+) -> Result<(), warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+
+    let user = match models::user::read_by_username_or_email(&mut conn, forgot.username.trim()) {
+        Ok(user) => user,
+        Err(_) => {
+            log::info!("No account to reset for {}", forgot.username);
+            return Ok(());
+        }
+    };
+    let email = match &user.email {
+        Some(email) => email.clone(),
+        None => {
+            log::info!("User {} has no email to reset with", user.username);
+            return Ok(());
+        }
+    };
+
+    models::password_reset::delete_by_user_id(&mut conn, user.id).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    // the email gets the token, the database only ever sees its hash
+    let token = generate_token();
+    models::password_reset::NewPasswordReset::new(user.id, hash_token(&token))
+        .insert(&mut conn)
+        .map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    let link = format!("{}/user/reset?token={}", context.config.app_url, token);
+    let body = format!(
+        "Hi {},\n\nSomeone asked to reset the password for your Digitheque account. \
+        If it was you, follow this link within {} minutes:\n\n{}\n\n\
+        If it wasn't, you can ignore this email.",
+        user.username,
+        models::password_reset::RESET_DURATION_MINUTES,
+        link
+    );
+
+    mailer::send(
+        context.mailer.clone(),
+        &email,
+        "Reset your Digitheque password",
+        &body,
+    )
+    .await
+    .map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+
+    log::info!("Sent password reset to {}", user.username);
+    Ok(())
+}
+
+async fn redeem_password_reset(
+    context: Context,
+    reset: models::user::ResetPasswordApi,
+) -> Result<(), warp::Rejection> {
+    let mut conn = context.db_conn.get_conn();
+    let token_hash = hash_token(&reset.token);
+
+    let password_reset = models::password_reset::read_valid_by_token_hash(&mut conn, &token_hash)
+        .map_err(|_| reject::custom(NotFound))?;
+
+    let user = models::user::read_by_id(&mut conn, password_reset.user_id)
+        .map_err(|_| reject::custom(NotFound))?;
+
+    let errors =
+        validation::validate_new_password(&reset.password, &reset.confirm_password, &user.username);
+    if !errors.is_empty() {
+        return Err(reject::custom(InvalidPasswordReset(reset.token, errors)));
+    }
+
+    conn.transaction(|conn| {
+        // two requests racing with the same link can't both get past this
+        models::password_reset::redeem(conn, &token_hash)?;
+        models::user::update_password(
+            conn,
+            &user,
+            &reset.password,
+            &context.config.password_params,
+        )?;
+        // whoever got into the account before the reset is kicked out
+        models::session::delete_by_user_id(conn, user.id)
+    })
+    .map_err(|e: diesel::result::Error| match e {
+        diesel::NotFound => reject::custom(NotFound),
+        err => {
+            log::error!("{:?}", err);
+            warp::reject()
+        }
+    })?;
+
+    log::info!("Reset password of {}", user.username);
+    Ok(())
 }
 
 pub fn signup_form() -> BoxedFilter<()> {
@@ -106,6 +1164,14 @@ pub fn signup_form() -> BoxedFilter<()> {
         .boxed()
 }
 
+// an invite link lands here with the code already filled in
+pub fn signup_form_with_invite() -> BoxedFilter<(Context, models::invite::InviteQuery)> {
+    signup_form()
+        .and(filters::ext::get::<Context>())
+        .and(warp::query::<models::invite::InviteQuery>())
+        .boxed()
+}
+
 pub fn login_form() -> BoxedFilter<()> {
     warp::path("login")
         .and(warp::path::end())
@@ -113,34 +1179,62 @@ pub fn login_form() -> BoxedFilter<()> {
         .boxed()
 }
 
-async fn with_new_session(
+pub async fn with_new_session(
     context: Context,
     user: models::user::User,
     background: models::background::Background,
-) -> Result<(Context, models::user::ExpandedUser), warp::Rejection> {
+    client: ClientInfo,
+) -> Result<(Context, models::user::ExpandedUser, String), warp::Rejection> {
+    create_session(context, user, background, false, client)
+}
+
+async fn with_new_remembered_session(
+    context: Context,
+    user: models::user::User,
+    background: models::background::Background,
+    remember: bool,
+    client: ClientInfo,
+) -> Result<(Context, models::user::ExpandedUser, String), warp::Rejection> {
+    create_session(context, user, background, remember, client)
+}
+
+// every login gets its own session so users can stay logged in on many devices
+fn create_session(
+    context: Context,
+    user: models::user::User,
+    background: models::background::Background,
+    remember: bool,
+    client: ClientInfo,
+) -> Result<(Context, models::user::ExpandedUser, String), warp::Rejection> {
     let mut conn = context.db_conn.get_conn();
+    // the cookie gets the token, the database only ever sees its hash
+    let token = generate_token();
 
-    models::session::delete_by_user_id(&mut conn, user.id).map_err(|err| {
-        log::error!("{:?}", err);
-        warp::reject()
+    let (idle, lifetime) = context.config.session_durations(remember);
+    let session = models::session::NewSession::new(
+        user.id,
+        hash_token(&token),
+        idle,
+        lifetime,
+        remember,
+        client,
+    )
+    .insert(&mut conn)
+    .map_err(|_| {
+        warp::reject::custom(ResourceError::Duplicate(ResourceErrorData {
+            context: Some(context.clone()),
+            expanded_user: None,
+            expanded_page: None,
+        }))
     })?;
 
-    let session = models::session::NewSession::new(user.id)
-        .insert(&mut conn)
-        .map_err(|_| {
-            warp::reject::custom(ResourceError::Duplicate(ResourceErrorData {
-                context: Some(context.clone()),
-                expanded_user: None,
-                expanded_page: None,
-            }))
-        })?;
-
     let expanded_user = ExpandedUser {
         user,
         background,
         session,
+        api_token: None,
     };
-    Ok((context, expanded_user))
+    Ok((context, expanded_user, token))
 }
 
 async fn clear_session(
@@ -154,34 +1248,88 @@ async fn clear_session(
 
 async fn with_user_from_cookie(
     context: Context,
-    session_id: i32,
+    token: String,
 ) -> Result<(Context, models::user::ExpandedUser), warp::Rejection> {
     let mut conn = context.db_conn.get_conn();
-    log::info!("Session ID: {}", session_id);
-    let expanded_user = models::user::read_user_by_session(&mut conn, session_id)
+    let expanded_user = models::user::read_user_by_session(&mut conn, &hash_token(&token))
         .map_err(|_| warp::reject::custom(NotAuthorized))?;
     log::info!(
-        "Recognized user {:?} from {:?}",
-        expanded_user.user,
-        expanded_user.session
+        "Recognized user {} from session {}",
+        expanded_user.user.username,
+        expanded_user.session.id
     );
 
-    if expanded_user.session.valid_until < now() {
+    if expanded_user.session.is_expired() {
         models::session::delete(&mut conn, &expanded_user.session)
             .map_err(|_| warp::reject::custom(NotFound))?;
         return Err(warp::reject::custom(OldCookie));
     }
 
-    Ok((context, expanded_user))
+    // any authenticated request counts as activity
+    let (idle, _) = context
+        .config
+        .session_durations(expanded_user.session.remember);
+    let session =
+        models::session::refresh(&mut conn, &expanded_user.session, idle).map_err(|e| {
+            log::error!("{:?}", e);
+            warp::reject()
+        })?;
+
+    Ok((
+        context,
+        ExpandedUser {
+            session,
+            ..expanded_user
+        },
+    ))
+}
+
+// an unknown token is refused outright rather than falling back to the cookie
+async fn with_user_from_bearer(
+    context: Context,
+    header: String,
+    scope: &'static str,
+) -> Result<(Context, models::user::ExpandedUser), warp::Rejection> {
+    let token = models::api_token::bearer_token(&header).ok_or_else(warp::reject)?;
+    let mut conn = context.db_conn.get_conn();
+
+    let api_token = models::api_token::read_by_token_hash(&mut conn, &hash_token(token))
+        .map_err(|_| warp::reject::custom(NotAuthorized))?;
+    if !api_token.allows(scope) {
+        log::warn!("API token {} lacks the {} scope", api_token.id, scope);
+        return Err(warp::reject::custom(NotAuthorized));
+    }
+
+    let (user, background) = models::user::read_with_background_by_id(&mut conn, api_token.user_id)
+        .map_err(|_| warp::reject::custom(NotAuthorized))?;
+    let api_token = models::api_token::touch(&mut conn, &api_token).map_err(|e| {
+        log::error!("{:?}", e);
+        warp::reject()
+    })?;
+    log::info!(
+        "Recognized user {} from API token {}",
+        user.username,
+        api_token.id
+    );
+
+    Ok((
+        context,
+        ExpandedUser {
+            user,
+            background,
+            session: api_token.session(),
+            api_token: Some(api_token),
+        },
+    ))
 }
 
 async fn with_session_from_cookie(
     context: Context,
-    session_id: i32,
+    token: String,
 ) -> Result<(Context, models::session::Session), warp::Rejection> {
     let mut conn = context.db_conn.get_conn();
 
-    let session = models::session::read_by_id(&mut conn, session_id)
+    let session = models::session::read_by_token_hash(&mut conn, &hash_token(&token))
         .map_err(|_| warp::reject::custom(NotFound))?;
 
     Ok((context, session))
@@ -190,17 +1338,349 @@ async fn with_session_from_cookie(
 pub fn authenticate_cookie() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
     warp::any()
         .and(filters::ext::get::<Context>())
-        .and(warp::cookie("session"))
+        .and(warp::cookie::<String>("session"))
         .and_then(with_user_from_cookie)
         .untuple_one()
         .boxed()
 }
 
+// scripts send a personal access token instead of the session cookie
+pub fn authenticate_bearer(
+    scope: &'static str,
+) -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    warp::any()
+        .and(filters::ext::get::<Context>())
+        .and(warp::header::<String>("authorization"))
+        .and_then(move |context, header| with_user_from_bearer(context, header, scope))
+        .untuple_one()
+        .boxed()
+}
+
+// the account, its sessions, passkeys and tokens are only ever handled from a logged in
+// browser, so a leaked token can't change the password, mint its successors or lock anyone out
+pub fn authenticate() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    authenticate_cookie()
+}
+
+// groups and links are what scripts are for, there a read token stands in for the cookie
+pub fn authenticate_or_token() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    authenticate_bearer(models::api_token::READ)
+        .or(authenticate())
+        .unify()
+        .boxed()
+}
+
+// state changing requests must also echo the session's csrf token,
+// htmx and fetch send it as a header
+pub fn authenticate_csrf() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    authenticate_cookie()
+        .and(warp::header::optional::<String>("x-csrf-token"))
+        .and_then(with_csrf_header)
+        .untuple_one()
+        .boxed()
+}
+
+// a token is never sent by the browser on its own, so it only needs the write scope
+pub fn authenticate_csrf_or_token() -> BoxedFilter<(Context, models::user::ExpandedUser)> {
+    authenticate_bearer(models::api_token::WRITE)
+        .or(authenticate_csrf())
+        .unify()
+        .boxed()
+}
+
+// plain html forms send the token as a hidden csrf_token field,
+// so the body is read once here and parsed into the form the route wants
+pub fn authenticate_csrf_form<T>() -> BoxedFilter<(Context, models::user::ExpandedUser, T)>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    authenticate_cookie()
+        .and(warp::header::optional::<String>("x-csrf-token"))
+        .and(warp::body::content_length_limit(MAX_FORM_BYTES))
+        .and(warp::body::bytes())
+        .and_then(with_csrf_form::<T>)
+        .untuple_one()
+        .boxed()
+}
+
+pub fn authenticate_csrf_form_or_token<T>() -> BoxedFilter<(Context, models::user::ExpandedUser, T)>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    authenticate_bearer(models::api_token::WRITE)
+        .and(warp::body::content_length_limit(MAX_FORM_BYTES))
+        .and(warp::body::bytes())
+        .and_then(with_form::<T>)
+        .untuple_one()
+        .or(authenticate_csrf_form::<T>())
+        .unify()
+        .boxed()
+}
+
+#[derive(Deserialize)]
+struct CsrfField {
+    csrf_token: Option<String>,
+}
+
+fn verify_csrf(
+    expanded_user: &models::user::ExpandedUser,
+    token: Option<String>,
+) -> Result<(), warp::Rejection> {
+    match token {
+        Some(token) if constant_time_eq(&token, &expanded_user.session.csrf_token) => Ok(()),
+        _ => {
+            log::warn!(
+                "CSRF token mismatch for user {}",
+                expanded_user.user.username
+            );
+            Err(reject::custom(CsrfMismatch))
+        }
+    }
+}
+
+async fn with_csrf_header(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    header: Option<String>,
+) -> Result<(Context, models::user::ExpandedUser), warp::Rejection> {
+    verify_csrf(&expanded_user, header)?;
+    Ok((context, expanded_user))
+}
+
+async fn with_csrf_form<T: DeserializeOwned>(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    header: Option<String>,
+    body: Bytes,
+) -> Result<(Context, models::user::ExpandedUser, T), warp::Rejection> {
+    let field = serde_urlencoded::from_bytes::<CsrfField>(&body)
+        .map_err(|e| reject::custom(MalformedForm(e.to_string())))?;
+    verify_csrf(&expanded_user, header.or(field.csrf_token))?;
+
+    with_form(context, expanded_user, body).await
+}
+
+async fn with_form<T: DeserializeOwned>(
+    context: Context,
+    expanded_user: models::user::ExpandedUser,
+    body: Bytes,
+) -> Result<(Context, models::user::ExpandedUser, T), warp::Rejection> {
+    let form = serde_urlencoded::from_bytes::<T>(&body)
+        .map_err(|e| reject::custom(MalformedForm(e.to_string())))?;
+    Ok((context, expanded_user, form))
+}
+
+pub fn client_info() -> BoxedFilter<(ClientInfo,)> {
+    warp::header::optional::<String>("user-agent")
+        .and(warp::header::optional::<String>("x-forwarded-for"))
+        .and(filters::ext::optional::<PeerAddr>())
+        .and(filters::ext::get::<Context>())
+        .map(
+            |user_agent: Option<String>,
+             forwarded_for: Option<String>,
+             peer: Option<PeerAddr>,
+             context: Context| {
+                let ip = client_ip(
+                    peer.and_then(|peer| peer.0).map(|peer| peer.ip()),
+                    forwarded_for.as_deref(),
+                    &context.config.trusted_proxies,
+                );
+
+                ClientInfo::new(user_agent, ip.map(|ip| ip.to_string()))
+            },
+        )
+        .boxed()
+}
+
 pub fn read_cookie() -> BoxedFilter<(Context, models::session::Session)> {
     warp::any()
         .and(filters::ext::get::<Context>())
-        .and(warp::cookie("session"))
+        .and(warp::cookie::<String>("session"))
         .and_then(with_session_from_cookie)
         .untuple_one()
         .boxed()
 }
+
+// a user logged in over the context's pool, as the cookie filter would hand them over
+#[cfg(test)]
+pub fn insert_test_login(context: &Context, username: &str, password: &str) -> ExpandedUser {
+    let mut conn = context.db_conn.get_conn();
+    let user = models::user::insert_test_user(&mut conn, username, password);
+    let (idle, lifetime) = context.config.session_durations(false);
+    let session = models::session::insert_test_session(&mut conn, user.id, idle, lifetime);
+    models::user::read_user_by_session(&mut conn, &session.token_hash).unwrap()
+}
+
+#[tokio::test]
+async fn test_update_password() {
+    let Some(context) = crate::server::test_context() else {
+        return;
+    };
+    let old_password = "plum orchard lantern";
+    let expanded_user = insert_test_login(&context, "rekeyed", old_password);
+    let other = {
+        let mut conn = context.db_conn.get_conn();
+        let (idle, lifetime) = context.config.session_durations(false);
+        models::session::insert_test_session(&mut conn, expanded_user.user.id, idle, lifetime)
+    };
+    let change = |current_password: &str, password: &str, confirm_password: &str| {
+        models::user::ChangePasswordApi {
+            current_password: current_password.to_string(),
+            password: password.to_string(),
+            confirm_password: confirm_password.to_string(),
+        }
+    };
+    let new_password = "granite kettle meadow";
+
+    let rejection = update_password(
+        context.clone(),
+        expanded_user.clone(),
+        change("not my password", new_password, new_password),
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(
+        rejection.find::<CredentialError>(),
+        Some(CredentialError::WrongPassword(_))
+    ));
+
+    let rejection = update_password(
+        context.clone(),
+        expanded_user.clone(),
+        change(old_password, new_password, "granite kettle"),
+    )
+    .await
+    .unwrap_err();
+    let errors = &rejection.find::<ValidationError>().unwrap().errors;
+    assert!(errors.confirm_password.is_some());
+
+    // neither refusal touched the stored password
+    let mut conn = context.db_conn.get_conn();
+    let user = models::user::read_by_id(&mut conn, expanded_user.user.id).unwrap();
+    assert!(user.verify_password(old_password));
+    drop(conn);
+
+    let (_, updated) = update_password(
+        context.clone(),
+        expanded_user.clone(),
+        change(old_password, new_password, new_password),
+    )
+    .await
+    .unwrap();
+    assert!(updated.user.verify_password(new_password));
+    assert!(!updated.user.verify_password(old_password));
+
+    // the other device is logged out, the one that made the change is not
+    let mut conn = context.db_conn.get_conn();
+    let user = models::user::read_by_id(&mut conn, expanded_user.user.id).unwrap();
+    assert!(user.verify_password(new_password));
+    assert!(models::user::read_user_by_session(&mut conn, &other.token_hash).is_err());
+    assert!(
+        models::user::read_user_by_session(&mut conn, &expanded_user.session.token_hash).is_ok()
+    );
+}
+
+#[tokio::test]
+async fn test_update_email() {
+    use crate::schema::email_verification;
+    use diesel::prelude::*;
+
+    let Some(context) = crate::server::test_context() else {
+        return;
+    };
+    let password = "copper willow harbor";
+    let expanded_user = insert_test_login(&context, "mailbox", password);
+    let pending_emails = |context: &Context| {
+        let mut conn = context.db_conn.get_conn();
+        email_verification::table
+            .filter(email_verification::user_id.eq(expanded_user.user.id))
+            .filter(email_verification::used_at.is_null())
+            .filter(email_verification::deleted_at.is_null())
+            .select(email_verification::email)
+            .load::<String>(&mut conn)
+            .unwrap()
+    };
+    let change = |email: &str, password: &str| models::user::ChangeEmailApi {
+        email: email.to_string(),
+        password: password.to_string(),
+    };
+
+    let rejection = update_email(
+        context.clone(),
+        expanded_user.clone(),
+        change("new@example.com", "not my password"),
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(
+        rejection.find::<CredentialError>(),
+        Some(CredentialError::WrongPassword(_))
+    ));
+    assert!(pending_emails(&context).is_empty());
+
+    // the address only changes once the link is followed
+    let (_, updated, pending) = update_email(
+        context.clone(),
+        expanded_user.clone(),
+        change(" new@example.com ", password),
+    )
+    .await
+    .unwrap();
+    assert_eq!(pending.as_deref(), Some("new@example.com"));
+    assert_eq!(updated.user.email, None);
+    assert_eq!(
+        pending_emails(&context),
+        vec![String::from("new@example.com")]
+    );
+
+    // asking again replaces the earlier link
+    let (_, _, pending) = update_email(
+        context.clone(),
+        expanded_user.clone(),
+        change("other@example.com", password),
+    )
+    .await
+    .unwrap();
+    assert_eq!(pending.as_deref(), Some("other@example.com"));
+    assert_eq!(
+        pending_emails(&context),
+        vec![String::from("other@example.com")]
+    );
+
+    let user = {
+        let mut conn = context.db_conn.get_conn();
+        models::user::update_email(
+            &mut conn,
+            &expanded_user.user,
+            Some(String::from("other@example.com")),
+        )
+        .unwrap()
+    };
+    let expanded_user = ExpandedUser {
+        user,
+        ..expanded_user.clone()
+    };
+
+    // the address already on the account needs no new link
+    let (_, updated, pending) = update_email(
+        context.clone(),
+        expanded_user.clone(),
+        change("other@example.com", password),
+    )
+    .await
+    .unwrap();
+    assert_eq!(pending, None);
+    assert_eq!(updated.user.email.as_deref(), Some("other@example.com"));
+    assert!(pending_emails(&context).is_empty());
+
+    // an empty address clears it straight away
+    let (_, updated, pending) = update_email(
+        context.clone(),
+        expanded_user.clone(),
+        change("  ", password),
+    )
+    .await
+    .unwrap();
+    assert_eq!(pending, None);
+    assert_eq!(updated.user.email, None);
+}
@@ -1,3 +1,28 @@
+table! {
+    api_token (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+        token_hash -> Varchar,
+        scope -> Varchar,
+        last_used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    audit_log (id) {
+        id -> Int4,
+        user_id -> Nullable<Int4>,
+        event -> Varchar,
+        detail -> Text,
+        ip -> Nullable<Varchar>,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     background (id) {
         id -> Int4,
@@ -15,6 +40,47 @@ table! {
     }
 }
 
+table! {
+    email_verification (id) {
+        id -> Int4,
+        user_id -> Int4,
+        email -> Varchar,
+        token_hash -> Varchar,
+        valid_until -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    identity (id) {
+        id -> Int4,
+        user_id -> Int4,
+        provider -> Varchar,
+        subject -> Varchar,
+        email -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    invite (id) {
+        id -> Int4,
+        issuer_user_id -> Int4,
+        code -> Varchar,
+        max_uses -> Int4,
+        uses -> Int4,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
 table! {
     link (id) {
         id -> Int4,
@@ -29,6 +95,33 @@ table! {
     }
 }
 
+table! {
+    login_attempt (id) {
+        id -> Int4,
+        username -> Varchar,
+        ip -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    oauth_state (id) {
+        id -> Int4,
+        provider -> Varchar,
+        state_hash -> Varchar,
+        code_verifier -> Varchar,
+        valid_until -> Timestamp,
+        created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+        me -> Nullable<Varchar>,
+        authorization_endpoint -> Nullable<Varchar>,
+        nonce -> Nullable<Varchar>,
+    }
+}
+
 table! {
     page (id) {
         id -> Int4,
@@ -39,6 +132,9 @@ table! {
         updated_at -> Nullable<Timestamp>,
         deleted_at -> Nullable<Timestamp>,
         background_id -> Int4,
+        visibility -> Varchar,
+        hidden -> Bool,
+        slug -> Varchar,
     }
 }
 
@@ -51,6 +147,85 @@ table! {
         created_at -> Timestamp,
         updated_at -> Nullable<Timestamp>,
         deleted_at -> Nullable<Timestamp>,
+        position -> Int4,
+        notes -> Text,
+        hidden_reason -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    page_slug (id) {
+        id -> Int4,
+        page_id -> Int4,
+        slug -> Varchar,
+        created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    passkey (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+        credential -> Text,
+        last_used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    passkey_challenge (id) {
+        id -> Int4,
+        user_id -> Int4,
+        token_hash -> Varchar,
+        state -> Text,
+        valid_until -> Timestamp,
+        created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    password_reset (id) {
+        id -> Int4,
+        user_id -> Int4,
+        token_hash -> Varchar,
+        valid_until -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    pending_login (id) {
+        id -> Int4,
+        user_id -> Int4,
+        token_hash -> Varchar,
+        remember -> Bool,
+        attempts -> Int4,
+        valid_until -> Timestamp,
+        created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    recovery_code (id) {
+        id -> Int4,
+        user_id -> Int4,
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -62,6 +237,13 @@ table! {
         created_at -> Timestamp,
         updated_at -> Nullable<Timestamp>,
         deleted_at -> Nullable<Timestamp>,
+        token_hash -> Varchar,
+        expires_at -> Timestamp,
+        remember -> Bool,
+        user_agent -> Nullable<Varchar>,
+        ip -> Nullable<Varchar>,
+        last_seen_at -> Timestamp,
+        csrf_token -> Varchar,
     }
 }
 
@@ -74,13 +256,53 @@ table! {
         updated_at -> Nullable<Timestamp>,
         deleted_at -> Nullable<Timestamp>,
         background_id -> Int4,
+        email -> Nullable<Varchar>,
+        totp_secret -> Nullable<Varchar>,
+        totp_enabled_at -> Nullable<Timestamp>,
+        role -> Varchar,
+        suspended_reason -> Nullable<Varchar>,
+        quota_plan -> Nullable<Varchar>,
+        max_pages -> Nullable<Int4>,
+        max_links -> Nullable<Int4>,
+        totp_last_step -> Nullable<Int8>,
     }
 }
 
+joinable!(api_token -> user (user_id));
+joinable!(audit_log -> user (user_id));
+joinable!(email_verification -> user (user_id));
+joinable!(identity -> user (user_id));
+joinable!(invite -> user (issuer_user_id));
 joinable!(link -> user (creator_user_id));
 joinable!(page -> background (background_id));
 joinable!(page_link -> link (link_id));
 joinable!(page_link -> page (page_id));
+joinable!(page_slug -> page (page_id));
+joinable!(passkey -> user (user_id));
+joinable!(passkey_challenge -> user (user_id));
+joinable!(password_reset -> user (user_id));
+joinable!(pending_login -> user (user_id));
+joinable!(recovery_code -> user (user_id));
 joinable!(user -> background (background_id));
 
-allow_tables_to_appear_in_same_query!(background, link, page, page_link, session, user,);
+allow_tables_to_appear_in_same_query!(
+    api_token,
+    audit_log,
+    background,
+    email_verification,
+    identity,
+    invite,
+    link,
+    login_attempt,
+    oauth_state,
+    page,
+    page_link,
+    page_slug,
+    passkey,
+    passkey_challenge,
+    password_reset,
+    pending_login,
+    recovery_code,
+    session,
+    user,
+);
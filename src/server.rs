@@ -1,11 +1,21 @@
 use crate::{
-    api::{assets::assets, index::index_api, link::link_api, page::page, user::user},
+    api::{
+        admin::admin,
+        assets::assets,
+        index::index_api,
+        link::link_api,
+        page::{page, page_slug},
+        user::user,
+    },
     config::Config,
     db_conn::DbConn,
-    handle_final_rejection, handle_rejection, handlers, routes,
+    handle_final_rejection, handle_rejection, handlers,
+    indieauth::ProfileClient,
+    mailer::{self, Mailer},
+    routes,
     utils::{load_certs, load_private_key},
 };
-use hyper_rustls::TlsAcceptor;
+use tokio_rustls::{rustls::ServerConfig, TlsAcceptor};
 
 use tower_http::{
     add_extension::AddExtensionLayer,
@@ -18,13 +28,16 @@ use tower_http::{
 use bytes::Bytes;
 use hyper::{
     header,
-    server::conn::{AddrIncoming, AddrStream},
+    server::{
+        accept::Accept,
+        conn::{AddrIncoming, Http},
+    },
     service::make_service_fn,
-    Body, Response, Server,
+    Body, Response,
 };
-use std::{convert::Infallible, net::SocketAddr, sync::Arc, time::Duration};
+use std::{convert::Infallible, net::SocketAddr, pin::Pin, sync::Arc, time::Duration};
 use tokio::{sync::Semaphore, time::timeout};
-use tower::{limit::GlobalConcurrencyLimitLayer, ServiceBuilder};
+use tower::{limit::GlobalConcurrencyLimitLayer, Service, ServiceBuilder};
 use warp::Filter;
 
 const CONN_TIMEOUT: u64 = 2 * 60;
@@ -37,15 +50,22 @@ pub async fn serve(addr: SocketAddr, config: Arc<Config>) -> Result<(), warp::hy
     let db_conn = Arc::new(DbConn::new(&config.db_path));
     let context = Context::new(config.clone(), db_conn.clone());
 
+    // each group is boxed so the compiler doesn't have to see through the whole chain at once
     let end = assets!()
-        .or(index_api!())
+        .boxed()
+        .or(index_api!().boxed())
         .or(user!()
-            .or(page!())
-            .or(link_api!())
+            .boxed()
+            .or(page!().boxed())
+            .or(page_slug!().boxed())
+            .or(link_api!().boxed())
+            .or(admin!().boxed())
             .map(|reply| warp::reply::with_header(reply, "Access-Control-Allow-Origin", "*")))
         .recover(handle_final_rejection);
 
-    let app = make_service_fn(move |_| {
+    let mut app = make_service_fn(move |peer: &PeerAddr| {
+        // warp never learns the peer behind the tls handshake, so it rides along per connection
+        let peer = *peer;
         let conns_limit = conns_limit.clone();
         let reqs_limit = reqs_limit.clone();
 
@@ -73,6 +93,7 @@ pub async fn serve(addr: SocketAddr, config: Arc<Config>) -> Result<(), warp::hy
                     .timeout(Duration::from_secs(REQ_TIMEOUT))
                     // Share the context with each handler via a request extension
                     .layer(AddExtensionLayer::new(context))
+                    .layer(AddExtensionLayer::new(peer))
                     // Compress responses
                     .layer(CompressionLayer::new())
                     // Mark the `Authorization` and `Cookie` headers as sensitive so it doesn't show in logs
@@ -98,13 +119,45 @@ pub async fn serve(addr: SocketAddr, config: Arc<Config>) -> Result<(), warp::hy
     let key = load_private_key(&config.key_path.clone().unwrap()).unwrap();
     // Build TLS configuration.
     // Create a TCP listener via tokio.
-    let incoming = AddrIncoming::bind(&addr)?;
-    let acceptor = TlsAcceptor::builder()
+    let mut tls_config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
         .with_single_cert(certs, key)
-        .unwrap()
-        .with_all_versions_alpn()
-        .with_incoming(incoming);
-    Server::builder(acceptor).serve(app).await?;
+        .unwrap();
+    tls_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec(), b"http/1.0".to_vec()];
+    let acceptor = TlsAcceptor::from(Arc::new(tls_config));
+    let mut incoming = AddrIncoming::bind(&addr)?;
+
+    // the tcp stream still knows its peer here, tls and hyper take over once it's handed on
+    while let Some(stream) =
+        std::future::poll_fn(|cx| Pin::new(&mut incoming).poll_accept(cx)).await
+    {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::error!("{:?}", e);
+                continue;
+            }
+        };
+        let peer = PeerAddr(Some(stream.remote_addr()));
+        let service = app.call(&peer);
+        let acceptor = acceptor.clone();
+
+        // a slow handshake only ever holds up its own connection
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::debug!("TLS handshake with {:?} failed: {:?}", peer.0, e);
+                    return;
+                }
+            };
+            let Ok(service) = service.await;
+            if let Err(e) = Http::new().serve_connection(stream, service).await {
+                log::debug!("Connection with {:?} ended: {:?}", peer.0, e);
+            }
+        });
+    }
 
     // otherwise serve normally
     // let listener = std::net::TcpListener::bind(addr).unwrap();
@@ -113,17 +166,40 @@ pub async fn serve(addr: SocketAddr, config: Arc<Config>) -> Result<(), warp::hy
     Ok(())
 }
 
+// where the connection came from, which may be a proxy rather than the client
+#[derive(Clone, Copy, Debug)]
+pub struct PeerAddr(pub Option<SocketAddr>);
+
 #[derive(Clone, Debug)]
 pub struct Context {
     pub config: Arc<Config>,
     pub db_conn: Arc<DbConn>,
+    pub mailer: Arc<dyn Mailer>,
+    pub profile_client: ProfileClient,
 }
 
 impl Context {
     pub fn new(config: Arc<Config>, db_conn: Arc<DbConn>) -> Self {
+        let mailer = mailer::from_config(&config.mailer);
         Context {
             config: config,
             db_conn,
+            mailer,
+            profile_client: ProfileClient::new(),
         }
     }
 }
+
+// the test config over the test pool, with mail kept out of the working directory
+#[cfg(test)]
+pub fn test_context() -> Option<Context> {
+    let mut config = crate::config::test_config();
+    config.mailer = crate::config::MailerConfig::File {
+        path: std::env::temp_dir()
+            .join("crate-test-mail.log")
+            .to_string_lossy()
+            .into_owned(),
+    };
+    let db_conn = crate::db_conn::test_pool()?;
+    Some(Context::new(Arc::new(config), Arc::new(db_conn)))
+}
@@ -1,8 +1,19 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
 use chrono::prelude::*;
+use pulldown_cmark::{escape, html, Event, Parser, Tag};
 use pwhash::bcrypt;
-use sanitize_html::{rules::predefined::DEFAULT, sanitize_str};
+use rand::RngCore;
+use sanitize_html::{
+    rules::predefined::{DEFAULT, RELAXED},
+    sanitize_str,
+};
+use sha2::{Digest, Sha256};
 use std::fs::File;
 use std::io::prelude::*;
+use std::net::IpAddr;
 use std::{fs, io};
 
 pub fn now() -> chrono::naive::NaiveDateTime {
@@ -23,22 +34,115 @@ pub fn read_file_to_bytes(path: &String) -> std::io::Result<Vec<u8>> {
     Ok(contents)
 }
 
-pub fn encrypt(password: &str) -> String {
-    bcrypt::hash(password).unwrap()
+pub fn encrypt(password: &str, params: &Params) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone())
+        .hash_password(password.as_bytes(), &salt)
+        .unwrap()
+        .to_string()
 }
 
+// older accounts still carry bcrypt hashes, argon2 hashes name their own parameters
 pub fn verify(password: &str, hashed: &str) -> bool {
-    bcrypt::verify(password, hashed)
+    if !hashed.starts_with("$argon2") {
+        return bcrypt::verify(password, hashed);
+    }
+
+    match PasswordHash::new(hashed) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+// anything that isn't argon2id with today's parameters gets upgraded on the next login
+pub fn needs_rehash(hashed: &str, params: &Params) -> bool {
+    if !hashed.starts_with("$argon2id$") {
+        return true;
+    }
+
+    match PasswordHash::new(hashed).and_then(|hash| Params::try_from(&hash)) {
+        Ok(current) => {
+            current.m_cost() != params.m_cost()
+                || current.t_cost() != params.t_cost()
+                || current.p_cost() != params.p_cost()
+        }
+        Err(_) => true,
+    }
 }
 
 pub fn sanitize_html(input: &str) -> String {
     sanitize_str(&DEFAULT, input).unwrap()
 }
 
+// text kept as it was typed, for putting back into a form field
+pub fn escape_html(input: &str) -> String {
+    let mut output = String::new();
+    escape::escape_html(&mut output, input).unwrap();
+    output
+}
+
+// notes are stored as typed, so this runs on every display: raw html is shown as text,
+// link targets are checked, and the sanitizer has the last word on what's left
+pub fn render_markdown(input: &str) -> String {
+    let events = Parser::new(input).map(|event| match event {
+        Event::Start(Tag::Link(kind, url, title)) if !is_safe_url(&url) => {
+            Event::Start(Tag::Link(kind, "".into(), title))
+        }
+        Event::Start(Tag::Image(kind, url, title)) if !is_safe_url(&url) => {
+            Event::Start(Tag::Image(kind, "".into(), title))
+        }
+        Event::Html(text) => Event::Text(text),
+        event => event,
+    });
+
+    let mut output = String::new();
+    html::push_html(&mut output, events);
+    sanitize_str(&RELAXED, &output).unwrap()
+}
+
+fn is_safe_url(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    url.starts_with("https://") || url.starts_with("http://") || url.starts_with("mailto:")
+}
+
+// keyset queries ask for one row past the limit, if it comes back there is more to load
+pub fn split_page<T>(mut rows: Vec<T>, limit: i64) -> (Vec<T>, bool) {
+    let has_more = rows.len() as i64 > limit;
+    rows.truncate(limit as usize);
+    (rows, has_more)
+}
+
 pub fn random(top: usize, bottom: usize) -> usize {
     (rand::random::<usize>() % top) + bottom
 }
 
+// 256 random bits, hex encoded, for anything we hand out as a bearer secret
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+// compares every byte so the time taken says nothing about where they differ
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+// we only ever store the hash of a token so a leaked table can't be replayed
+pub fn hash_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 pub struct SiteMetadata {
     pub title: Option<String>,
     pub description: Option<String>,
@@ -97,15 +201,71 @@ pub fn load_private_key(filename: &str) -> io::Result<rustls::PrivateKey> {
 }
 
 fn error(err: String) -> io::Error {
-    io::Error::new(io::ErrorKind::Other, err)
+    io::Error::other(err)
+}
+
+// walks x-forwarded-for from the right while the hop that told us is a proxy we trust,
+// anything to the left of the first untrusted hop could have been made up by the client
+pub fn client_ip(
+    peer: Option<IpAddr>,
+    forwarded_for: Option<&str>,
+    trusted_proxies: &[IpAddr],
+) -> Option<IpAddr> {
+    let mut ip = peer?;
+    let hops = forwarded_for.unwrap_or("").rsplit(',');
+    for hop in hops {
+        if !trusted_proxies.contains(&ip) {
+            break;
+        }
+        match hop.trim().parse::<IpAddr>() {
+            Ok(hop) => ip = hop,
+            Err(_) => break,
+        }
+    }
+    Some(ip)
 }
 
 #[test]
 fn test_encryption() {
     // Hash a password with default parameters.
-    let h_new = encrypt("password");
+    let h_new = encrypt("password", &Params::default());
 
     assert!(verify("password", &h_new));
+    assert!(!verify("wrong password", &h_new));
+    assert!(!needs_rehash(&h_new, &Params::default()));
+}
+
+#[test]
+fn test_bcrypt_upgrade() {
+    let h_old = bcrypt::hash("password").unwrap();
+
+    assert!(verify("password", &h_old));
+    assert!(!verify("wrong password", &h_old));
+    assert!(needs_rehash(&h_old, &Params::default()));
+
+    let stronger = Params::new(Params::DEFAULT_M_COST, 3, 1, None).unwrap();
+    assert!(needs_rehash(
+        &encrypt("password", &Params::default()),
+        &stronger
+    ));
+}
+
+#[test]
+fn test_token_hashing() {
+    let token = generate_token();
+
+    assert_eq!(token.len(), 64);
+    assert_ne!(token, generate_token());
+    assert_eq!(hash_token(&token), hash_token(&token));
+    assert_ne!(hash_token(&token), token);
+}
+
+#[test]
+fn test_constant_time_eq() {
+    assert!(constant_time_eq("abc", "abc"));
+    assert!(!constant_time_eq("abc", "abd"));
+    assert!(!constant_time_eq("abc", "ab"));
+    assert!(constant_time_eq("", ""));
 }
 
 #[test]
@@ -117,3 +277,65 @@ fn opengraph_scrape() {
         Err(_) => println!("error occured"),
     }
 }
+
+#[test]
+fn test_render_markdown() {
+    assert_eq!(
+        render_markdown("Read *chapter 2* first"),
+        "<p>Read <em>chapter 2</em> first</p>\n"
+    );
+    assert_eq!(
+        render_markdown("[docs](https://example.com)"),
+        "<p><a href=\"https://example.com\">docs</a></p>\n"
+    );
+    assert_eq!(
+        render_markdown("[click](javascript:alert(1))"),
+        "<p><a href=\"\">click</a></p>\n"
+    );
+    assert_eq!(
+        render_markdown("Read *this* <script>alert(1)</script> & more"),
+        "<p>Read <em>this</em> &lt;script&gt;alert(1)&lt;/script&gt; &amp; more</p>\n"
+    );
+    assert_eq!(
+        render_markdown("> quoted\n\n`a < b`"),
+        "<blockquote>\n<p>quoted</p>\n</blockquote>\n<p><code>a &lt; b</code></p>\n"
+    );
+
+    // what goes back into the textarea is escaped once, and only there
+    assert_eq!(escape_html("a < b & *c*"), "a &lt; b &amp; *c*");
+}
+
+#[test]
+fn test_split_page() {
+    assert_eq!(split_page(vec![1, 2, 3], 2), (vec![1, 2], true));
+    assert_eq!(split_page(vec![1, 2], 2), (vec![1, 2], false));
+    assert_eq!(split_page(Vec::<i32>::new(), 2), (vec![], false));
+}
+
+#[test]
+fn test_client_ip() {
+    let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+    let client: IpAddr = "203.0.113.7".parse().unwrap();
+
+    // nobody is trusted, so the header is ignored
+    assert_eq!(
+        client_ip(Some(client), Some("198.51.100.1"), &[]),
+        Some(client)
+    );
+    assert_eq!(
+        client_ip(Some(proxy), Some("198.51.100.1, 203.0.113.7"), &[]),
+        Some(proxy)
+    );
+    // behind a trusted proxy the right-most hop it saw wins over a forged one
+    assert_eq!(
+        client_ip(Some(proxy), Some("198.51.100.1, 203.0.113.7"), &[proxy]),
+        Some(client)
+    );
+    // a trusted proxy with nothing to forward is the client itself
+    assert_eq!(client_ip(Some(proxy), None, &[proxy]), Some(proxy));
+    assert_eq!(
+        client_ip(Some(proxy), Some("not an ip"), &[proxy]),
+        Some(proxy)
+    );
+    assert_eq!(client_ip(None, Some("203.0.113.7"), &[proxy]), None);
+}
@@ -0,0 +1,206 @@
+use std::include_str;
+
+pub const MIN_USERNAME_LENGTH: usize = 3;
+// matches the VARCHAR(48) username column
+pub const MAX_USERNAME_LENGTH: usize = 48;
+pub const MIN_PASSWORD_LENGTH: usize = 10;
+// bcrypt ignores everything past 72 bytes
+pub const MAX_PASSWORD_LENGTH: usize = 72;
+
+// one per line, lowercase
+const COMMON_PASSWORDS: &str = include_str!("common-passwords.txt");
+
+#[derive(Clone, Debug, Default)]
+pub struct FieldErrors {
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub confirm_password: Option<String>,
+    pub invite: Option<String>,
+}
+
+impl FieldErrors {
+    pub fn is_empty(&self) -> bool {
+        self.username.is_none()
+            && self.password.is_none()
+            && self.confirm_password.is_none()
+            && self.invite.is_none()
+    }
+
+    // for forms that only have room for a single message
+    pub fn message(&self) -> String {
+        [
+            &self.username,
+            &self.password,
+            &self.confirm_password,
+            &self.invite,
+        ]
+        .iter()
+        .filter_map(|error| error.as_deref())
+        .map(|error| format!("Error: {}", error))
+        .collect::<Vec<String>>()
+        .join(" ")
+    }
+
+    pub fn inject_values(&self, string: &str) -> String {
+        string
+            .replace("{username_error}", self.username.as_deref().unwrap_or(""))
+            .replace("{password_error}", self.password.as_deref().unwrap_or(""))
+            .replace(
+                "{confirm_password_error}",
+                self.confirm_password.as_deref().unwrap_or(""),
+            )
+            .replace("{invite_error}", self.invite.as_deref().unwrap_or(""))
+    }
+}
+
+pub fn validate_username(username: &str) -> Result<(), String> {
+    let length = username.chars().count();
+    if !(MIN_USERNAME_LENGTH..=MAX_USERNAME_LENGTH).contains(&length) {
+        return Err(format!(
+            "Usernames must be between {} and {} characters",
+            MIN_USERNAME_LENGTH, MAX_USERNAME_LENGTH
+        ));
+    }
+
+    if !username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+    {
+        return Err(String::from(
+            "Usernames may only contain letters, numbers, dots, dashes and underscores",
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn validate_password(password: &str, username: &str) -> Result<(), String> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(format!(
+            "Passwords must be at least {} characters",
+            MIN_PASSWORD_LENGTH
+        ));
+    }
+
+    if password.len() > MAX_PASSWORD_LENGTH {
+        return Err(format!(
+            "Passwords must be at most {} bytes",
+            MAX_PASSWORD_LENGTH
+        ));
+    }
+
+    let lowercase = password.to_lowercase();
+    if !username.is_empty() && lowercase.contains(&username.to_lowercase()) {
+        return Err(String::from("Passwords must not contain your username"));
+    }
+
+    if is_common_password(&lowercase) {
+        return Err(String::from(
+            "This password is too common, please choose another",
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn is_common_password(password: &str) -> bool {
+    let password = password.to_lowercase();
+    COMMON_PASSWORDS.lines().any(|common| common == password)
+}
+
+pub fn validate_new_password(
+    password: &str,
+    confirm_password: &str,
+    username: &str,
+) -> FieldErrors {
+    FieldErrors {
+        username: None,
+        password: validate_password(password, username).err(),
+        confirm_password: if password != confirm_password {
+            Some(String::from("Passwords do not match"))
+        } else {
+            None
+        },
+        invite: None,
+    }
+}
+
+pub fn validate_signup(username: &str, password: &str, confirm_password: &str) -> FieldErrors {
+    FieldErrors {
+        username: validate_username(username).err(),
+        ..validate_new_password(password, confirm_password, username)
+    }
+}
+
+// turns whatever a provider tells us about someone into a username we would accept,
+// trying each hint in turn and falling back to "user"
+pub fn suggest_username(hints: &[Option<&str>]) -> String {
+    hints
+        .iter()
+        .flatten()
+        .map(|hint| {
+            hint.split('@')
+                .next()
+                .unwrap_or("")
+                .chars()
+                .map(|c| if c == ' ' { '_' } else { c })
+                .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-' || *c == '.')
+                // leave room for a suffix if the name is taken
+                .take(MAX_USERNAME_LENGTH - 5)
+                .collect::<String>()
+        })
+        .find(|username| validate_username(username).is_ok())
+        .unwrap_or(String::from("user"))
+}
+
+#[test]
+fn test_validate_username() {
+    assert!(validate_username("hg").is_err());
+    assert!(validate_username("hghimself").is_ok());
+    assert!(validate_username("hg.himself-2_0").is_ok());
+    assert!(validate_username("<script>").is_err());
+    assert!(validate_username("h g").is_err());
+    assert!(validate_username(&"a".repeat(MAX_USERNAME_LENGTH + 1)).is_err());
+}
+
+#[test]
+fn test_validate_password() {
+    assert!(validate_password("short", "hg").is_err());
+    assert!(validate_password("Password123", "hg").is_err());
+    assert!(validate_password("QWERTYUIOP", "hg").is_err());
+    assert!(validate_password("hghimself-rocks", "hghimself").is_err());
+    assert!(validate_password(&"a".repeat(MAX_PASSWORD_LENGTH + 1), "hg").is_err());
+    assert!(validate_password("correct horse battery staple", "hg").is_ok());
+}
+
+#[test]
+fn test_validate_signup() {
+    let errors = validate_signup("hghimself", "correct horse battery staple", "correct horse");
+    assert!(errors.username.is_none());
+    assert!(errors.password.is_none());
+    assert!(errors.confirm_password.is_some());
+    assert!(!errors.is_empty());
+
+    let errors = validate_signup(
+        "hghimself",
+        "correct horse battery staple",
+        "correct horse battery staple",
+    );
+    assert!(errors.is_empty());
+    assert_eq!(errors.message(), "");
+}
+
+#[test]
+fn test_suggest_username() {
+    assert_eq!(suggest_username(&[Some("ada"), Some("x@y.z")]), "ada");
+    assert_eq!(
+        suggest_username(&[None, Some("grace.hopper@example.com")]),
+        "grace.hopper"
+    );
+    assert_eq!(
+        suggest_username(&[Some("é"), Some("Alan Turing")]),
+        "Alan_Turing"
+    );
+    assert_eq!(suggest_username(&[Some("ab"), None]), "user");
+    assert!(suggest_username(&[Some(&"a".repeat(100))]).len() <= MAX_USERNAME_LENGTH - 5);
+}
@@ -0,0 +1,121 @@
+use crate::{
+    config::{Quota, QuotaPlanConfig},
+    models, views,
+};
+use std::include_str;
+
+pub struct Counts {
+    pub users: usize,
+    pub pages: usize,
+    pub links: usize,
+}
+
+pub fn console(
+    expanded_user: &models::user::ExpandedUser,
+    counts: Counts,
+    users: Vec<(models::user::User, usize, Quota)>,
+    pages: Vec<(models::page::Page, String, usize)>,
+    links: Vec<(
+        models::page_link::PageLink,
+        models::link::Link,
+        models::page::Page,
+    )>,
+    plans: &[QuotaPlanConfig],
+) -> String {
+    let users_html = users
+        .iter()
+        .map(|(user, page_count, quota)| user_item(expanded_user, user, *page_count, quota, plans))
+        .collect::<String>();
+    let pages_html = pages
+        .iter()
+        .map(|(page, username, link_count)| {
+            page.inject_values(include_str!("admin-page-item.html"))
+                .replace("{page.url}", &page.url(username))
+                .replace("{page.username}", username)
+                .replace("{link_count}", &link_count.to_string())
+        })
+        .collect::<String>();
+    let links_html = links
+        .iter()
+        .map(|(_, link, page)| {
+            page.inject_values(&link.inject_values(&views::body::with_csrf(
+                include_str!("admin-link-item.html"),
+                &expanded_user.session,
+            )))
+        })
+        .collect::<String>();
+
+    views::body::document_authenticated(
+        String::from("Admin"),
+        &expanded_user.user,
+        &expanded_user.session,
+        expanded_user
+            .user
+            .inject_values(include_str!("admin.html"))
+            .replace("{user_count}", &counts.users.to_string())
+            .replace("{page_count}", &counts.pages.to_string())
+            .replace("{link_count}", &counts.links.to_string())
+            .replace("{users}", &users_html)
+            .replace("{pages}", &pages_html)
+            .replace("{links}", &links_html)
+            .replace("{background}", &expanded_user.background.to_call()),
+    )
+}
+
+// only admins get controls, and never over themselves or another admin
+fn user_item(
+    viewer: &models::user::ExpandedUser,
+    user: &models::user::User,
+    page_count: usize,
+    quota: &Quota,
+    plans: &[QuotaPlanConfig],
+) -> String {
+    let status = match (&user.deleted_at, &user.suspended_reason) {
+        (Some(_), Some(reason)) => format!("suspended: {}", reason),
+        (Some(_), None) => String::from("deleted"),
+        (None, _) => format!("joined {}", user.created_at.format("%Y-%m-%d")),
+    };
+    let actions = if viewer.user.has_role(models::user::ROLE_ADMIN)
+        && user.deleted_at.is_none()
+        && user.id != viewer.user.id
+        && !user.has_role(models::user::ROLE_ADMIN)
+    {
+        include_str!("admin-user-actions.html")
+    } else {
+        ""
+    };
+
+    let template = include_str!("admin-user-item.html").replace("{actions}", actions);
+    user.inject_values(&views::body::with_csrf(&template, &viewer.session))
+        .replace("{page_count}", &page_count.to_string())
+        .replace("{status}", &status)
+        .replace("{quota.plan}", &quota.plan)
+        .replace("{quota.max_pages}", &quota.max_pages.to_string())
+        .replace("{quota.max_links}", &quota.max_links.to_string())
+        .replace("{plan_options}", &plan_options(plans, &quota.plan))
+        .replace("{user.max_pages}", &limit_value(user.max_pages))
+        .replace("{user.max_links}", &limit_value(user.max_links))
+}
+
+fn plan_options(plans: &[QuotaPlanConfig], selected: &str) -> String {
+    plans
+        .iter()
+        .map(|plan| {
+            format!(
+                "<option value='{}'{}>{}</option>",
+                plan.name,
+                if plan.name == selected {
+                    " selected"
+                } else {
+                    ""
+                },
+                plan.name
+            )
+        })
+        .collect::<String>()
+}
+
+// an empty field means the plan's limit applies
+fn limit_value(limit: Option<i32>) -> String {
+    limit.map(|limit| limit.to_string()).unwrap_or_default()
+}
@@ -18,7 +18,7 @@ pub fn document(title: String, content: String) -> String {
 </main>
 {footer}
 </div>
-<script>sal();</script>
+<script>sal(); document.body.addEventListener('htmx:afterSettle', () => sal());</script>
 </body>
 </html>"
     )
@@ -50,6 +50,8 @@ pub fn head(title: String) -> String {
 <script src='/sal.js'></script>
 <link rel='stylesheet' href='/sal.css' />
 <script src='/background.js'></script>
+<script src='/passkey.js'></script>
+<script src='/toast.js'></script>
 </head>",
     )
 }
@@ -70,6 +72,11 @@ pub fn header() -> String {
 }
 
 pub fn header_authenticated(user: &models::user::User) -> String {
+    let admin = if user.has_role(models::user::ROLE_MODERATOR) {
+        "<li><a href='/admin'>Admin</a></li>"
+    } else {
+        ""
+    };
     format!(
         "
     <header>
@@ -77,19 +84,25 @@ pub fn header_authenticated(user: &models::user::User) -> String {
             <h1 class='title'><a href='/' class='normalized'>digitheque.io</a></h1>
             <ul class='actions'>
                 <li><a href='/link/random'>Random link</a></li>
+                {}
                 <li><a href='/user'>{}</a></li>
                 <li><a href='/user/logout'>Logout</a></li>
             </ul>
         </div>
     </header>",
-        user.username
+        admin, user.username
     )
 }
 
-pub fn index_authenticated(user: &models::user::User, message: &str) -> String {
+pub fn index_authenticated(
+    user: &models::user::User,
+    session: &models::session::Session,
+    message: &str,
+) -> String {
     document_authenticated(
         String::from("Digitheque"),
         user,
+        session,
         include_str!("index-authenticated.html")
             .to_string()
             .replace("{message}", message)
@@ -107,15 +120,29 @@ pub fn index(message: &str) -> String {
     )
 }
 
-pub fn document_authenticated(title: String, user: &models::user::User, content: String) -> String {
+// the token goes into the bare template before any user or scraped value does,
+// so a value that happens to spell out the placeholder can't pull it into the page
+pub fn with_csrf(template: &str, session: &models::session::Session) -> String {
+    template.replace("{csrf_token}", &session.csrf_token)
+}
+
+// every htmx request on the page carries the session's csrf token,
+// forms get theirs from with_csrf
+pub fn document_authenticated(
+    title: String,
+    user: &models::user::User,
+    session: &models::session::Session,
+    content: String,
+) -> String {
     let head = head(title);
     let footer = footer();
     let header = header_authenticated(user);
+    let csrf_token = &session.csrf_token;
     format!(
         "<!DOCTYPE html>
 <html lang='en'>
 {head}
-<body>
+<body hx-headers='{{\"X-CSRF-Token\": \"{csrf_token}\"}}'>
 <div>
 {header}
 <main id='content' class='content'>
@@ -123,7 +150,7 @@ pub fn document_authenticated(title: String, user: &models::user::User, content:
 </main>
 {footer}
 </div>
-<script>sal();</script>
+<script>sal(); document.body.addEventListener('htmx:afterSettle', () => sal());</script>
 </body>
 </html>"
     )
@@ -1,4 +1,4 @@
-use crate::models;
+use crate::{models, views};
 use std::include_str;
 
 pub fn link(
@@ -15,8 +15,12 @@ pub fn link_authenticated(
     _i: usize,
     link: &models::link::Link,
     page_link: &models::page_link::PageLink,
+    session: &models::session::Session,
 ) -> String {
-    page_link.inject_values(&link.inject_values(include_str!("link-authenticated.html")))
+    page_link.inject_values(&link.inject_values(&views::body::with_csrf(
+        include_str!("link-authenticated.html"),
+        session,
+    )))
     // .replace("{y}", &(i + 1).to_string())
     // .replace("{x}", &random(5, 1).to_string())
 }
@@ -24,6 +28,7 @@ pub fn link_authenticated(
 pub fn links_to_list_authenticated(
     links: Vec<(models::link::Link, models::page_link::PageLink)>,
     expanded_page: &models::page::ExpandedPage,
+    session: &models::session::Session,
 ) -> String {
     if links.len() != 0 {
         links
@@ -32,7 +37,7 @@ pub fn links_to_list_authenticated(
             .map(|(i, (link, page_link))| {
                 expanded_page
                     .page
-                    .inject_values(&link_authenticated(i, link, page_link))
+                    .inject_values(&link_authenticated(i, link, page_link, session))
             })
             .collect::<String>()
     } else {
@@ -53,10 +58,74 @@ pub fn links_to_list(
             .map(|(i, (da_link, page_link))| {
                 expanded_page
                     .page
-                    .inject_values(&link(i, &da_link, page_link))
+                    .inject_values(&link(i, da_link, page_link))
             })
             .collect::<String>()
     } else {
         String::from("<div class='neubrutalist-card'><h5 class='empty-error'>This page does not have any links, yet.</h5></div>")
     }
 }
+
+pub fn more_links(
+    links: Vec<(models::link::Link, models::page_link::PageLink)>,
+    expanded_page: &models::page::ExpandedPage,
+    cursor: Option<models::page_link::LinkCursor>,
+) -> String {
+    links
+        .iter()
+        .enumerate()
+        .map(|(i, (da_link, page_link))| {
+            expanded_page
+                .page
+                .inject_values(&link(i, da_link, page_link))
+        })
+        .collect::<String>()
+        + &load_more_links(expanded_page, cursor)
+}
+
+// the owner's screens keep their edit forms, and come from a route only the owner can use
+pub fn more_links_authenticated(
+    links: Vec<(models::link::Link, models::page_link::PageLink)>,
+    expanded_page: &models::page::ExpandedPage,
+    session: &models::session::Session,
+    cursor: Option<models::page_link::LinkCursor>,
+) -> String {
+    links
+        .iter()
+        .enumerate()
+        .map(|(i, (link, page_link))| {
+            expanded_page
+                .page
+                .inject_values(&link_authenticated(i, link, page_link, session))
+        })
+        .collect::<String>()
+        + &load_more_links_authenticated(expanded_page, cursor)
+}
+
+pub fn load_more_links_authenticated(
+    expanded_page: &models::page::ExpandedPage,
+    cursor: Option<models::page_link::LinkCursor>,
+) -> String {
+    match cursor {
+        Some(cursor) => views::page::load_more(format!(
+            "/page/{}/links/edit?cursor={}",
+            expanded_page.page.id,
+            cursor.to_param()
+        )),
+        None => String::new(),
+    }
+}
+
+pub fn load_more_links(
+    expanded_page: &models::page::ExpandedPage,
+    cursor: Option<models::page_link::LinkCursor>,
+) -> String {
+    match cursor {
+        Some(cursor) => views::page::load_more(format!(
+            "/page/{}/links?cursor={}",
+            expanded_page.page.id,
+            cursor.to_param()
+        )),
+        None => String::new(),
+    }
+}
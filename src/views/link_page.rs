@@ -4,12 +4,14 @@ use std::include_str;
 pub fn link_page(
     link: &models::link::Link,
     user: &models::user::User,
+    session: &models::session::Session,
     pages: &str,
     add_to_my_page_form: &str,
 ) -> String {
     views::body::document_authenticated(
         String::from("Link View"),
         user,
+        session,
         user.inject_values(&link.inject_values(include_str!("link-page-authenticated.html")))
             .replace("{pages}", pages)
             .replace("{add-to-my-page-form}", add_to_my_page_form)
@@ -26,7 +28,66 @@ pub fn link_page_unauthenticated(link: &models::link::Link, pages: &str) -> Stri
     )
 }
 
-pub fn add_to_my_page(link: &models::link::Link, options: String) -> String {
-    link.inject_values(include_str!("add-to-my-page.html"))
-        .replace("{options}", &options)
+pub fn add_to_my_page(
+    link: &models::link::Link,
+    options: String,
+    cursor: Option<i32>,
+    session: &models::session::Session,
+) -> String {
+    link.inject_values(&views::body::with_csrf(
+        include_str!("add-to-my-page.html"),
+        session,
+    ))
+    .replace("{options}", &options)
+    .replace(
+        "{more_options}",
+        &views::page::load_more_page_options(cursor),
+    )
+}
+
+#[test]
+fn test_csrf_token_stays_out_of_link_values() {
+    use diesel::Connection;
+
+    let Some(mut conn) = crate::db_conn::test_connection() else {
+        return;
+    };
+    conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+        let user = models::user::insert_test_user(conn, "csrf", "password");
+        let session = models::session::NewSession::new(
+            user.id,
+            String::from("token-hash"),
+            chrono::Duration::hours(1),
+            chrono::Duration::hours(1),
+            false,
+            models::session::ClientInfo::new(None, None),
+        )
+        .insert(conn)?;
+        let link = models::link::NewLink {
+            url: String::from("https://example.com/?t={csrf_token}"),
+            creator_user_id: user.id,
+            created_at: crate::utils::now(),
+            updated_at: None,
+            deleted_at: None,
+            img_url: Some(String::from("https://evil.example/x?t={csrf_token}")),
+            title: Some(String::from("{csrf_token}")),
+            description: None,
+        }
+        .insert(conn)?;
+
+        let html = link_page(
+            &link,
+            &user,
+            &session,
+            "",
+            &add_to_my_page(&link, String::new(), None, &session),
+        );
+
+        assert!(html.contains("https://evil.example/x?t={csrf_token}"));
+        assert!(html.contains("https://example.com/?t={csrf_token}"));
+        assert!(!html.contains(&format!("x?t={}", session.csrf_token)));
+        // the form still gets the real token
+        assert!(html.contains(&format!("value='{}'", session.csrf_token)));
+        Ok(())
+    });
 }
@@ -1,6 +1,9 @@
+pub mod admin;
 pub mod body;
 pub mod error;
 pub mod link;
 pub mod link_page;
 pub mod page;
+pub mod session;
+pub mod trash;
 pub mod user;
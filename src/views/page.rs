@@ -4,17 +4,26 @@ use crate::{
 };
 use std::include_str;
 
+// the first screen of groups for a picker, the rest are appended as options
+pub struct PageOptions {
+    pub pages: Vec<models::page::Page>,
+    pub cursor: Option<i32>,
+}
+
 pub fn view(
     expanded_user: models::user::ExpandedUser,
     expanded_page: models::page::ExpandedPage,
     links: Vec<(models::link::Link, models::page_link::PageLink)>,
+    cursor: Option<models::page_link::LinkCursor>,
     message: &str,
 ) -> String {
-    let links_html = views::link::links_to_list(links, &expanded_page);
+    let links_html = views::link::links_to_list(links, &expanded_page)
+        + &views::link::load_more_links(&expanded_page, cursor);
 
     views::body::document_authenticated(
         expanded_page.page.name.clone(),
         &expanded_user.user,
+        &expanded_user.session,
         expanded_page
             .page
             .inject_values(include_str!("page.html"))
@@ -27,9 +36,11 @@ pub fn view(
 pub fn view_unauthenticated(
     expanded_page: models::page::ExpandedPage,
     links: Vec<(models::link::Link, models::page_link::PageLink)>,
+    cursor: Option<models::page_link::LinkCursor>,
     message: &str,
 ) -> String {
-    let links_html = views::link::links_to_list(links, &expanded_page);
+    let links_html = views::link::links_to_list(links, &expanded_page)
+        + &views::link::load_more_links(&expanded_page, cursor);
 
     views::body::document(
         expanded_page.page.name.clone(),
@@ -46,34 +57,167 @@ pub fn view_authenticated(
     expanded_user: models::user::ExpandedUser,
     expanded_page: models::page::ExpandedPage,
     links: Vec<(models::link::Link, models::page_link::PageLink)>,
+    cursor: Option<models::page_link::LinkCursor>,
+    pages: PageOptions,
     message: &str,
+    bulk_results: &str,
 ) -> String {
-    let links_html = views::link::links_to_list_authenticated(links, &expanded_page);
+    let links_html =
+        views::link::links_to_list_authenticated(links, &expanded_page, &expanded_user.session)
+            + &views::link::load_more_links_authenticated(&expanded_page, cursor);
 
     views::body::document_authenticated(
         expanded_page.page.name.clone(),
         &expanded_user.user,
+        &expanded_user.session,
         expanded_user
             .user
-            .inject_values(
-                &expanded_page
-                    .page
-                    .inject_values(include_str!("page-authenticated.html")),
-            )
+            .inject_values(&expanded_page.page.inject_values(&views::body::with_csrf(
+                include_str!("page-authenticated.html"),
+                &expanded_user.session,
+            )))
             .replace("{links}", &links_html)
+            .replace(
+                "{page.url}",
+                &expanded_page.page.url(&expanded_user.user.username),
+            )
+            .replace(
+                "{visibility_options}",
+                &visibility_options(&expanded_page.page),
+            )
+            .replace(
+                "{target_page_options}",
+                &target_page_options(&pages, &expanded_page.page),
+            )
+            .replace(
+                "{more_target_page_options}",
+                &load_more_page_options(pages.cursor),
+            )
+            .replace("{bulk_results}", bulk_results)
             .replace("{error}", message)
             .replace("{background}", &expanded_page.background.to_call()),
     )
 }
 
-pub fn list_item(page: &models::page::Page) -> String {
+fn visibility_options(page: &models::page::Page) -> String {
+    [
+        models::page::PUBLIC,
+        models::page::UNLISTED,
+        models::page::PRIVATE,
+    ]
+    .iter()
+    .map(|visibility| {
+        format!(
+            "<option value='{0}'{1}>{0}</option>",
+            visibility,
+            if page.visibility == *visibility {
+                " selected"
+            } else {
+                ""
+            }
+        )
+    })
+    .collect::<String>()
+}
+
+// everywhere the selected links could go
+fn target_page_options(pages: &PageOptions, page: &models::page::Page) -> String {
+    pages
+        .pages
+        .iter()
+        .filter(|other| other.id != page.id)
+        .map(option_item)
+        .collect::<String>()
+}
+
+pub fn bulk_results(results: &[models::page_link::BulkLinkResult]) -> String {
+    results
+        .iter()
+        .map(|result| {
+            include_str!("bulk-link-result.html")
+                .replace("{result.name}", &result.name)
+                .replace("{result.outcome}", result.outcome)
+        })
+        .collect::<String>()
+}
+
+pub fn list_item(page: &models::page::Page, username: &str) -> String {
     page.inject_values(include_str!("page-list-item.html"))
+        .replace("{page.url}", &page.url(username))
 }
 
-pub fn list_item_authenticated(page: &models::page::Page) -> String {
+pub fn list_item_authenticated(page: &models::page::Page, username: &str) -> String {
     page.inject_values(include_str!("page-list-item-authenticated.html"))
+        .replace("{page.url}", &page.url(username))
+}
+
+// htmx swaps the button for the next screen of the list, which brings its own button
+pub fn load_more(url: String) -> String {
+    include_str!("load-more.html").replace("{url}", &url)
+}
+
+pub fn load_more_pages(path: &str, cursor: Option<i32>) -> String {
+    match cursor {
+        Some(cursor) => load_more(format!("{}?cursor={}", path, cursor)),
+        None => String::new(),
+    }
 }
 
 pub fn option_item(page: &models::page::Page) -> String {
     page.inject_values(include_str!("page-option-item.html"))
 }
+
+pub fn more_page_options(pages: Vec<models::page::Page>, cursor: Option<i32>) -> String {
+    pages.iter().map(option_item).collect::<String>()
+        + &match cursor {
+            Some(_) => load_more_options(cursor, " hx-swap-oob='true'"),
+            None => String::from("<span id='morePageOptions' hx-swap-oob='true'></span>"),
+        }
+}
+
+// a select can't hold a load more button, so it sits right after the select and
+// every screen of options swaps in the button for the next one
+pub fn load_more_page_options(cursor: Option<i32>) -> String {
+    load_more_options(cursor, "")
+}
+
+fn load_more_options(cursor: Option<i32>, oob: &str) -> String {
+    match cursor {
+        Some(cursor) => include_str!("load-more-options.html")
+            .replace("{url}", &format!("/user/pages/options?cursor={}", cursor))
+            .replace("{oob}", oob),
+        None => String::new(),
+    }
+}
+
+#[test]
+fn test_more_page_options_swap_the_button() {
+    let page = models::page::Page {
+        id: 7,
+        name: String::from("Reading list"),
+        description: String::new(),
+        user_id: 1,
+        created_at: crate::utils::now(),
+        updated_at: None,
+        deleted_at: None,
+        background_id: 1,
+        visibility: String::from(models::page::PUBLIC),
+        hidden: false,
+        slug: String::from("reading-list"),
+    };
+
+    // the first screen's button is plain, later screens replace it out of band
+    let first = load_more_page_options(Some(7));
+    assert!(first.contains("/user/pages/options?cursor=7"));
+    assert!(!first.contains("hx-swap-oob"));
+
+    let more = more_page_options(vec![page.clone()], Some(7));
+    assert!(more.starts_with("<option value=\"7\">Reading list</option>"));
+    assert!(more.contains("id=\"morePageOptions\""));
+    assert!(more.contains("hx-swap-oob='true'"));
+
+    // the last screen takes the button away
+    let last = more_page_options(vec![page], None);
+    assert!(last.ends_with("<span id='morePageOptions' hx-swap-oob='true'></span>"));
+    assert_eq!(load_more_page_options(None), "");
+}
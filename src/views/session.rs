@@ -0,0 +1,29 @@
+use crate::{models, views};
+use std::include_str;
+
+pub fn list(
+    user: &models::user::User,
+    background: &models::background::Background,
+    current: &models::session::Session,
+    sessions: Vec<models::session::Session>,
+) -> String {
+    let sessions_html = sessions
+        .iter()
+        .map(|session| {
+            if session.id == current.id {
+                session.inject_values(include_str!("session-list-item-current.html"))
+            } else {
+                session.inject_values(include_str!("session-list-item.html"))
+            }
+        })
+        .collect::<String>();
+
+    views::body::document_authenticated(
+        String::from("Sessions"),
+        user,
+        current,
+        user.inject_values(include_str!("sessions.html"))
+            .replace("{sessions}", &sessions_html)
+            .replace("{background}", &background.to_call()),
+    )
+}
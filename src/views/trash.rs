@@ -0,0 +1,60 @@
+use crate::{models, views};
+use std::include_str;
+
+pub fn list(
+    user: &models::user::User,
+    background: &models::background::Background,
+    session: &models::session::Session,
+    pages: Vec<models::page::Page>,
+    page_links: Vec<(models::page_link::PageLink, String)>,
+    hidden_links: Vec<(models::page_link::PageLink, String)>,
+    message: &str,
+) -> String {
+    let pages_html = if pages.len() != 0 {
+        pages
+            .iter()
+            .map(|page| page.inject_values(include_str!("trash-page-item.html")))
+            .collect::<String>()
+    } else {
+        String::from("<li><h5 class='empty-error'>No deleted groups.</h5></li>")
+    };
+    let links_html = if page_links.len() != 0 {
+        page_links
+            .iter()
+            .map(|(page_link, page_name)| {
+                page_link
+                    .inject_values(include_str!("trash-link-item.html"))
+                    .replace("{page.name}", page_name)
+            })
+            .collect::<String>()
+    } else {
+        String::from("<li><h5 class='empty-error'>No deleted links.</h5></li>")
+    };
+    // only there when a moderator has taken something down, and there's nothing to do but read why
+    let hidden_html = if hidden_links.len() != 0 {
+        String::from("<h5>Hidden by a moderator</h5><ul class='passkey-list'>")
+            + &hidden_links
+                .iter()
+                .map(|(page_link, page_name)| {
+                    page_link
+                        .inject_values(include_str!("trash-hidden-link-item.html"))
+                        .replace("{page.name}", page_name)
+                })
+                .collect::<String>()
+            + "</ul>"
+    } else {
+        String::new()
+    };
+
+    views::body::document_authenticated(
+        String::from("Trash"),
+        user,
+        session,
+        user.inject_values(include_str!("trash.html"))
+            .replace("{pages}", &pages_html)
+            .replace("{links}", &links_html)
+            .replace("{hidden_links}", &hidden_html)
+            .replace("{error}", message)
+            .replace("{background}", &background.to_call()),
+    )
+}
@@ -1,20 +1,58 @@
-use crate::{models, views};
+use crate::{
+    config::{OAuthProviderConfig, Quota},
+    models,
+    utils::sanitize_html,
+    validation, views,
+};
 use std::include_str;
+use totp_rs::TOTP;
 
+// the first screen of groups, with how many there are in all for the quota
+pub struct ProfilePages {
+    pub pages: Vec<models::page::Page>,
+    pub cursor: Option<i32>,
+    pub count: usize,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn profile(
     user: models::user::User,
     background: models::background::Background,
-    pages: Vec<models::page::Page>,
+    session: &models::session::Session,
+    pages: ProfilePages,
+    quota: &Quota,
+    passkeys: Vec<models::passkey::Passkey>,
+    api_tokens: Vec<models::api_token::ApiToken>,
+    new_api_token: Option<&str>,
     message: &str,
 ) -> String {
-    let pages_html = pages_authenticated(pages);
+    let usage = format!(
+        "{} of {} groups, up to {} links in each.",
+        pages.count, quota.max_pages, quota.max_links
+    );
+    let pages_html = pages_authenticated(pages.pages, &user.username)
+        + &views::page::load_more_pages("/user/pages", pages.cursor);
+    let passkeys_html = passkeys_to_list(passkeys);
+    let api_tokens_html = api_tokens_to_list(api_tokens);
+    let new_api_token_html = match new_api_token {
+        Some(token) => include_str!("api-token-created.html").replace("{token}", token),
+        None => String::new(),
+    };
     views::body::document_authenticated(
         String::from("Profile"),
         &user,
-        user.inject_values(include_str!("profile.html"))
-            .replace("{pages}", &pages_html)
-            .replace("{error}", message)
-            .replace("{background}", &background.to_call()),
+        session,
+        user.inject_values(&views::body::with_csrf(
+            include_str!("profile.html"),
+            session,
+        ))
+        .replace("{pages}", &pages_html)
+        .replace("{usage}", &usage)
+        .replace("{passkeys}", &passkeys_html)
+        .replace("{api_tokens}", &api_tokens_html)
+        .replace("{new_api_token}", &new_api_token_html)
+        .replace("{error}", message)
+        .replace("{background}", &background.to_call()),
     )
 }
 
@@ -27,20 +65,53 @@ pub fn login_form(message: &str) -> String {
     )
 }
 
-pub fn signup_form(message: &str) -> String {
+pub fn signup_form(message: &str, errors: &validation::FieldErrors, invite: &str) -> String {
     views::body::document(
         String::from("Signup"),
-        String::from(include_str!("signup.html"))
+        errors
+            .inject_values(include_str!("signup.html"))
+            .replace("{invite}", &sanitize_html(invite))
             .replace("{error}", message)
             .replace("{background}", &models::background::signup()),
     )
 }
 
-pub fn pages_authenticated(pages: Vec<models::page::Page>) -> String {
+pub fn forgot_password_form(message: &str) -> String {
+    views::body::document(
+        String::from("Forgot Password"),
+        String::from(include_str!("forgot.html"))
+            .replace("{error}", message)
+            .replace("{background}", &models::background::login()),
+    )
+}
+
+pub fn reset_password_form(token: &str, message: &str) -> String {
+    views::body::document(
+        String::from("Reset Password"),
+        String::from(include_str!("reset.html"))
+            .replace("{token}", &sanitize_html(token))
+            .replace("{error}", message)
+            .replace("{background}", &models::background::login()),
+    )
+}
+
+// loaded into the login form by htmx, empty when no providers are configured
+pub fn oauth_providers(providers: &[OAuthProviderConfig]) -> String {
+    providers
+        .iter()
+        .map(|provider| {
+            include_str!("oauth-provider.html")
+                .replace("{provider.slug}", &provider.slug)
+                .replace("{provider.name}", &sanitize_html(&provider.name))
+        })
+        .collect::<String>()
+}
+
+pub fn pages_authenticated(pages: Vec<models::page::Page>, username: &str) -> String {
     if pages.len() != 0 {
         pages
             .iter()
-            .map(|page| views::page::list_item_authenticated(page))
+            .map(|page| views::page::list_item_authenticated(page, username))
             .collect::<String>()
     } else {
         String::from(
@@ -48,3 +119,161 @@ pub fn pages_authenticated(pages: Vec<models::page::Page>) -> String {
         )
     }
 }
+
+pub fn more_pages(pages: Vec<models::page::Page>, username: &str, cursor: Option<i32>) -> String {
+    pages
+        .iter()
+        .map(|page| views::page::list_item_authenticated(page, username))
+        .collect::<String>()
+        + &views::page::load_more_pages("/user/pages", cursor)
+}
+
+pub fn passkeys_to_list(passkeys: Vec<models::passkey::Passkey>) -> String {
+    if passkeys.len() != 0 {
+        passkeys
+            .iter()
+            .map(|passkey| passkey.inject_values(include_str!("passkey-list-item.html")))
+            .collect::<String>()
+    } else {
+        String::from("<li><h5 class='empty-error'>You have no passkeys yet.</h5></li>")
+    }
+}
+
+pub fn api_tokens_to_list(api_tokens: Vec<models::api_token::ApiToken>) -> String {
+    if api_tokens.len() != 0 {
+        api_tokens
+            .iter()
+            .map(|api_token| api_token.inject_values(include_str!("api-token-list-item.html")))
+            .collect::<String>()
+    } else {
+        String::from("<li><h5 class='empty-error'>You have no API tokens yet.</h5></li>")
+    }
+}
+
+pub fn invites(
+    invites: Vec<models::invite::Invite>,
+    remaining: Option<usize>,
+    message: &str,
+) -> String {
+    let invites_html = if invites.len() != 0 {
+        invites
+            .iter()
+            .map(|invite| invite.inject_values(include_str!("invite-list-item.html")))
+            .collect::<String>()
+    } else {
+        String::from("<li><h5 class='empty-error'>You have no invites yet.</h5></li>")
+    };
+    let remaining = match remaining {
+        Some(remaining) => format!("You can create {} more.", remaining),
+        None => String::new(),
+    };
+
+    include_str!("invites.html")
+        .replace("{invites}", &invites_html)
+        .replace("{remaining}", &remaining)
+        .replace("{error}", message)
+}
+
+pub fn settings(
+    user: &models::user::User,
+    background: &models::background::Background,
+    session: &models::session::Session,
+    message: &str,
+) -> String {
+    views::body::document_authenticated(
+        String::from("Settings"),
+        user,
+        session,
+        user.inject_values(&views::body::with_csrf(
+            include_str!("settings.html"),
+            session,
+        ))
+        .replace("{error}", message)
+        .replace("{background}", &background.to_call()),
+    )
+}
+
+pub fn two_factor_form(message: &str) -> String {
+    views::body::document(
+        String::from("Two-Factor Login"),
+        String::from(include_str!("two-factor.html"))
+            .replace("{error}", message)
+            .replace("{background}", &models::background::login()),
+    )
+}
+
+pub fn two_factor_settings(
+    user: &models::user::User,
+    background: &models::background::Background,
+    session: &models::session::Session,
+    recovery_codes_left: i64,
+    message: &str,
+) -> String {
+    let two_factor_html = if user.has_two_factor() {
+        views::body::with_csrf(include_str!("two-factor-enabled.html"), session)
+            .replace("{recovery_codes_left}", &recovery_codes_left.to_string())
+    } else {
+        views::body::with_csrf(include_str!("two-factor-disabled.html"), session)
+    };
+
+    views::body::document_authenticated(
+        String::from("Two-Factor Login"),
+        user,
+        session,
+        user.inject_values(include_str!("two-factor-settings.html"))
+            .replace("{two_factor}", &two_factor_html)
+            .replace("{error}", message)
+            .replace("{background}", &background.to_call()),
+    )
+}
+
+pub fn two_factor_setup(
+    user: &models::user::User,
+    background: &models::background::Background,
+    session: &models::session::Session,
+    totp: &TOTP,
+    message: &str,
+) -> String {
+    let qr_code = totp.get_qr_base64().unwrap_or_else(|e| {
+        log::error!("{}", e);
+        String::new()
+    });
+
+    views::body::document_authenticated(
+        String::from("Two-Factor Setup"),
+        user,
+        session,
+        user.inject_values(&views::body::with_csrf(
+            include_str!("two-factor-setup.html"),
+            session,
+        ))
+        .replace("{qr_code}", &qr_code)
+        .replace("{secret}", &totp.get_secret_base32())
+        .replace("{otpauth_url}", &sanitize_html(&totp.get_url()))
+        .replace("{error}", message)
+        .replace("{background}", &background.to_call()),
+    )
+}
+
+pub fn recovery_codes(
+    user: &models::user::User,
+    background: &models::background::Background,
+    session: &models::session::Session,
+    codes: &[String],
+    message: &str,
+) -> String {
+    let codes_html = codes
+        .iter()
+        .map(|code| format!("<li><code>{}</code></li>", code))
+        .collect::<String>();
+
+    views::body::document_authenticated(
+        String::from("Recovery Codes"),
+        user,
+        session,
+        user.inject_values(include_str!("recovery-codes.html"))
+            .replace("{recovery_codes}", &codes_html)
+            .replace("{error}", message)
+            .replace("{background}", &background.to_call()),
+    )
+}
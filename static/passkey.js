@@ -0,0 +1,123 @@
+// webauthn-rs speaks base64url, the browser speaks ArrayBuffers
+const fromBase64Url = (value) => {
+    const base64 = value.replace(/-/g, '+').replace(/_/g, '/');
+    const padded = base64.padEnd(Math.ceil(base64.length / 4) * 4, '=');
+    return Uint8Array.from(atob(padded), (c) => c.charCodeAt(0));
+};
+
+const toBase64Url = (buffer) =>
+    btoa(String.fromCharCode(...new Uint8Array(buffer)))
+        .replace(/\+/g, '-')
+        .replace(/\//g, '_')
+        .replace(/=+$/, '');
+
+const showPasskeyError = (form, message) => {
+    const errorEl = form.querySelector('.error');
+    if (errorEl) {
+        errorEl.textContent = message;
+    }
+};
+
+// authenticated pages put the session's csrf token on the body for htmx, reuse it
+const csrfHeaders = () => {
+    const headers = document.body.getAttribute('hx-headers');
+    return headers ? JSON.parse(headers) : {};
+};
+
+const registerPasskey = async (event) => {
+    event.preventDefault();
+    const form = event.target;
+
+    try {
+        const start = await fetch('/user/passkey/register/start', {
+            method: 'POST',
+            headers: csrfHeaders(),
+        });
+        if (!start.ok) {
+            throw Error('Could not start passkey registration');
+        }
+
+        const options = await start.json();
+        options.publicKey.challenge = fromBase64Url(options.publicKey.challenge);
+        options.publicKey.user.id = fromBase64Url(options.publicKey.user.id);
+        (options.publicKey.excludeCredentials || []).forEach((credential) => {
+            credential.id = fromBase64Url(credential.id);
+        });
+
+        const credential = await navigator.credentials.create(options);
+        const finish = await fetch('/user/passkey/register/finish', {
+            method: 'POST',
+            headers: { ...csrfHeaders(), 'Content-Type': 'application/json' },
+            body: JSON.stringify({
+                name: new FormData(form).get('name'),
+                credential: {
+                    id: credential.id,
+                    rawId: toBase64Url(credential.rawId),
+                    type: credential.type,
+                    extensions: credential.getClientExtensionResults(),
+                    response: {
+                        attestationObject: toBase64Url(credential.response.attestationObject),
+                        clientDataJSON: toBase64Url(credential.response.clientDataJSON),
+                    },
+                },
+            }),
+        });
+        if (!finish.ok) {
+            throw Error('Your passkey could not be registered');
+        }
+
+        window.location.href = '/user';
+    } catch (e) {
+        showPasskeyError(form, `Error: ${e.message}`);
+    }
+};
+
+const loginWithPasskey = async (form) => {
+    if (!form.username.value) {
+        showPasskeyError(form, 'Error: Enter your username first');
+        return;
+    }
+
+    try {
+        const start = await fetch('/user/passkey/login/start', {
+            method: 'POST',
+            body: new URLSearchParams({ username: form.username.value }),
+        });
+        if (!start.ok) {
+            throw Error('No passkeys found for this user');
+        }
+
+        const options = await start.json();
+        options.publicKey.challenge = fromBase64Url(options.publicKey.challenge);
+        (options.publicKey.allowCredentials || []).forEach((credential) => {
+            credential.id = fromBase64Url(credential.id);
+        });
+
+        const assertion = await navigator.credentials.get(options);
+        const finish = await fetch('/user/passkey/login/finish', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({
+                id: assertion.id,
+                rawId: toBase64Url(assertion.rawId),
+                type: assertion.type,
+                extensions: assertion.getClientExtensionResults(),
+                response: {
+                    authenticatorData: toBase64Url(assertion.response.authenticatorData),
+                    clientDataJSON: toBase64Url(assertion.response.clientDataJSON),
+                    signature: toBase64Url(assertion.response.signature),
+                    userHandle: assertion.response.userHandle
+                        ? toBase64Url(assertion.response.userHandle)
+                        : null,
+                },
+            }),
+        });
+        if (!finish.ok) {
+            throw Error('Your passkey was not accepted');
+        }
+
+        window.location.href = '/user';
+    } catch (e) {
+        showPasskeyError(form, `Error: ${e.message}`);
+    }
+};
@@ -0,0 +1,24 @@
+// deletes answer with an HX-Trigger naming the url that restores what was deleted,
+// the toast waits for the swap to settle so it isn't wiped along with the old page
+document.addEventListener('trashed', (event) => {
+    const undo = event.detail.value;
+    document.addEventListener('htmx:afterSettle', () => showUndoToast(undo), { once: true });
+});
+
+const showUndoToast = (undo) => {
+    const toast = document.createElement('div');
+    toast.className = 'toast neubrutalist-card';
+    toast.setAttribute('role', 'status');
+    toast.textContent = 'Moved to the trash. ';
+
+    const button = document.createElement('button');
+    button.className = 'button-link';
+    button.textContent = 'Undo';
+    button.addEventListener('click', () => {
+        htmx.ajax('POST', undo, { source: document.body, target: 'body' });
+    });
+    toast.appendChild(button);
+
+    document.body.appendChild(toast);
+    setTimeout(() => toast.remove(), 8000);
+};